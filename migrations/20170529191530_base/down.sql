@@ -5,3 +5,4 @@ DROP TABLE IF EXISTS person_knows_person CASCADE;
 DROP TABLE IF EXISTS person_registered_for_event CASCADE;
 DROP TABLE IF EXISTS person_attended_for_talk CASCADE;
 DROP TABLE IF EXISTS person_rated_talk CASCADE;
+DROP TYPE IF EXISTS talk_status CASCADE;
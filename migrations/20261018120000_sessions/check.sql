@@ -0,0 +1,2 @@
+-- Just checks for existance of required tables in database
+SELECT 'sessions'::regclass;
@@ -0,0 +1,6 @@
+CREATE TABLE sessions (
+	token varchar PRIMARY KEY,
+	person_id integer NOT NULL REFERENCES persons (id),
+	created_at timestamp NOT NULL DEFAULT now(),
+	expires_at timestamp NOT NULL
+);
@@ -0,0 +1,3 @@
+ALTER TABLE talks DROP CONSTRAINT IF EXISTS talks_room_not_double_booked;
+ALTER TABLE talks DROP CONSTRAINT IF EXISTS talks_end_after_start;
+ALTER TABLE talks DROP COLUMN IF EXISTS end_timestamp;
@@ -0,0 +1,41 @@
+-- Talks registered before end times existed are assumed to take half an hour
+ALTER TABLE talks ADD COLUMN end_timestamp timestamp;
+UPDATE talks SET end_timestamp = start_timestamp + interval '30 minutes';
+
+-- Accepted talks starting at the same time in the same room can't be told apart
+DO $$
+BEGIN
+	IF EXISTS (
+		SELECT room, start_timestamp
+		FROM talks
+		WHERE status = 'Accepted'
+		  AND room IS NOT NULL
+		GROUP BY room, start_timestamp
+		HAVING count(*) > 1
+	) THEN
+		RAISE EXCEPTION 'accepted talks share a room and a start time, '
+			'reschedule them before migrating';
+	END IF;
+END $$;
+
+-- ...but otherwise end no later than the next accepted talk in their room starts
+UPDATE talks SET end_timestamp = next.start_timestamp
+FROM (
+	SELECT id, lead(start_timestamp) OVER (PARTITION BY room ORDER BY start_timestamp)
+		AS start_timestamp
+	FROM talks
+	WHERE status = 'Accepted'
+	  AND room IS NOT NULL
+) next
+WHERE next.id = talks.id
+  AND next.start_timestamp < talks.end_timestamp;
+
+ALTER TABLE talks ALTER COLUMN end_timestamp SET NOT NULL;
+ALTER TABLE talks ADD CONSTRAINT talks_end_after_start
+	CHECK (end_timestamp > start_timestamp);
+
+-- No two accepted talks may take the same room at the same time
+CREATE EXTENSION IF NOT EXISTS btree_gist;
+ALTER TABLE talks ADD CONSTRAINT talks_room_not_double_booked
+	EXCLUDE USING gist (room WITH =, tsrange(start_timestamp, end_timestamp) WITH &&)
+	WHERE (status = 'Accepted');
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS event_waitlist CASCADE;
+ALTER TABLE events DROP COLUMN IF EXISTS capacity;
@@ -0,0 +1,14 @@
+-- Every change of a rating in the order of `id`, NULL means that it was retracted
+CREATE TABLE rating_history (
+	id serial PRIMARY KEY,
+	person_id integer NOT NULL REFERENCES persons (id),
+	talk_id integer NOT NULL REFERENCES talks (id),
+	rating smallint CHECK (rating >= 0 AND rating <= 10),
+	changed_at timestamp NOT NULL DEFAULT now()
+);
+
+-- Ratings given before the history was kept
+INSERT INTO rating_history (person_id, talk_id, rating)
+SELECT person_id, talk_id, rating
+FROM person_rated_talk
+ORDER BY talk_id, person_id;
@@ -0,0 +1 @@
+ALTER TABLE person_rated_talk DROP COLUMN IF EXISTS comment;
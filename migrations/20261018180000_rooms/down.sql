@@ -0,0 +1,3 @@
+ALTER TABLE talks DROP CONSTRAINT IF EXISTS talks_room_fkey;
+DROP TABLE IF EXISTS rooms CASCADE;
+DROP TABLE IF EXISTS venues CASCADE;
@@ -0,0 +1,31 @@
+-- Names of venues and rooms are told apart regardless of case, `A1` and `a1` is one room
+CREATE TABLE venues (
+	id serial PRIMARY KEY,
+	venue varchar NOT NULL
+);
+CREATE UNIQUE INDEX venues_lower_venue_key ON venues (lower(venue));
+
+-- NULL capacity means that the room has no known number of seats
+CREATE TABLE rooms (
+	id serial PRIMARY KEY,
+	room varchar NOT NULL UNIQUE,
+	venue_id integer REFERENCES venues (id),
+	capacity integer CHECK (capacity > 0),
+	floor integer
+);
+CREATE UNIQUE INDEX rooms_lower_room_key ON rooms (lower(room));
+
+-- Rooms of talks registered before rooms existed, without a venue;
+-- the spellings of a room that differ only in case are merged into one
+INSERT INTO rooms (room)
+SELECT DISTINCT ON (lower(room)) room
+FROM talks
+WHERE room IS NOT NULL
+ORDER BY lower(room), room;
+UPDATE talks SET room = rooms.room
+FROM rooms
+WHERE lower(talks.room) = lower(rooms.room)
+  AND talks.room <> rooms.room;
+
+ALTER TABLE talks ADD CONSTRAINT talks_room_fkey
+	FOREIGN KEY (room) REFERENCES rooms (room) ON UPDATE CASCADE;
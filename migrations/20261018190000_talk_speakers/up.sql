@@ -0,0 +1,24 @@
+-- Everyone presenting a talk in the order of `position`; the primary speaker comes first
+-- and is also `talks.speaker_id`
+CREATE TABLE talk_speakers (
+	talk_id integer REFERENCES talks (id),
+	person_id integer REFERENCES persons (id),
+	role varchar NOT NULL CHECK (role IN ('primary', 'co_speaker', 'moderator')),
+	position smallint NOT NULL,
+	PRIMARY KEY (talk_id, person_id)
+);
+
+-- Talks registered before they could have more speakers
+INSERT INTO talk_speakers (talk_id, person_id, role, position)
+SELECT id, speaker_id, 'primary', 0
+FROM talks;
+
+-- Speakers of each talk as a JSON list of {"login", "role"} and their logins for searching
+CREATE VIEW talk_speaker_lists AS
+SELECT talk_id,
+       json_agg(json_build_object('login', login, 'role', role) ORDER BY position)::text
+           AS speakers,
+       string_agg(login, ' ' ORDER BY position) AS logins
+FROM talk_speakers
+  JOIN persons ON persons.id = talk_speakers.person_id
+GROUP BY talk_id;
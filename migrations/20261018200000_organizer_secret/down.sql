@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS organizer_attempts CASCADE;
+DROP TABLE IF EXISTS used_organizer_secrets CASCADE;
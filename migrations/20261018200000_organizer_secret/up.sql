@@ -0,0 +1,16 @@
+-- SHA-256 digests of the secrets an organizer account has been created with,
+-- each secret creates one account only
+CREATE TABLE used_organizer_secrets (
+	id serial PRIMARY KEY,
+	secret_digest varchar NOT NULL UNIQUE,
+	used_at timestamp NOT NULL DEFAULT now()
+);
+
+-- Every `organizer` request in the order of `id`, successful or not
+CREATE TABLE organizer_attempts (
+	id serial PRIMARY KEY,
+	newlogin varchar NOT NULL,
+	outcome varchar NOT NULL
+		CHECK (outcome IN ('created', 'no_secret', 'invalid_secret', 'used_secret', 'failed')),
+	attempted_at timestamp NOT NULL DEFAULT now()
+);
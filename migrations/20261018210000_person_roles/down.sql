@@ -0,0 +1,7 @@
+ALTER TABLE persons ADD COLUMN is_organizer boolean NOT NULL DEFAULT false;
+
+UPDATE persons
+SET is_organizer = TRUE
+WHERE id IN (SELECT person_id FROM person_roles WHERE role = 'admin' AND event_id IS NULL);
+
+DROP TABLE IF EXISTS person_roles;
@@ -0,0 +1,17 @@
+-- Roles of persons; a role with an `event_id` applies to that event only,
+-- one without it to every event
+CREATE TABLE person_roles (
+	person_id integer NOT NULL REFERENCES persons (id),
+	role varchar NOT NULL CHECK (role IN ('admin', 'program_committee', 'track_chair',
+	                                      'volunteer', 'speaker', 'attendee')),
+	event_id integer REFERENCES events (id)
+);
+
+CREATE UNIQUE INDEX person_roles_unique ON person_roles (person_id, role, COALESCE(event_id, 0));
+
+-- Organizers become admins and everyone else attendees of every event
+INSERT INTO person_roles (person_id, role)
+SELECT id, CASE WHEN is_organizer THEN 'admin' ELSE 'attendee' END
+FROM persons;
+
+ALTER TABLE persons DROP COLUMN is_organizer;
@@ -0,0 +1,8 @@
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS person_rated_talk;
+DROP TABLE IF EXISTS person_attended_for_talk;
+DROP TABLE IF EXISTS person_registered_for_event;
+DROP TABLE IF EXISTS person_knows_person;
+DROP TABLE IF EXISTS talks;
+DROP TABLE IF EXISTS events;
+DROP TABLE IF EXISTS persons;
@@ -0,0 +1,89 @@
+-- Same schema as the Postgres migrations up to 20261018130000_talk_end.
+-- Timestamps are kept as text in the `YYYY-MM-DD HH:MM:SS` format, so they compare in order.
+CREATE TABLE persons (
+	id integer PRIMARY KEY,
+	login text NOT NULL UNIQUE,
+	password text NOT NULL,
+	is_organizer boolean NOT NULL DEFAULT false
+);
+
+CREATE TABLE events (
+	id integer PRIMARY KEY,
+	eventname text NOT NULL UNIQUE,
+	start_timestamp text NOT NULL,
+	end_timestamp text NOT NULL
+);
+
+CREATE TABLE talks (
+	id integer PRIMARY KEY,
+	talk text NOT NULL UNIQUE,
+	status text NOT NULL CHECK (status IN ('Proposed', 'Accepted', 'Rejected')),
+	title text NOT NULL,
+	speaker_id integer NOT NULL REFERENCES persons (id),
+	event_id integer REFERENCES events (id),
+	room text,
+	start_timestamp text NOT NULL,
+	end_timestamp text NOT NULL CHECK (end_timestamp > start_timestamp),
+	modified_at text NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime'))
+);
+
+-- No two accepted talks may take the same room at the same time
+CREATE TRIGGER talks_room_not_double_booked_insert
+BEFORE INSERT ON talks
+WHEN NEW.status = 'Accepted'
+BEGIN
+	SELECT RAISE(ABORT, 'room is already taken')
+	WHERE EXISTS (
+		SELECT 1 FROM talks
+		WHERE status = 'Accepted'
+		  AND room = NEW.room
+		  AND talk <> NEW.talk
+		  AND start_timestamp < NEW.end_timestamp
+		  AND NEW.start_timestamp < end_timestamp);
+END;
+
+CREATE TRIGGER talks_room_not_double_booked_update
+BEFORE UPDATE ON talks
+WHEN NEW.status = 'Accepted'
+BEGIN
+	SELECT RAISE(ABORT, 'room is already taken')
+	WHERE EXISTS (
+		SELECT 1 FROM talks
+		WHERE status = 'Accepted'
+		  AND room = NEW.room
+		  AND id <> NEW.id
+		  AND start_timestamp < NEW.end_timestamp
+		  AND NEW.start_timestamp < end_timestamp);
+END;
+
+CREATE TABLE person_knows_person (
+	person1_id integer REFERENCES persons (id),
+	person2_id integer REFERENCES persons (id),
+	PRIMARY KEY (person1_id, person2_id)
+);
+
+CREATE TABLE person_registered_for_event (
+	person_id integer REFERENCES persons (id),
+	event_id integer REFERENCES events (id),
+	PRIMARY KEY (person_id, event_id)
+);
+
+CREATE TABLE person_attended_for_talk (
+	person_id integer REFERENCES persons (id),
+	talk_id integer REFERENCES talks (id),
+	PRIMARY KEY (person_id, talk_id)
+);
+
+CREATE TABLE person_rated_talk (
+	person_id integer REFERENCES persons (id),
+	talk_id integer REFERENCES talks (id),
+	rating smallint NOT NULL CHECK (rating >= 0 AND rating <= 10),
+	PRIMARY KEY (person_id, talk_id)
+);
+
+CREATE TABLE sessions (
+	token text PRIMARY KEY,
+	person_id integer NOT NULL REFERENCES persons (id),
+	created_at text NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime')),
+	expires_at text NOT NULL
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS event_waitlist;
+ALTER TABLE events DROP COLUMN capacity;
@@ -0,0 +1,10 @@
+-- NULL means that the event takes everyone
+ALTER TABLE events ADD COLUMN capacity integer CHECK (capacity > 0);
+
+-- Registrations past the capacity, served in the order of `id`
+CREATE TABLE event_waitlist (
+	id integer PRIMARY KEY,
+	person_id integer NOT NULL REFERENCES persons (id),
+	event_id integer NOT NULL REFERENCES events (id),
+	UNIQUE (person_id, event_id)
+);
@@ -0,0 +1,2 @@
+-- Optional written review accompanying the rating
+ALTER TABLE person_rated_talk ADD COLUMN comment text;
@@ -0,0 +1,4 @@
+DROP TRIGGER IF EXISTS talks_room_exists_update;
+DROP TRIGGER IF EXISTS talks_room_exists_insert;
+DROP TABLE IF EXISTS rooms;
+DROP TABLE IF EXISTS venues;
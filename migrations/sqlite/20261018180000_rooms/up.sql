@@ -0,0 +1,41 @@
+-- Names of venues and rooms are told apart regardless of case, `A1` and `a1` is one room
+CREATE TABLE venues (
+	id integer PRIMARY KEY,
+	venue text NOT NULL UNIQUE COLLATE NOCASE
+);
+
+-- NULL capacity means that the room has no known number of seats
+CREATE TABLE rooms (
+	id integer PRIMARY KEY,
+	room text NOT NULL UNIQUE COLLATE NOCASE,
+	venue_id integer REFERENCES venues (id),
+	capacity integer CHECK (capacity > 0),
+	floor integer
+);
+
+-- Rooms of talks registered before rooms existed, without a venue;
+-- the spellings of a room that differ only in case are merged into one
+INSERT INTO rooms (room)
+SELECT min(room)
+FROM talks
+WHERE room IS NOT NULL
+GROUP BY room COLLATE NOCASE;
+UPDATE talks SET room = (SELECT room FROM rooms WHERE rooms.room = talks.room)
+WHERE room IS NOT NULL;
+
+-- A column added to talks could not reference rooms, so triggers stand in for the key
+CREATE TRIGGER talks_room_exists_insert
+BEFORE INSERT ON talks
+WHEN NEW.room IS NOT NULL
+BEGIN
+	SELECT RAISE(ABORT, 'FOREIGN KEY constraint failed')
+	WHERE NOT EXISTS (SELECT 1 FROM rooms WHERE room = NEW.room);
+END;
+
+CREATE TRIGGER talks_room_exists_update
+BEFORE UPDATE OF room ON talks
+WHEN NEW.room IS NOT NULL
+BEGIN
+	SELECT RAISE(ABORT, 'FOREIGN KEY constraint failed')
+	WHERE NOT EXISTS (SELECT 1 FROM rooms WHERE room = NEW.room);
+END;
@@ -0,0 +1,2 @@
+DROP VIEW IF EXISTS talk_speaker_lists;
+DROP TABLE IF EXISTS talk_speakers;
@@ -0,0 +1,16 @@
+-- SHA-256 digests of the secrets an organizer account has been created with,
+-- each secret creates one account only
+CREATE TABLE used_organizer_secrets (
+	id integer PRIMARY KEY,
+	secret_digest text NOT NULL UNIQUE,
+	used_at text NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime'))
+);
+
+-- Every `organizer` request in the order of `id`, successful or not
+CREATE TABLE organizer_attempts (
+	id integer PRIMARY KEY,
+	newlogin text NOT NULL,
+	outcome text NOT NULL
+		CHECK (outcome IN ('created', 'no_secret', 'invalid_secret', 'used_secret', 'failed')),
+	attempted_at text NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime'))
+);
@@ -0,0 +1,15 @@
+-- Persons organizing an event; owners may also choose the other organizers
+CREATE TABLE event_organizers (
+	event_id integer NOT NULL REFERENCES events (id),
+	person_id integer NOT NULL REFERENCES persons (id),
+	is_owner boolean NOT NULL DEFAULT false,
+	PRIMARY KEY (event_id, person_id)
+);
+
+-- Existing events are owned by every admin, who could manage them so far
+INSERT INTO event_organizers (event_id, person_id, is_owner)
+SELECT events.id, person_roles.person_id, true
+FROM events
+  CROSS JOIN person_roles
+WHERE person_roles.role = 'admin'
+  AND person_roles.event_id IS NULL;
@@ -1,5 +1,6 @@
 use errors::*;
 use serde_json;
+use tabular::OutputFormat;
 
 pub use chrono::NaiveDateTime as DateTime;
 pub use chrono::NaiveDate as Date;
@@ -16,16 +17,16 @@ mod timestamp_fmt {
     use super::{Timestamp, Date, DateTime};
     use serde::{self, Deserialize, Deserializer};
 
-    const FORMAT_DATETIME: &'static str = "%Y-%m-%d %H:%M:%S";
-    const FORMAT_DATE: &'static str = "%Y-%m-%d";
+    const FORMAT_DATETIME: &str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT_DATE: &str = "%Y-%m-%d";
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Timestamp, D::Error>
         where D: Deserializer<'de>
     {
         let s = String::deserialize(deserializer)?;
         let datetime = DateTime::parse_from_str(&s, FORMAT_DATETIME)
-            .map(|dt| Timestamp::DateTime(dt));
-        let date = |_| Date::parse_from_str(&s, FORMAT_DATE).map(|d| Timestamp::Date(d));
+            .map(Timestamp::DateTime);
+        let date = |_| Date::parse_from_str(&s, FORMAT_DATE).map(Timestamp::Date);
         datetime.or_else(date).map_err(serde::de::Error::custom)
     }
 }
@@ -34,7 +35,7 @@ mod date_fmt {
     use super::Date;
     use serde::{self, Deserialize, Deserializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d";
+    const FORMAT: &str = "%Y-%m-%d";
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<Date, D::Error>
         where D: Deserializer<'de>
@@ -48,7 +49,7 @@ mod datetime_fmt {
     use super::DateTime;
     use serde::{self, Deserialize, Serializer, Deserializer};
 
-    const FORMAT: &'static str = "%Y-%m-%d %H:%M:%S";
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 
     pub fn serialize<S>(date: &DateTime, serializer: S) -> Result<S::Ok, S::Error>
         where S: Serializer
@@ -65,6 +66,42 @@ mod datetime_fmt {
     }
 }
 
+/// Same as `datetime_fmt` for optional fields, use together with `#[serde(default)]`.
+mod option_datetime_fmt {
+    use super::DateTime;
+    use serde::Deserializer;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime>, D::Error>
+        where D: Deserializer<'de>
+    {
+        super::datetime_fmt::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Same as `timestamp_fmt` for optional fields, use together with `#[serde(default)]`.
+mod option_timestamp_fmt {
+    use super::Timestamp;
+    use serde::Deserializer;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+        where D: Deserializer<'de>
+    {
+        super::timestamp_fmt::deserialize(deserializer).map(Some)
+    }
+}
+
+/// Same as `date_fmt` for optional fields, use together with `#[serde(default)]`.
+mod option_date_fmt {
+    use super::Date;
+    use serde::Deserializer;
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Date>, D::Error>
+        where D: Deserializer<'de>
+    {
+        super::date_fmt::deserialize(deserializer).map(Some)
+    }
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 #[serde(untagged)]
 pub enum StrOr<T> {
@@ -86,6 +123,20 @@ impl<T> StrOr<T>
     }
 }
 
+/// Identifies the caller of an authenticated request, either by login and password
+/// or by a token of a session opened with `login`.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum Credentials {
+    Password {
+        // `friends` names the caller `login1`
+        #[serde(alias = "login1")]
+        login: String,
+        password: String,
+    },
+    Token { token: String },
+}
+
 // ================================================
 //                    REQUESTS
 // ================================================
@@ -96,14 +147,22 @@ pub enum Request {
     Open(OpenInfo),
     Organizer(OrganizerInfo),
     Event(EventInfo),
+    EventCapacity(EventCapacityInfo),
+    CreateRoom(CreateRoomInfo),
+    ListRooms(ListRoomsInfo),
     User(UserInfo),
     Talk(TalkInfo),
     RegisterUserForEvent(RegisterUserForEventInfo),
+    UnregisterUserFromEvent(UnregisterUserFromEventInfo),
     Attendance(AttendanceInfo),
+    CancelAttendance(CancelAttendanceInfo),
     Evaluation(EvaluationInfo),
+    RetractEvaluation(RetractEvaluationInfo),
     Reject(RejectInfo),
+    Accept(AcceptInfo),
     Proposal(ProposalInfo),
     Friends(FriendsInfo),
+    Unfriend(UnfriendInfo),
     UserPlan(UserPlanInfo),
     DayPlan(DayPlanInfo),
     BestTalks(BestTalksInfo),
@@ -116,13 +175,53 @@ pub enum Request {
     FriendsTalks(FriendsTalksInfo),
     FriendsEvents(FriendsEventsInfo),
     RecommendedTalks(RecommendedTalksInfo),
+    Waitlist(WaitlistInfo),
+    RatingHistory(RatingHistoryInfo),
+    TalkReviews(TalkReviewsInfo),
+    MyFeedback(MyFeedbackInfo),
+    ExportIcal(ExportIcalInfo),
+    SearchTalks(SearchTalksInfo),
+    Login(LoginInfo),
+    Logout(LogoutInfo),
+    RevokeSessions(RevokeSessionsInfo),
+    GrantRole(GrantRoleInfo),
+    RevokeRole(RevokeRoleInfo),
+    GrantEventOrganizer(GrantEventOrganizerInfo),
+    RevokeEventOrganizer(RevokeEventOrganizerInfo),
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+impl Request {
+    /// Whether the request changes anything, as opposed to only reading data.
+    pub fn is_mutating(&self) -> bool {
+        use Request::*;
+        match *self {
+            Open(_) | Organizer(_) | Event(_) | EventCapacity(_) | CreateRoom(_) | User(_) |
+            Talk(_) | RegisterUserForEvent(_) | UnregisterUserFromEvent(_) | Attendance(_) |
+            CancelAttendance(_) | Evaluation(_) | RetractEvaluation(_) | Reject(_) |
+            Accept(_) | Proposal(_) | Friends(_) | Unfriend(_) | Login(_) | Logout(_) |
+            RevokeSessions(_) | GrantRole(_) | RevokeRole(_) | GrantEventOrganizer(_) |
+            RevokeEventOrganizer(_) => true,
+            UserPlan(_) | DayPlan(_) | BestTalks(_) | MostPopularTalks(_) | AttendedTalks(_) |
+            AbandonedTalks(_) | RecentlyAddedTalks(_) | RejectedTalks(_) | Proposals(_) |
+            FriendsTalks(_) | FriendsEvents(_) | RecommendedTalks(_) | Waitlist(_) |
+            RatingHistory(_) | TalkReviews(_) | MyFeedback(_) | ExportIcal(_) |
+            SearchTalks(_) | ListRooms(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq)]
 pub struct OpenInfo {
-    pub baza: String,
-    pub login: String,
-    pub password: String,
+    /// `postgres` (the default) or `sqlite`, which takes `baza` as the database file.
+    pub backend: Option<String>,
+    pub baza: Option<String>,
+    pub login: Option<String>,
+    pub password: Option<String>,
+    pub url: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<StrOr<u16>>,
+    pub sslmode: Option<String>,
+    pub application_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -134,80 +233,203 @@ pub struct OrganizerInfo {
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct EventInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub eventname: String,
     #[serde(with = "timestamp_fmt")]
     pub start_timestamp: Timestamp,
     #[serde(with = "timestamp_fmt")]
     pub end_timestamp: Timestamp,
+    /// Number of seats, unlimited if left out. Registrations past it go onto the waitlist.
+    pub capacity: Option<StrOr<u32>>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct EventCapacityInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub eventname: String,
+    /// Left out to lift the limit.
+    pub capacity: Option<StrOr<u32>>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct CreateRoomInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub room: String,
+    /// Created along with the room if it does not exist yet.
+    pub venue: String,
+    /// Number of seats, unknown if left out.
+    pub capacity: Option<StrOr<u32>>,
+    pub floor: Option<StrOr<i32>>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ListRoomsInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    /// Lists only the rooms of this venue.
+    pub venue: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct UserInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub newlogin: String,
     pub newpassword: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct TalkInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    /// The primary speaker.
     pub speakerlogin: String,
+    /// Further speakers, in order.
+    #[serde(default)]
+    pub speakers: Vec<SpeakerInfo>,
     pub talk: String,
     pub title: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    /// Either `end_timestamp` or `duration` in minutes, by default the talk
+    /// keeps the duration it was proposed with.
+    #[serde(default, with = "option_datetime_fmt")]
+    pub end_timestamp: Option<DateTime>,
+    pub duration: Option<StrOr<i64>>,
+    /// One of the rooms created with `create_room`, in any case.
     pub room: String,
     pub initial_evaluation: StrOr<i16>,
     pub eventname: String,
 }
 
+/// Role of a person presenting a talk.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeakerRole {
+    /// Named by `speakerlogin`, or the author of a proposal.
+    Primary,
+    CoSpeaker,
+    Moderator,
+}
+
+impl SpeakerRole {
+    /// The name of the role in requests, responses and the database.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            SpeakerRole::Primary => "primary",
+            SpeakerRole::CoSpeaker => "co_speaker",
+            SpeakerRole::Moderator => "moderator",
+        }
+    }
+}
+
+/// A speaker besides the primary one, either a login of a co-speaker
+/// or a login with a role.
+#[derive(Debug, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SpeakerInfo {
+    Login(String),
+    WithRole { login: String, role: SpeakerRole },
+}
+
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RegisterUserForEventInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub eventname: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct UnregisterUserFromEventInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub eventname: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct AttendanceInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub talk: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct CancelAttendanceInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub talk: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct EvaluationInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub talk: String,
     pub rating: StrOr<i16>,
+    /// Written review, replaced together with the rating.
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RetractEvaluationInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub talk: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RejectInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub talk: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct AcceptInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub talk: String,
+    /// Replaces the room preferred in the proposal.
+    pub room: Option<String>,
+    /// Left out to accept the talk without rating it.
+    pub initial_evaluation: Option<StrOr<i16>>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct ProposalInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub talk: String,
     pub title: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    /// Either `end_timestamp` or `duration` in minutes, 30 minutes by default.
+    #[serde(default, with = "option_datetime_fmt")]
+    pub end_timestamp: Option<DateTime>,
+    pub duration: Option<StrOr<i64>>,
+    /// Speakers besides the caller, who is the primary one.
+    #[serde(default)]
+    pub speakers: Vec<SpeakerInfo>,
+    /// The event the talk is meant for, running at `start_timestamp`.
+    pub eventname: Option<String>,
+    /// The preferred room, kept when the proposal is accepted with `accept`.
+    pub room: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct FriendsInfo {
-    pub login1: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub login2: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct UnfriendInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub login2: String,
 }
 
@@ -215,6 +437,8 @@ pub struct FriendsInfo {
 pub struct UserPlanInfo {
     pub login: String,
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -231,6 +455,8 @@ pub struct BestTalksInfo {
     pub end_timestamp: Timestamp,
     pub limit: StrOr<u32>,
     pub all: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -240,65 +466,218 @@ pub struct MostPopularTalksInfo {
     #[serde(with = "timestamp_fmt")]
     pub end_timestamp: Timestamp,
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct AttendedTalksInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct AbandonedTalksInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RecentlyAddedTalksInfo {
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RejectedTalksInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct ProposalsInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct FriendsTalksInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     #[serde(with = "timestamp_fmt")]
     pub start_timestamp: Timestamp,
     #[serde(with = "timestamp_fmt")]
     pub end_timestamp: Timestamp,
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct FriendsEventsInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     pub eventname: String,
 }
 
 #[derive(Debug, Deserialize, PartialEq)]
 pub struct RecommendedTalksInfo {
-    pub login: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub credentials: Credentials,
     #[serde(with = "timestamp_fmt")]
     pub start_timestamp: Timestamp,
     #[serde(with = "timestamp_fmt")]
     pub end_timestamp: Timestamp,
     pub limit: StrOr<u32>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct WaitlistInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub eventname: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RatingHistoryInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub talk: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct TalkReviewsInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub talk: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct MyFeedbackInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+}
+
+/// Status of talks as named in requests.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TalkStatusName {
+    Proposed,
+    Accepted,
+    Rejected,
+}
+
+/// Only organizers may search talks other than accepted ones.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct SearchTalksInfo {
+    #[serde(flatten)]
+    pub credentials: Option<Credentials>,
+    pub query: String,
+    #[serde(default, with = "option_timestamp_fmt")]
+    pub start_timestamp: Option<Timestamp>,
+    #[serde(default, with = "option_timestamp_fmt")]
+    pub end_timestamp: Option<Timestamp>,
+    pub status: Option<TalkStatusName>,
+    pub limit: Option<StrOr<u32>>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+}
+
+/// Exactly one of the fields selects the exported talks: those of the event `eventname`,
+/// of the day `timestamp`, or the plan of the user `login`.
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct ExportIcalInfo {
+    pub eventname: Option<String>,
+    #[serde(default, with = "option_date_fmt")]
+    pub timestamp: Option<Date>,
+    pub login: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct LoginInfo {
+    pub login: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct LogoutInfo {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RevokeSessionsInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+}
+
+/// What a person may do, see `storage::Permission`.
+#[derive(Debug, Clone, Copy, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Admin,
+    ProgramCommittee,
+    TrackChair,
+    Volunteer,
+    Speaker,
+    Attendee,
+}
+
+impl Role {
+    /// The name of the role in requests and the database.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Role::Admin => "admin",
+            Role::ProgramCommittee => "program_committee",
+            Role::TrackChair => "track_chair",
+            Role::Volunteer => "volunteer",
+            Role::Speaker => "speaker",
+            Role::Attendee => "attendee",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GrantRoleInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub personlogin: String,
+    pub role: Role,
+    /// Left out to grant the role in every event.
+    pub eventname: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RevokeRoleInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub personlogin: String,
+    pub role: Role,
+    pub eventname: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct GrantEventOrganizerInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub personlogin: String,
+    pub eventname: String,
+    /// Owners may choose the other organizers of the event.
+    #[serde(default)]
+    pub owner: bool,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct RevokeEventOrganizerInfo {
+    #[serde(flatten)]
+    pub credentials: Credentials,
+    pub personlogin: String,
+    pub eventname: String,
 }
 
 // ================================================
@@ -307,6 +686,8 @@ pub struct RecommendedTalksInfo {
 
 pub enum Response {
     Ok(ResponseInfo),
+    /// A page of a list, with the cursor of the next page unless it is the last one.
+    Page(ResponseInfo, Option<String>),
     NotImplemented,
 }
 
@@ -324,18 +705,85 @@ pub enum ResponseInfo {
     Proposals(Vec<Proposal>),
     FriendsTalks(Vec<FriendsTalk>),
     FriendsEvents(Vec<FriendsEvent>),
-    #[allow(dead_code)]
     RecommendedTalks(Vec<RecommendedTalk>),
+    Waitlist(Vec<WaitlistEntry>),
+    Rooms(Vec<Room>),
+    FoundTalks(Vec<FoundTalk>),
+    RatingHistory(Vec<RatingChange>),
+    TalkReviews(Vec<TalkReview>),
+    Feedback(Vec<Feedback>),
+    /// An RFC 5545 calendar.
+    Ical(String),
+    /// A list rendered by `tabular::tabulate`.
+    Csv(String),
+    Tsv(String),
+    Sessions(Vec<Session>),
     Empty,
 }
 
+impl ResponseInfo {
+    /// Names of the fields of the listed rows, in order, so that a table of an empty list
+    /// still has its header. `None` if the response is not a list.
+    pub fn columns(&self) -> Option<&'static [&'static str]> {
+        use self::ResponseInfo::*;
+        Some(match *self {
+                 AttendedTalks(_) | BestTalks(_) | MostPopularTalks(_) => {
+                     &["talk", "start_timestamp", "end_timestamp", "title", "room", "speakers"]
+                 }
+                 UserPlans(_) => {
+                     &["login", "talk", "start_timestamp", "end_timestamp", "title", "room",
+                       "speakers"]
+                 }
+                 DayPlans(_) => {
+                     &["talk", "start_timestamp", "end_timestamp", "title", "room", "venue",
+                       "capacity", "floor", "speakers"]
+                 }
+                 AbandonedTalks(_) => {
+                     &["talk", "start_timestamp", "end_timestamp", "title", "room", "number",
+                       "speakers"]
+                 }
+                 RecentlyAddedTalks(_) | FriendsTalks(_) => {
+                     &["talk", "speakerlogin", "start_timestamp", "end_timestamp", "title", "room",
+                       "speakers"]
+                 }
+                 RejectedTalks(_) | Proposals(_) => {
+                     &["talk", "speakerlogin", "start_timestamp", "end_timestamp", "title",
+                       "speakers"]
+                 }
+                 RecommendedTalks(_) | FoundTalks(_) => {
+                     &["talk", "speakerlogin", "start_timestamp", "end_timestamp", "title", "room",
+                       "score", "speakers"]
+                 }
+                 FriendsEvents(_) => &["login", "eventname", "friendlogin"],
+                 Waitlist(_) => &["login", "eventname", "position"],
+                 Rooms(_) => &["room", "venue", "capacity", "floor"],
+                 RatingHistory(_) => &["login", "rating", "changed_at", "average"],
+                 TalkReviews(_) => &["login", "rating", "comment", "attended"],
+                 Feedback(_) => &["talk", "rating", "comment"],
+                 Sessions(_) => &["token", "expires_at"],
+                 Ical(_) | Csv(_) | Tsv(_) | Empty => return None,
+             })
+    }
+}
+
+/// A person presenting a talk. Talks list their speakers in the order they were given,
+/// starting with the primary one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Speaker {
+    pub login: String,
+    pub role: SpeakerRole,
+}
+
 #[derive(Debug, Serialize, PartialEq)]
 pub struct AttendedTalk {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -344,8 +792,11 @@ pub struct UserPlan {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -353,8 +804,15 @@ pub struct DayPlan {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    /// `None` for rooms left over from before venues were recorded.
+    pub venue: Option<String>,
+    pub capacity: Option<i32>,
+    pub floor: Option<i32>,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -362,8 +820,11 @@ pub struct BestTalk {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -371,8 +832,11 @@ pub struct MostPopularTalk {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -380,9 +844,12 @@ pub struct AbandonedTalk {
     pub talk: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
     pub number: i64,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -391,8 +858,27 @@ pub struct RecentlyAddedTalk {
     pub speakerlogin: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FoundTalk {
+    pub talk: String,
+    pub speakerlogin: String,
+    #[serde(with = "datetime_fmt")]
+    pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
+    pub title: String,
+    /// Proposals have no room yet.
+    pub room: Option<String>,
+    /// Relevance to the query, comparable only within one search.
+    pub score: f64,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -401,7 +887,10 @@ pub struct RejectedTalk {
     pub speakerlogin: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -410,7 +899,10 @@ pub struct Proposal {
     pub speakerlogin: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -419,8 +911,11 @@ pub struct FriendsTalk {
     pub speakerlogin: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
+    pub speakers: Vec<Speaker>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
@@ -436,26 +931,135 @@ pub struct RecommendedTalk {
     pub speakerlogin: String,
     #[serde(with = "datetime_fmt")]
     pub start_timestamp: DateTime,
+    #[serde(with = "datetime_fmt")]
+    pub end_timestamp: DateTime,
     pub title: String,
     pub room: String,
     pub score: u32,
+    pub speakers: Vec<Speaker>,
 }
 
+#[derive(Debug, Serialize, PartialEq)]
+pub struct WaitlistEntry {
+    pub login: String,
+    pub eventname: String,
+    /// Counted from 1, the first person is promoted when a seat frees up.
+    pub position: i64,
+}
 
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Room {
+    pub room: String,
+    /// `None` for rooms left over from before venues were recorded.
+    pub venue: Option<String>,
+    pub capacity: Option<i32>,
+    pub floor: Option<i32>,
+}
 
-static SECRET: &str = "d8578edf8458ce06fbc5bb76a58c5ca4";
+#[derive(Debug, Serialize, PartialEq)]
+pub struct RatingChange {
+    pub login: String,
+    /// `None` when the rating was retracted.
+    pub rating: Option<i16>,
+    #[serde(with = "datetime_fmt")]
+    pub changed_at: DateTime,
+    /// Average rating of the talk right after the change.
+    pub average: Option<f64>,
+}
 
-pub fn read_call(data: &str) -> Result<Request> {
-    let info: Request = serde_json::from_str(data)?;
-    match &info {
-        &Request::Organizer(OrganizerInfo { ref secret, .. }) => {
-            if secret != SECRET {
-                bail!("invalid secret")
-            }
+#[derive(Debug, Serialize, PartialEq)]
+pub struct TalkReview {
+    pub login: String,
+    pub rating: i16,
+    pub comment: Option<String>,
+    /// Whether the reviewer's attendance at the talk was recorded.
+    pub attended: bool,
+}
+
+/// A review of the caller's talk, without the reviewer's login.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Feedback {
+    pub talk: String,
+    pub rating: i16,
+    pub comment: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct Session {
+    pub token: String,
+    #[serde(with = "datetime_fmt")]
+    pub expires_at: DateTime,
+}
+
+/// How an `organizer` request ended, as kept in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OrganizerOutcome {
+    Created,
+    /// No secret is configured, so no organizer can be created.
+    NoSecret,
+    InvalidSecret,
+    /// The secret has already created an organizer.
+    UsedSecret,
+    /// The secret was right, but the account could not be created, e.g. the login is taken.
+    Failed,
+}
+
+impl OrganizerOutcome {
+    /// The name of the outcome in the audit log and the database.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            OrganizerOutcome::Created => "created",
+            OrganizerOutcome::NoSecret => "no_secret",
+            OrganizerOutcome::InvalidSecret => "invalid_secret",
+            OrganizerOutcome::UsedSecret => "used_secret",
+            OrganizerOutcome::Failed => "failed",
         }
-        _ => {}
     }
-    Ok(info)
+}
+
+impl FromStr for OrganizerOutcome {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OrganizerOutcome> {
+        [OrganizerOutcome::Created,
+         OrganizerOutcome::NoSecret,
+         OrganizerOutcome::InvalidSecret,
+         OrganizerOutcome::UsedSecret,
+         OrganizerOutcome::Failed]
+            .iter()
+            .cloned()
+            .find(|outcome| outcome.as_str() == s)
+            .ok_or_else(|| format!("unknown organizer outcome `{}`", s).into())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OrganizerAttempt {
+    pub newlogin: String,
+    pub outcome: OrganizerOutcome,
+    pub attempted_at: DateTime,
+}
+
+/// Reads a request line. Any request may have a `format` field choosing the output format
+/// of its response, which is taken out of the request and returned next to it.
+pub fn read_formatted_call(data: &str) -> Result<(Request, Option<OutputFormat>)> {
+    let mut call: serde_json::Value = serde_json::from_str(data)?;
+    let format = call.as_object_mut()
+        .and_then(|call| call.values_mut().next())
+        .and_then(|fields| fields.as_object_mut())
+        .and_then(|fields| fields.remove("format"));
+    let format = match format {
+        Some(format) => Some(serde_json::from_value(format)?),
+        None => None,
+    };
+
+    let info: Request = serde_json::from_value(call)?;
+    Ok((info, format))
+}
+
+#[cfg(test)]
+pub fn read_call(data: &str) -> Result<Request> {
+    read_formatted_call(data).map(|(info, _)| info)
 }
 
 
@@ -471,24 +1075,29 @@ mod tests {
     fn deserialize_connection_info() {
         let data =
             r#"{ "open": { "baza": "stud", "login": "stud", "password": "d8578edf8458ce06fbc"}}"#;
-        let info: Request = read_call(&data).unwrap();
+        let info: Request = read_call(data).unwrap();
         assert!(info ==
                 Request::Open(OpenInfo {
-                                  baza: "stud".to_owned(),
-                                  login: "stud".to_owned(),
-                                  password: "d8578edf8458ce06fbc".to_owned(),
+                                  backend: None,
+                                  baza: Some("stud".to_owned()),
+                                  login: Some("stud".to_owned()),
+                                  password: Some("d8578edf8458ce06fbc".to_owned()),
+                                  url: None,
+                                  host: None,
+                                  port: None,
+                                  sslmode: None,
+                                  application_name: None,
                               }));
     }
 
     #[test]
-    fn deserialize_organizer_info_with_valid_secret() {
-        let mut data = r#"{ "organizer": { "secret": ""#.to_owned();
-        data += SECRET;
-        data += r#"", "newlogin": "organizer", "newpassword": "d8578edf8458ce06fbc"}}"#;
-        let info: Request = read_call(&data).unwrap();
+    fn deserialize_organizer_info() {
+        let data = r#"{ "organizer": { "secret": "d8578edf8458ce06fbc5bb76a58c5ca4",
+                        "newlogin": "organizer", "newpassword": "d8578edf8458ce06fbc"}}"#;
+        let info: Request = read_call(data).unwrap();
         assert!(info ==
                 Request::Organizer(OrganizerInfo {
-                                       secret: SECRET.to_owned(),
+                                       secret: "d8578edf8458ce06fbc5bb76a58c5ca4".to_owned(),
                                        newlogin: "organizer".to_owned(),
                                        newpassword: "d8578edf8458ce06fbc".to_owned(),
                                    }));
@@ -500,7 +1109,7 @@ mod tests {
             "start_timestamp": "2015-09-05 23:56:04",
             "end_timestamp": "2015-09-05 23:56:04",
             "limit": "42"}}"#;
-        let info: Request = read_call(&data).expect("json input needs a fix");
+        let info: Request = read_call(data).expect("json input needs a fix");
 
         let timestamp = DateTime::parse_from_str("2015-09-05 23:56:04", "%Y-%m-%d %H:%M:%S")
             .expect("not a timestamp");
@@ -509,22 +1118,27 @@ mod tests {
 
         assert!(info ==
                 Request::MostPopularTalks(MostPopularTalksInfo {
-                                              start_timestamp: start_timestamp,
-                                              end_timestamp: end_timestamp,
+                                              start_timestamp,
+                                              end_timestamp,
                                               limit: StrOr::Str("42".to_owned()),
+                                              cursor: None,
                                           }));
     }
 
     #[test]
-    fn deserialize_organizer_info_with_invalid_secret() {
-        let mut data = r#"{ "organizer": { "secret": ""#.to_owned();
-        data += "INVALIDSECRET";
-        data += r#"", "newlogin": "organizer", "newpassword": "d8578edf8458ce06fbc"}}"#;
-        let err = read_call(&data).unwrap_err();
-        match err {
-            Error(ErrorKind::Msg(msg), ..) => assert!(msg == "invalid secret"),
-            _ => assert!(false),
-        }
+    fn format_is_taken_out_of_any_request() {
+        let data = r#"{"proposals": {"login": "o", "password": "o", "format": "csv"}}"#;
+        let (info, format) = read_formatted_call(data).unwrap();
+        assert_eq!(format, Some(OutputFormat::Csv));
+        assert!(info ==
+                Request::Proposals(ProposalsInfo {
+                                       credentials: Credentials::Password {
+                                           login: "o".to_owned(),
+                                           password: "o".to_owned(),
+                                       },
+                                   }));
+        let data = r#"{"proposals": {"login": "o", "password": "o", "format": "xlsx"}}"#;
+        assert!(read_formatted_call(data).is_err());
     }
 
     #[test]
@@ -533,7 +1147,7 @@ mod tests {
                        "eventname": "Konwent",
                        "start_timestamp": "2016-01-20 10:00:00",
                        "end_timestamp": "2016-02-01 18:00:00"}}"#;
-        let info: Request = read_call(&data).expect("json input needs a fix");
+        let info: Request = read_call(data).expect("json input needs a fix");
 
         let timestamp = DateTime::parse_from_str("2016-01-20 10:00:00", "%Y-%m-%d %H:%M:%S")
             .expect("not a timestamp");
@@ -544,11 +1158,63 @@ mod tests {
 
         assert!(info ==
                 Request::Event(EventInfo {
-                                   login: "Donald_Grump11".to_owned(),
-                                   password: "admin".to_owned(),
+                                   credentials: Credentials::Password {
+                                       login: "Donald_Grump11".to_owned(),
+                                       password: "admin".to_owned(),
+                                   },
                                    eventname: "Konwent".to_owned(),
-                                   start_timestamp: start_timestamp,
-                                   end_timestamp: end_timestamp,
+                                   start_timestamp,
+                                   end_timestamp,
+                                   capacity: None,
                                }));
     }
+
+    #[test]
+    fn deserialize_proposal_end() {
+        let data = r#"{"proposal": {"login": "u", "password": "p", "talk": "t", "title": "T",
+                       "start_timestamp": "2016-01-20 10:00:00", "duration": "45"}}"#;
+        match read_call(data).expect("json input needs a fix") {
+            Request::Proposal(info) => {
+                assert!(info.end_timestamp.is_none());
+                assert!(info.duration == Some(StrOr::Str("45".to_owned())));
+            }
+            _ => panic!("unexpected request"),
+        }
+
+        let data = r#"{"proposal": {"login": "u", "password": "p", "talk": "t", "title": "T",
+                       "start_timestamp": "2016-01-20 10:00:00",
+                       "end_timestamp": "2016-01-20 11:00:00"}}"#;
+        let end_timestamp = DateTime::parse_from_str("2016-01-20 11:00:00", "%Y-%m-%d %H:%M:%S")
+            .expect("not a timestamp");
+        match read_call(data).expect("json input needs a fix") {
+            Request::Proposal(info) => {
+                assert!(info.end_timestamp == Some(end_timestamp));
+                assert!(info.duration.is_none());
+            }
+            _ => panic!("unexpected request"),
+        }
+    }
+
+    #[test]
+    fn deserialize_credentials() {
+        let data = r#"{"attended_talks": {"token": "Xw3k9"}}"#;
+        let info: Request = read_call(data).expect("json input needs a fix");
+        assert!(info ==
+                Request::AttendedTalks(AttendedTalksInfo {
+                                           credentials: Credentials::Token {
+                                               token: "Xw3k9".to_owned(),
+                                           },
+                                       }));
+
+        let data = r#"{"friends": {"login1": "u1", "password": "p", "login2": "u2"}}"#;
+        let info: Request = read_call(data).expect("json input needs a fix");
+        assert!(info ==
+                Request::Friends(FriendsInfo {
+                                     credentials: Credentials::Password {
+                                         login: "u1".to_owned(),
+                                         password: "p".to_owned(),
+                                     },
+                                     login2: "u2".to_owned(),
+                                 }));
+    }
 }
@@ -0,0 +1,442 @@
+use errors::*;
+use api::OpenInfo;
+
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Component, Path};
+use std::str::FromStr;
+use std::error::Error as StdError;
+
+use serde_json;
+use native_tls::{self, TlsConnector};
+use postgres::params::{ConnectParams, Host, IntoConnectParams};
+use postgres::tls::{Stream, TlsHandshake, TlsStream};
+
+const DEFAULT_HOST: &str = "localhost";
+const DEFAULT_PORT: u16 = 5432;
+const SQLITE_SCHEME: &str = "sqlite://";
+const SQLITE_MEMORY: &str = ":memory:";
+
+/// Whether the connection to the database is encrypted, named after libpq's `sslmode`.
+/// Like in libpq, only `verify-full` checks the server's certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyFull,
+}
+
+impl FromStr for SslMode {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<SslMode> {
+        match s {
+            "disable" => Ok(SslMode::Disable),
+            "prefer" => Ok(SslMode::Prefer),
+            "require" => Ok(SslMode::Require),
+            "verify-full" => Ok(SslMode::VerifyFull),
+            _ => {
+                let msg = "sslmode must be one of disable, prefer, require, verify-full";
+                bail!(ErrorKind::Validation(msg.into()))
+            }
+        }
+    }
+}
+
+/// Database engine the settings point at.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    Postgres,
+    Sqlite,
+}
+
+impl FromStr for Engine {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Engine> {
+        match s {
+            "postgres" => Ok(Engine::Postgres),
+            "sqlite" => Ok(Engine::Sqlite),
+            _ => bail!(ErrorKind::Validation("backend must be one of postgres, sqlite".into())),
+        }
+    }
+}
+
+/// Database connection settings. Every field is optional, so that settings coming
+/// from the `open` request, the environment, and a config file can be layered
+/// with `or`. A full connection `url` fills in whatever the other fields leave out.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ConnectionConfig {
+    pub backend: Option<Engine>,
+    pub url: Option<String>,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub database: Option<String>,
+    pub user: Option<String>,
+    pub password: Option<String>,
+    pub sslmode: Option<SslMode>,
+    pub application_name: Option<String>,
+    /// Not a connection setting: the secret that lets an `organizer` request create
+    /// an organizer account. Without it no organizer can be created.
+    pub organizer_secret: Option<String>,
+    /// Not a connection setting either: the directory holding SQLite files named
+    /// by the `open` request. Without it `open` may only choose `:memory:`.
+    pub sqlite_dir: Option<String>,
+    /// Not a connection setting either: `false` makes opening a database only check
+    /// that its schema is up to date, leaving the migrations to `rusty_ems migrate up`.
+    pub auto_migrate: Option<bool>,
+}
+
+impl ConnectionConfig {
+    /// Settings from the `open` request.
+    pub fn from_open(info: OpenInfo) -> Result<ConnectionConfig> {
+        Ok(ConnectionConfig {
+               backend: match info.backend {
+                   Some(backend) => Some(backend.parse()?),
+                   None => None,
+               },
+               url: info.url,
+               host: info.host,
+               port: match info.port {
+                   Some(port) => Some(port.validate()?),
+                   None => None,
+               },
+               database: info.baza,
+               user: info.login,
+               password: info.password,
+               sslmode: match info.sslmode {
+                   Some(sslmode) => Some(sslmode.parse()?),
+                   None => None,
+               },
+               application_name: info.application_name,
+               organizer_secret: None,
+               sqlite_dir: None,
+               auto_migrate: None,
+           })
+    }
+
+    /// Settings from the `open` request filled in with these ones. A SQLite file
+    /// named by the request rather than by these settings is looked up in `sqlite_dir`,
+    /// so that clients can't open or create files anywhere else.
+    pub fn for_open(&self, info: OpenInfo) -> Result<ConnectionConfig> {
+        let config = ConnectionConfig::from_open(info)?.or(self.clone());
+        let path = match config.sqlite_path()? {
+            Some(path) => path,
+            None => return Ok(config),
+        };
+        if path == SQLITE_MEMORY || Some(&path) == self.sqlite_path()?.as_ref() {
+            return Ok(config);
+        }
+        let inside = Path::new(&path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        let dir = match self.sqlite_dir {
+            Some(ref dir) if inside => dir,
+            _ => {
+                let msg = "open can only choose SQLite files in the configured sqlite_dir";
+                bail!(ErrorKind::Unauthorized(msg.into()))
+            }
+        };
+        Ok(ConnectionConfig {
+               backend: Some(Engine::Sqlite),
+               database: Some(Path::new(dir).join(path).to_string_lossy().into_owned()),
+               ..config
+           })
+    }
+
+    /// Settings from `DATABASE_URL` and the `PG*` variables known from libpq,
+    /// plus the organizer secret from `EMS_ORGANIZER_SECRET`, the directory
+    /// of SQLite files from `EMS_SQLITE_DIR` and `EMS_AUTO_MIGRATE`.
+    pub fn from_env() -> Result<ConnectionConfig> {
+        let var = |name| env::var(name).ok();
+        Ok(ConnectionConfig {
+               backend: None,
+               url: var("DATABASE_URL"),
+               host: var("PGHOST"),
+               port: match var("PGPORT") {
+                   Some(port) => Some(port.parse().chain_err(|| "invalid PGPORT")?),
+                   None => None,
+               },
+               database: var("PGDATABASE"),
+               user: var("PGUSER"),
+               password: var("PGPASSWORD"),
+               sslmode: match var("PGSSLMODE") {
+                   Some(sslmode) => Some(sslmode.parse().chain_err(|| "invalid PGSSLMODE")?),
+                   None => None,
+               },
+               application_name: var("PGAPPNAME"),
+               organizer_secret: var("EMS_ORGANIZER_SECRET"),
+               sqlite_dir: var("EMS_SQLITE_DIR"),
+               auto_migrate: match var("EMS_AUTO_MIGRATE") {
+                   Some(auto) => Some(auto.parse().chain_err(|| "invalid EMS_AUTO_MIGRATE")?),
+                   None => None,
+               },
+           })
+    }
+
+    /// Settings from a JSON file with the same field names as `ConnectionConfig`.
+    pub fn from_file(path: &str) -> Result<ConnectionConfig> {
+        let mut contents = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut contents))
+            .chain_err(|| format!("Unable to read config file {}", path))?;
+        serde_json::from_str(&contents).chain_err(|| format!("Invalid config file {}", path))
+    }
+
+    /// Fills the settings missing here with the ones from `other`.
+    pub fn or(self, other: ConnectionConfig) -> ConnectionConfig {
+        ConnectionConfig {
+            backend: self.backend.or(other.backend),
+            url: self.url.or(other.url),
+            host: self.host.or(other.host),
+            port: self.port.or(other.port),
+            database: self.database.or(other.database),
+            user: self.user.or(other.user),
+            password: self.password.or(other.password),
+            sslmode: self.sslmode.or(other.sslmode),
+            application_name: self.application_name.or(other.application_name),
+            organizer_secret: self.organizer_secret.or(other.organizer_secret),
+            sqlite_dir: self.sqlite_dir.or(other.sqlite_dir),
+            auto_migrate: self.auto_migrate.or(other.auto_migrate),
+        }
+    }
+
+    /// Path of the SQLite database if the settings select SQLite, either with `backend`
+    /// set to `sqlite` and the file given as `database`, or with a `sqlite://<file>` url.
+    pub fn sqlite_path(&self) -> Result<Option<String>> {
+        let url_path = self.url
+            .as_ref()
+            .and_then(|url| url.strip_prefix(SQLITE_SCHEME));
+        match self.backend {
+            Some(Engine::Postgres) => Ok(None),
+            Some(Engine::Sqlite) => {
+                let path = self.database.as_deref().or(url_path).ok_or_else(|| {
+                    ErrorKind::Validation("SQLite needs a database file".into())
+                })?;
+                Ok(Some(path.to_owned()))
+            }
+            None => Ok(url_path.map(str::to_owned)),
+        }
+    }
+
+    /// Resolves the settings into parameters accepted by `Connection::connect`.
+    pub fn connect_params(&self) -> Result<(ConnectParams, SslMode)> {
+        let url = match self.url {
+            Some(ref url) => {
+                Some(url.as_str()
+                         .into_connect_params()
+                         .map_err(|e| format!("invalid database url: {}", e))?)
+            }
+            None => None,
+        };
+        let url = url.as_ref();
+
+        let url_user = url.and_then(|p| p.user());
+        let user = self.user.as_deref().or_else(|| url_user.map(|u| u.name()));
+        let password = self.password.as_deref().or_else(|| url_user.and_then(|u| u.password()));
+        let database = self.database.as_deref().or_else(|| url.and_then(|p| p.database()));
+
+        let mut builder = ConnectParams::builder();
+        builder.port(self.port.or_else(|| url.map(|p| p.port())).unwrap_or(DEFAULT_PORT));
+        if let Some(user) = user {
+            builder.user(user, password);
+        }
+        if let Some(database) = database {
+            builder.database(database);
+        }
+
+        let mut sslmode = self.sslmode;
+        for (name, value) in url.map(|p| p.options()).unwrap_or(&[]) {
+            match &name[..] {
+                "sslmode" => sslmode = sslmode.or(Some(value.parse()?)),
+                "application_name" if self.application_name.is_some() => {}
+                _ => {
+                    builder.option(name, value);
+                }
+            }
+        }
+        if let Some(ref application_name) = self.application_name {
+            builder.option("application_name", application_name);
+        }
+
+        let host = match (&self.host, url) {
+            (Some(host), _) if host.starts_with('/') => Host::Unix(host.into()),
+            (Some(host), _) => Host::Tcp(host.clone()),
+            (None, Some(params)) => params.host().clone(),
+            (None, None) => Host::Tcp(DEFAULT_HOST.to_owned()),
+        };
+
+        Ok((builder.build(host), sslmode.unwrap_or(SslMode::Disable)))
+    }
+}
+
+/// `TlsHandshake` backed by the platform's TLS library through native-tls.
+pub struct NativeTls(TlsConnector);
+
+impl NativeTls {
+    pub fn new(verify: bool) -> Result<NativeTls> {
+        TlsConnector::builder()
+            .danger_accept_invalid_certs(!verify)
+            .danger_accept_invalid_hostnames(!verify)
+            .build()
+            .map(NativeTls)
+            .chain_err(|| "Unable to initialize TLS")
+    }
+}
+
+impl fmt::Debug for NativeTls {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("NativeTls").finish()
+    }
+}
+
+impl TlsHandshake for NativeTls {
+    fn tls_handshake(&self,
+                     domain: &str,
+                     stream: Stream)
+                     -> ::std::result::Result<Box<dyn TlsStream>, Box<dyn StdError + Sync + Send>> {
+        let stream = self.0
+            .connect(domain, stream)
+            .map_err(|e| e.to_string())?;
+        Ok(Box::new(NativeTlsStream(stream)))
+    }
+}
+
+#[derive(Debug)]
+struct NativeTlsStream(native_tls::TlsStream<Stream>);
+
+impl Read for NativeTlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Write for NativeTlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl TlsStream for NativeTlsStream {
+    fn get_ref(&self) -> &Stream {
+        self.0.get_ref()
+    }
+
+    fn get_mut(&mut self) -> &mut Stream {
+        self.0.get_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_settings_override_url() {
+        let config = ConnectionConfig {
+            url: Some("postgres://stud:secret@db:6543/ems?sslmode=require".to_owned()),
+            user: Some("admin".to_owned()),
+            application_name: Some("rusty_ems".to_owned()),
+            ..ConnectionConfig::default()
+        };
+        let (params, sslmode) = config.connect_params().expect("valid config");
+        assert_eq!(format!("{:?}", params.host()), r#"Tcp("db")"#);
+        assert_eq!(params.port(), 6543);
+        assert_eq!(params.user().map(|u| u.name()), Some("admin"));
+        assert_eq!(params.user().and_then(|u| u.password()), Some("secret"));
+        assert_eq!(params.database(), Some("ems"));
+        assert_eq!(params.options(),
+                   &[("application_name".to_owned(), "rusty_ems".to_owned())]);
+        assert_eq!(sslmode, SslMode::Require);
+    }
+
+    #[test]
+    fn defaults_to_unencrypted_localhost() {
+        let config = ConnectionConfig {
+            database: Some("stud".to_owned()),
+            ..ConnectionConfig::default()
+        }
+            .or(ConnectionConfig {
+                    database: Some("ignored".to_owned()),
+                    port: Some(5433),
+                    ..ConnectionConfig::default()
+                });
+        let (params, sslmode) = config.connect_params().expect("valid config");
+        assert_eq!(format!("{:?}", params.host()), r#"Tcp("localhost")"#);
+        assert_eq!(params.port(), 5433);
+        assert_eq!(params.database(), Some("stud"));
+        assert_eq!(sslmode, SslMode::Disable);
+    }
+
+    #[test]
+    fn selects_sqlite_by_url_or_backend() {
+        let config = ConnectionConfig {
+            url: Some("sqlite:///var/lib/ems.db".to_owned()),
+            ..ConnectionConfig::default()
+        };
+        assert_eq!(config.sqlite_path().unwrap(), Some("/var/lib/ems.db".to_owned()));
+
+        let config = ConnectionConfig {
+            backend: Some(Engine::Sqlite),
+            database: Some("ems.db".to_owned()),
+            ..ConnectionConfig::default()
+        }
+            .or(config);
+        assert_eq!(config.sqlite_path().unwrap(), Some("ems.db".to_owned()));
+
+        let config = ConnectionConfig {
+            url: Some("postgres://db/ems".to_owned()),
+            ..ConnectionConfig::default()
+        };
+        assert_eq!(config.sqlite_path().unwrap(), None);
+    }
+
+    #[test]
+    fn open_chooses_sqlite_files_in_sqlite_dir() {
+        let open = |backend: &str, baza: &str| {
+            OpenInfo {
+                backend: Some(backend.to_owned()),
+                baza: Some(baza.to_owned()),
+                ..OpenInfo::default()
+            }
+        };
+        let settings = ConnectionConfig::default();
+        let config = settings.for_open(open("sqlite", ":memory:")).unwrap();
+        assert_eq!(config.sqlite_path().unwrap(), Some(":memory:".to_owned()));
+        assert!(settings.for_open(open("sqlite", "ems.db")).is_err());
+
+        let settings = ConnectionConfig {
+            sqlite_dir: Some("/var/lib/ems".to_owned()),
+            ..ConnectionConfig::default()
+        };
+        let config = settings.for_open(open("sqlite", "ems.db")).unwrap();
+        assert_eq!(config.sqlite_path().unwrap(), Some("/var/lib/ems/ems.db".to_owned()));
+        for path in &["/tmp/evil.db", "../evil.db", "data/../../evil.db"] {
+            let e = settings.for_open(open("sqlite", path)).unwrap_err();
+            assert_eq!(e.classify().0, ErrorCode::Unauthorized);
+        }
+        let url = OpenInfo {
+            url: Some("sqlite:///tmp/evil.db".to_owned()),
+            ..OpenInfo::default()
+        };
+        assert!(settings.for_open(url).is_err());
+
+        let settings = ConnectionConfig {
+            url: Some("sqlite:///srv/ems.db".to_owned()),
+            ..ConnectionConfig::default()
+        };
+        let e = settings.for_open(open("sqlite", "other.db")).unwrap_err();
+        assert_eq!(e.classify().0, ErrorCode::Unauthorized);
+        let config = settings.for_open(OpenInfo::default()).unwrap();
+        assert_eq!(config.sqlite_path().unwrap(), Some("/srv/ems.db".to_owned()));
+    }
+}
@@ -1,68 +1,277 @@
 use errors::*;
 
-use api::{Date, DateTime};
-#[allow(unused_imports)]
+use api::{Date, DateTime, Credentials, Session, WaitlistEntry, RatingChange, TalkReview,
+          Feedback, TalkStatusName, Room, Speaker, SpeakerRole, OrganizerOutcome,
+          OrganizerAttempt, Role};
 use api::{AttendedTalk, UserPlan, DayPlan, BestTalk, MostPopularTalk, AbandonedTalk,
-          RecentlyAddedTalk, RejectedTalk, Proposal, FriendsTalk, FriendsEvent, RecommendedTalk};
+          RecentlyAddedTalk, RejectedTalk, Proposal, FriendsTalk, FriendsEvent, RecommendedTalk,
+          FoundTalk};
 
 pub use postgres::{Connection, TlsMode};
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use postgres::params::Host;
+use connection::{ConnectionConfig, NativeTls, SslMode};
+use migrate;
+use paging::{Key, KeyType, Keyset, Page, Paged, asc, desc};
+use storage::{Storage, Permission, SESSION_TTL_HOURS, DEFAULT_TALK_MINUTES, new_session_token, hash_password,
+              secret_digest, verify_unknown_login, constant_time_eq};
+
+use bcrypt;
+use serde_json;
 
 /// (*) open <baza> <login> <password>
 /// przekazuje dane umożliwiające podłączenie Twojego programu do bazy - nazwę bazy,
 /// login oraz hasło, wywoływane dokładnie jeden raz, w pierwszej linii wejścia
 /// zwraca status OK/ERROR w zależności od tego czy udało się nawiązać połączenie z bazą
-pub fn establish_connection(login: String, password: String, baza: String) -> Result<Connection> {
-    let database_url = format!("postgres://{}:{}@localhost/{}", login, password, baza);
-    Connection::connect(database_url.as_ref(), TlsMode::None)
-        .chain_err(|| format!("Error connecting to {}", database_url))
+///
+/// Ustawienia połączenia nie podane w zapytaniu pochodzą ze zmiennych środowiskowych
+/// lub pliku konfiguracyjnego, zob. `ConnectionConfig`.
+pub fn establish_connection(config: &ConnectionConfig) -> Result<Connection> {
+    let (params, sslmode) = config.connect_params()?;
+    let target = match *params.host() {
+        Host::Tcp(ref host) => format!("{}:{}", host, params.port()),
+        Host::Unix(ref path) => format!("{}:{}", path.display(), params.port()),
+    };
+    let tls = match sslmode {
+        SslMode::Disable => None,
+        SslMode::Prefer | SslMode::Require => Some(NativeTls::new(false)?),
+        SslMode::VerifyFull => Some(NativeTls::new(true)?),
+    };
+    let tls_mode = match (sslmode, tls.as_ref()) {
+        (SslMode::Prefer, Some(tls)) => TlsMode::Prefer(tls),
+        (SslMode::Require, Some(tls)) |
+        (SslMode::VerifyFull, Some(tls)) => TlsMode::Require(tls),
+        _ => TlsMode::None,
+    };
+    Connection::connect(params, tls_mode).chain_err(|| format!("Error connecting to {}", target))
 }
 
 /// Sprawdza, czy połączona baza zawiera odpowiednią strukturę tabel.
 /// Przeprowadza migrację jeśli jest taka potrzeba.
-/// Pozyskuje zawartość skryptu tworzącego bazę w trakcie kompilacji.
+/// Skrypty migracji są wkompilowane w program, zob. `migrate`.
 pub fn setup_database(conn: &Connection) -> Result<()> {
-    let up_sql = include_str!("../migrations/20170529191530_base/up.sql");
-    let check_sql = include_str!("../migrations/20170529191530_base/check.sql");
-    conn.batch_execute(check_sql)
-        .or_else(|_| conn.batch_execute(up_sql))
+    migrate::up(conn)
+        .map(|_| ())
         .chain_err(|| "Unable to setup a database")
 }
 
 /// (*) organizer <secret> <newlogin> <newpassword>
 /// tworzy uczestnika <newlogin> z uprawnieniami organizatora i hasłem <newpassword>,
-/// argument <secret> musi być równy d8578edf8458ce06fbc5bb76a58c5ca4 // zwraca status OK/ERROR
+/// argument <secret> musi być równy sekretowi z konfiguracji // zwraca status OK/ERROR
+///
+/// Sekret sprawdza `routes`, zob. `use_organizer_secret`.
 pub fn create_organizer_account(conn: &Connection,
                                 newlogin: String,
                                 newpassword: String)
                                 -> Result<()> {
 
+    let password_hash = hash_password(&newpassword)?;
+    let query = r#"
+        INSERT INTO persons (login, password)
+        VALUES ($1, $2)
+        RETURNING id"#;
+    let person_id: i32 = conn.query(query, &[&newlogin, &password_hash])
+        .chain_err(|| "Unable to insert organizer person")?
+        .get(0)
+        .get("id");
+
+    insert_role(conn, person_id, Role::Admin, None)
+}
+
+/// Zużywa sekret organizatora: każdy sekret tworzy co najwyżej jedno konto organizatora.
+/// Zwraca `false`, jeśli sekret był już użyty. Zapisywany jest tylko skrót sekretu.
+///
+/// Unikalny indeks na skrócie nie pozwala użyć sekretu dwa razy, także równolegle.
+pub fn use_organizer_secret(conn: &Connection, secret: &str) -> Result<bool> {
+    let query = r#"
+        INSERT INTO used_organizer_secrets (secret_digest)
+        VALUES ($1)
+        ON CONFLICT (secret_digest) DO NOTHING"#;
+    conn.execute(query, &[&secret_digest(secret)])
+        .map(|inserted| inserted == 1)
+        .chain_err(|| "Unable to mark the organizer secret as used")
+}
+
+/// Zapisuje w dzienniku próbę utworzenia konta organizatora <newlogin> i jej wynik.
+pub fn record_organizer_attempt(conn: &Connection,
+                                newlogin: &str,
+                                outcome: OrganizerOutcome)
+                                -> Result<()> {
     let query = r#"
-        INSERT INTO persons (login, password, is_organizer)
-        VALUES ($1, $2, TRUE)"#;
-    conn.execute(query, &[&newlogin, &newpassword])
+        INSERT INTO organizer_attempts (newlogin, outcome)
+        VALUES ($1, $2)"#;
+    conn.execute(query, &[&newlogin, &outcome.as_str()])
         .map(|_| ())
-        .chain_err(|| "Unable to insert organizer person")
+        .chain_err(|| "Unable to record the organizer attempt")
+}
+
+/// admin audit
+/// dziennik prób utworzenia konta organizatora, od najstarszej
+///  <newlogin> <outcome> <attempted_at>
+pub fn organizer_attempts(conn: &Connection) -> Result<Vec<OrganizerAttempt>> {
+    let query = r#"
+        SELECT newlogin, outcome, attempted_at
+        FROM organizer_attempts
+        ORDER BY id"#;
+    conn.query(query, &[])
+        .chain_err(|| "Unable to load organizer attempts")?
+        .iter()
+        .map(|row| {
+                 let outcome: String = row.get("outcome");
+                 Ok(OrganizerAttempt {
+                        newlogin: row.get("newlogin"),
+                        outcome: outcome.parse()?,
+                        attempted_at: row.get("attempted_at"),
+                    })
+             })
+        .collect()
 }
 
 /// (*O) event <login> <password> <eventname> <start_timestamp> <end_timestamp>
 /// rejestracja wydarzenia, napis <eventname> jest unikalny
+///
+/// Opcjonalny <capacity> ogranicza liczbę zarejestrowanych uczestników,
+/// kolejni trafiają na listę oczekujących.
+///
+/// Wywołujący zostaje właścicielem wydarzenia (zob. grant_event_organizer).
 pub fn create_event(conn: &Connection,
-                    login: String,
-                    password: String,
+                    credentials: Credentials,
                     eventname: String,
                     start_timestamp: DateTime,
-                    end_timestamp: DateTime)
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
                     -> Result<()> {
 
-    authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ManageEvents, None)?;
 
     // insert new event
     let query = r#"
-        INSERT INTO events (eventname, start_timestamp, end_timestamp)
-        VALUES ($1, $2, $3)"#;
-    conn.execute(query, &[&eventname, &start_timestamp, &end_timestamp])
+        INSERT INTO events (eventname, start_timestamp, end_timestamp, capacity)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id"#;
+    let event_id: i32 = conn.query(query,
+                                   &[&eventname, &start_timestamp, &end_timestamp, &capacity])
+        .chain_err(|| "Unable to insert event")?
+        .get(0)
+        .get("id");
+
+    insert_organizer(conn, event_id, person_id, true)
+}
+
+/// (O) event_capacity <login> <password> <eventname> <capacity>
+/// zmienia limit miejsc wydarzenia <eventname>, pominięty <capacity> znosi limit;
+/// zwolnione miejsca zajmują kolejne osoby z listy oczekujących
+///
+/// Zmniejszenie limitu nie wyrejestrowuje nikogo.
+pub fn set_event_capacity(conn: &Connection,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+
+    let query = r#"
+        UPDATE events
+        SET capacity = $1
+        WHERE eventname = $2
+        RETURNING id"#;
+    let event_id: i32 = conn.query(query, &[&capacity, &eventname])
+        .chain_err(|| "Unable to change the capacity")?
+        .iter()
+        .map(|row| row.get("id"))
+        .next()
+        .ok_or_else(|| {
+            ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+        })?;
+    authorize(conn, person_id, Permission::ManageEvents, Some(event_id))?;
+
+    promote_waitlisted(conn, event_id)
+}
+
+/// (O) create_room <login> <password> <room> <venue> [<capacity> <floor>]
+/// tworzy salę <room> w miejscu <venue>, które powstaje razem ze swoją pierwszą salą,
+/// nazwy sal i miejsc nie rozróżniają wielkości liter - `A1` i `a1` to ta sama sala
+///
+/// Opcjonalny <capacity> to liczba miejsc w sali, a <floor> - piętro.
+pub fn create_room(conn: &Connection,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ManageEvents, None)?;
+
+    if let Some(existing) = room_named(conn, &room)? {
+        bail!(ErrorKind::Conflict(format!("room `{}` already exists", existing)))
+    }
+
+    let query = r#"
+        INSERT INTO venues (venue)
+        VALUES ($1)
+        ON CONFLICT ((lower(venue))) DO NOTHING"#;
+    conn.execute(query, &[&venue])
+        .chain_err(|| "Unable to insert venue")?;
+
+    let query = r#"
+        INSERT INTO rooms (room, venue_id, capacity, floor)
+        SELECT $1, id, $3, $4
+        FROM venues
+        WHERE lower(venue) = lower($2)"#;
+    conn.execute(query, &[&room, &venue, &capacity, &floor])
         .map(|_| ())
-        .chain_err(|| "Unable to insert event")
+        .chain_err(|| "Unable to insert room")
+}
+
+/// (O) list_rooms <login> <password> [<venue>]
+/// zwraca listę sal posortowaną wg nazwy, z <venue> - tylko sale tego miejsca
+/// Atrybuty zwracanych krotek:
+///   <room> <venue> <capacity> <floor>
+pub fn list_rooms(conn: &Connection,
+                  credentials: Credentials,
+                  venue: Option<String>)
+                  -> Result<Vec<Room>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ScheduleTalks, None)?;
+
+    if let Some(ref venue) = venue {
+        let query = r#"
+            SELECT 1 FROM venues
+            WHERE lower(venue) = lower($1)"#;
+        if conn.query(query, &[venue])
+               .chain_err(|| "Unable to load venue")?
+               .is_empty() {
+            bail!(ErrorKind::NotFound(format!("venue with venue=`{}` not found", venue)))
+        }
+    }
+
+    let query = r#"
+        SELECT room, venue, capacity, floor
+        FROM rooms
+        LEFT JOIN venues ON venues.id = rooms.venue_id
+        WHERE $1::varchar IS NULL
+           OR lower(venue) = lower($1)
+        ORDER BY lower(room) COLLATE "C""#;
+    let rooms: Vec<_> = conn.query(query, &[&venue])
+        .chain_err(|| "Unable to load rooms")?
+        .iter()
+        .map(|row| {
+                 Room {
+                     room: row.get("room"),
+                     venue: row.get("venue"),
+                     capacity: row.get("capacity"),
+                     floor: row.get("floor"),
+                 }
+             })
+        .collect();
+
+    Ok(rooms)
 }
 
 /// (*O) user <login> <password> <newlogin> <newpassword>
@@ -72,21 +281,26 @@ pub fn create_event(conn: &Connection,
 /// <newlogin> <newpassword> są danymi nowego uczestnika,
 /// <newlogin> jest unikalny
 pub fn create_user(conn: &Connection,
-                   login: String,
-                   password: String,
+                   credentials: Credentials,
                    newlogin: String,
                    newpassword: String)
                    -> Result<()> {
 
-    authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ManageAccounts, None)?;
 
     // insert new person
+    let password_hash = hash_password(&newpassword)?;
     let query = r#"
-        INSERT INTO persons (login, password, is_organizer)
-        VALUES ($1, $2, FALSE)"#;
-    conn.execute(query, &[&newlogin, &newpassword])
-        .map(|_| ())
-        .chain_err(|| "Unable to insert User person")
+        INSERT INTO persons (login, password)
+        VALUES ($1, $2)
+        RETURNING id"#;
+    let person_id: i32 = conn.query(query, &[&newlogin, &password_hash])
+        .chain_err(|| "Unable to insert User person")?
+        .get(0)
+        .get("id");
+
+    insert_role(conn, person_id, Role::Attendee, None)
 }
 
 #[derive(Debug, ToSql, FromSql)]
@@ -97,6 +311,16 @@ pub enum TalkStatus {
     Rejected,
 }
 
+impl From<TalkStatusName> for TalkStatus {
+    fn from(status: TalkStatusName) -> TalkStatus {
+        match status {
+            TalkStatusName::Proposed => TalkStatus::Proposed,
+            TalkStatusName::Accepted => TalkStatus::Accepted,
+            TalkStatusName::Rejected => TalkStatus::Rejected,
+        }
+    }
+}
+
 /// (*O) talk <login> <password>
 ///     <speakerlogin> <talk> <title> <start_timestamp> <room> <initial_evaluation> <eventname>
 /// rejestracja referatu/zatwierdzenie referatu spontanicznego,
@@ -107,46 +331,130 @@ pub enum TalkStatus {
 ///     napisem, co oznacza, że referat nie jest przydzielony do jakiegokolwiek wydarzenia
 ///
 /// Referat, który jest przypisywany do wydarzenia, musi zaczynać się w czasie trwania wydarzenia.
+///
+/// Opcjonalny <end_timestamp> wyznacza koniec referatu, domyślnie referat spontaniczny zachowuje
+/// zaproponowany czas trwania, a nowy trwa DEFAULT_TALK_MINUTES minut. Zatwierdzone referaty
+/// nie mogą zajmować tej samej sali w tym samym czasie.
+///
+/// Sala <room> musi istnieć (zob. create_room), referat dostaje jej nazwę w pisowni
+/// z create_room.
+///
+/// Opcjonalna lista <speakers> zawiera kolejnych prelegentów - loginy współprowadzących
+/// (co_speaker) albo pary <login> <role>, gdzie <role> to co_speaker lub moderator;
+/// <speakerlogin> jest prelegentem głównym (primary). Lista zastępuje prelegentów
+/// zatwierdzanego referatu spontanicznego. Tutaj `speakers` zaczyna się od prelegenta
+/// głównego.
+#[allow(clippy::too_many_arguments)]
 pub fn register_or_accept_talk(conn: &Connection,
-                               login: String,
-                               password: String,
-                               speakerlogin: String,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
                                talk: String,
                                title: String,
                                start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
                                room: String,
                                initial_evaluation: i16,
                                eventname: String)
                                -> Result<()> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
-    let speaker_id = authorize_person_as(conn, &speakerlogin, None, PersonType::Whatever)?;
-    let event_id: Option<i32> = if !eventname.is_empty() {
-        let query = r#"
-            SELECT id FROM events
-            WHERE eventname = $1
-              AND start_timestamp <= $2
-              AND end_timestamp >= $2
-            LIMIT 1"#;
-        conn.query(query, &[&eventname, &start_timestamp])
-            .chain_err(|| "Unable to load event")?
-            .iter()
-            .map(|row| row.get("id"))
-            .next()
-            .ok_or_else(|| format!("event with eventname=`{}` not found", eventname))?
+    let person_id = authenticate(conn, &credentials)?;
+    schedule_talk(conn,
+                  person_id,
+                  speakers,
+                  talk,
+                  title,
+                  start_timestamp,
+                  end_timestamp,
+                  room,
+                  Some(initial_evaluation),
+                  eventname)
+}
+
+/// Wspólna część talk i accept: zatwierdza referat w imieniu osoby `person_id`,
+/// ocena początkowa jest opcjonalna.
+#[allow(clippy::too_many_arguments)]
+fn schedule_talk(conn: &Connection,
+                 person_id: i32,
+                 speakers: Vec<Speaker>,
+                 talk: String,
+                 title: String,
+                 start_timestamp: DateTime,
+                 end_timestamp: Option<DateTime>,
+                 room: String,
+                 initial_evaluation: Option<i16>,
+                 eventname: String)
+                 -> Result<()> {
+
+    let (primary, others) = speakers.split_first()
+        .ok_or_else(|| ErrorKind::Validation("a talk needs a speaker".into()))?;
+    let speaker_id = authorize_person(conn, &primary.login, None)?;
+    let event_id = if !eventname.is_empty() {
+        Some(event_at(conn, &eventname, start_timestamp)?)
     } else {
         None
     };
+    authorize_organizer(conn, person_id, Permission::ScheduleTalks, event_id)?;
+    // the upsert below moves an existing talk out of its event as well
+    if let Some(current) = talk_event(conn, &talk)? {
+        if Some(current) != event_id {
+            authorize_organizer(conn, person_id, Permission::ScheduleTalks, Some(current))?;
+        }
+    }
+
+    let room = room_named(conn, &room)?
+        .ok_or_else(|| ErrorKind::NotFound(format!("room with room=`{}` not found", room)))?;
+
+    // keep the duration of a proposal unless told otherwise
+    let query = r#"
+        SELECT coalesce($1::timestamp,
+                        $2::timestamp + (SELECT end_timestamp - start_timestamp
+                                         FROM talks
+                                         WHERE talk = $3),
+                        $2::timestamp + make_interval(mins => $4)) AS end_timestamp"#;
+    let end_timestamp: DateTime = conn.query(query,
+                                             &[&end_timestamp,
+                                               &start_timestamp,
+                                               &talk,
+                                               &DEFAULT_TALK_MINUTES])
+        .chain_err(|| "Unable to compute the end of a talk")?
+        .get(0)
+        .get("end_timestamp");
+
+    // the exclusion constraint on talks only says that there is a collision
+    let query = r#"
+        SELECT talk FROM talks
+        WHERE status = $1
+          AND room = $2
+          AND talk <> $3
+          AND tsrange(start_timestamp, end_timestamp) && tsrange($4, $5)
+        LIMIT 1"#;
+    let colliding: Option<String> = conn.query(query,
+                                               &[&TalkStatus::Accepted,
+                                                 &room,
+                                                 &talk,
+                                                 &start_timestamp,
+                                                 &end_timestamp])
+        .chain_err(|| "Unable to check the room")?
+        .iter()
+        .map(|row| row.get("talk"))
+        .next();
+    if let Some(colliding) = colliding {
+        bail!(ErrorKind::Conflict(format!("room `{}` is already taken by talk `{}`",
+                                          room,
+                                          colliding)))
+    }
 
     // upsert a talk
     let query = r#"
-        INSERT INTO talks (talk, speaker_id, status, title, start_timestamp, room, event_id)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        INSERT INTO talks (talk, speaker_id, status, title, start_timestamp, end_timestamp,
+                           room, event_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
         ON CONFLICT (talk) DO UPDATE SET
           speaker_id = EXCLUDED.speaker_id,
           status = EXCLUDED.status,
           title = EXCLUDED.title,
           start_timestamp = EXCLUDED.start_timestamp,
+          end_timestamp = EXCLUDED.end_timestamp,
           room = EXCLUDED.room,
           event_id = EXCLUDED.event_id,
           modified_at = now()
@@ -157,32 +465,99 @@ pub fn register_or_accept_talk(conn: &Connection,
                                     &TalkStatus::Accepted,
                                     &title,
                                     &start_timestamp,
+                                    &end_timestamp,
                                     &room,
                                     &event_id])
         .chain_err(|| "Unable to upsert a talk")?
         .iter()
         .map(|row| row.get("id"))
         .next()
-        .ok_or_else(|| format!("talk with talk=`{}` not found", talk))?;
+        .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+
+    set_speakers(conn, talk_id, speaker_id, others)?;
 
     // initial evaluation
-    let query = r#"
-        INSERT INTO person_rated_talk (person_id, talk_id, rating)
-        VALUES ($1, $2, $3)"#;
-    conn.execute(query, &[&person_id, &talk_id, &initial_evaluation])
-        .map(|_| ())
-        .chain_err(|| "Unable to evaluate the talk")
+    if let Some(initial_evaluation) = initial_evaluation {
+        let query = r#"
+            INSERT INTO person_rated_talk (person_id, talk_id, rating)
+            VALUES ($1, $2, $3)"#;
+        conn.execute(query, &[&person_id, &talk_id, &initial_evaluation])
+            .chain_err(|| "Unable to evaluate the talk")?;
+        record_rating(conn, person_id, talk_id, Some(initial_evaluation))?;
+    }
+
+    Ok(())
 }
 
 /// (*U) register_user_for_event <login> <password> <eventname>
 /// rejestracja uczestnika <login> na wydarzenie <eventname>
+///
+/// Jeśli wydarzenie nie ma już wolnych miejsc, uczestnik trafia na koniec listy oczekujących.
 pub fn register_user_for_event(conn: &Connection,
-                               login: String,
-                               password: String,
+                               credentials: Credentials,
                                eventname: String)
                                -> Result<()> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, event_named(conn, &eventname)?)?;
+
+    // the lock keeps concurrent registrations from taking the same seat
+    let query = r#"
+        SELECT id, capacity - (SELECT count(*)
+                               FROM person_registered_for_event
+                               WHERE event_id = events.id) AS free
+        FROM events
+        WHERE eventname = $1
+        LIMIT 1
+        FOR UPDATE"#;
+    let (event_id, free): (i32, Option<i64>) =
+        conn.query(query, &[&eventname])
+            .chain_err(|| "Unable to load event")?
+            .iter()
+            .map(|row| (row.get("id"), row.get("free")))
+            .next()
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+            })?;
+
+    let full = free.is_some_and(|free| free <= 0);
+    if !full {
+        let query = r#"
+            INSERT INTO person_registered_for_event (person_id, event_id)
+            VALUES ($1, $2)"#;
+        return conn.execute(query, &[&person_id, &event_id])
+                   .map(|_| ())
+                   .chain_err(|| "Person can't be registered for event");
+    }
+
+    let query = r#"
+        SELECT 1 FROM person_registered_for_event
+        WHERE person_id = $1
+          AND event_id = $2"#;
+    if !conn.query(query, &[&person_id, &event_id])
+            .chain_err(|| "Unable to load registrations")?
+            .is_empty() {
+        bail!(ErrorKind::Conflict(format!("already registered for event `{}`", eventname)))
+    }
+
+    let query = r#"
+        INSERT INTO event_waitlist (person_id, event_id)
+        VALUES ($1, $2)"#;
+    conn.execute(query, &[&person_id, &event_id])
+        .map(|_| ())
+        .chain_err(|| "Person can't be put on the waitlist")
+}
+
+/// (U) unregister_user_from_event <login> <password> <eventname>
+/// wyrejestrowanie uczestnika <login> z wydarzenia <eventname> lub zejście z listy oczekujących,
+/// zwolnione miejsce zajmuje pierwsza osoba z listy oczekujących
+pub fn unregister_user_from_event(conn: &Connection,
+                                  credentials: Credentials,
+                                  eventname: String)
+                                  -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, event_named(conn, &eventname)?)?;
 
     let query = r#"
         SELECT id FROM events
@@ -194,23 +569,75 @@ pub fn register_user_for_event(conn: &Connection,
             .iter()
             .map(|row| row.get("id"))
             .next()
-            .ok_or_else(|| format!("event with eventname=`{}` not found", eventname))?;
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+            })?;
+
+    let query = r#"
+        DELETE FROM person_registered_for_event
+        WHERE person_id = $1
+          AND event_id = $2"#;
+    let deletes = conn.execute(query, &[&person_id, &event_id])
+        .chain_err(|| "Unable to unregister person from event")?;
+    if deletes == 1 {
+        return promote_waitlisted(conn, event_id);
+    }
+
+    let query = r#"
+        DELETE FROM event_waitlist
+        WHERE person_id = $1
+          AND event_id = $2"#;
+    let deletes = conn.execute(query, &[&person_id, &event_id])
+        .chain_err(|| "Unable to remove person from the waitlist")?;
+    if deletes != 1 {
+        bail!(ErrorKind::NotFound("There was no registration to cancel".into()))
+    }
+
+    Ok(())
+}
+
+/// Rejestruje na wydarzenie tyle pierwszych osób z listy oczekujących, ile jest wolnych miejsc.
+fn promote_waitlisted(conn: &Connection, event_id: i32) -> Result<()> {
+    let query = r#"
+        SELECT capacity - (SELECT count(*)
+                           FROM person_registered_for_event
+                           WHERE event_id = events.id) AS free
+        FROM events
+        WHERE id = $1
+        FOR UPDATE"#;
+    let free: Option<i64> = conn.query(query, &[&event_id])
+        .chain_err(|| "Unable to load event")?
+        .get(0)
+        .get("free");
+    if free.is_some_and(|free| free <= 0) {
+        return Ok(());
+    }
 
+    // LIMIT NULL takes everyone
     let query = r#"
+        WITH promoted AS (
+          DELETE FROM event_waitlist
+          WHERE id IN (SELECT id FROM event_waitlist
+                       WHERE event_id = $1
+                       ORDER BY id
+                       LIMIT $2)
+          RETURNING person_id, event_id
+        )
         INSERT INTO person_registered_for_event (person_id, event_id)
-        VALUES ($1, $2)"#;
-    conn.execute(query, &[&person_id, &event_id])
+        SELECT person_id, event_id FROM promoted"#;
+    conn.execute(query, &[&event_id, &free])
         .map(|_| ())
-        .chain_err(|| "Person can't be registered for event")
+        .chain_err(|| "Unable to promote waitlisted persons")
 }
 
 /// (*U) attendance <login> <password> <talk>
 /// odnotowanie faktycznej obecności uczestnika <login> na referacie <talk>
 ///
 /// Uczestnik mógł być tylko na zatwierdzonym referacie.
-pub fn attendance(conn: &Connection, login: String, password: String, talk: String) -> Result<()> {
+pub fn attendance(conn: &Connection, credentials: Credentials, talk: String) -> Result<()> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, talk_event(conn, &talk)?)?;
 
     let query = r#"
         SELECT id FROM talks
@@ -222,7 +649,7 @@ pub fn attendance(conn: &Connection, login: String, password: String, talk: Stri
         .iter()
         .map(|row| row.get("id"))
         .next()
-        .ok_or_else(|| format!("talk with talk=`{}` not found", talk))?;
+        .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
 
     let query = r#"
         INSERT INTO person_attended_for_talk (person_id, talk_id)
@@ -232,16 +659,41 @@ pub fn attendance(conn: &Connection, login: String, password: String, talk: Stri
         .chain_err(|| "Unable to mark attendance of User for the talk")
 }
 
-/// (*U) evaluation <login> <password> <talk> <rating>
-/// ocena referatu <talk> w skali 0-10 przez uczestnika <login>
+/// (U) cancel_attendance <login> <password> <talk>
+/// wycofanie obecności uczestnika <login> na referacie <talk>, np. odnotowanej przez pomyłkę
+///
+/// Wystawiona ocena referatu pozostaje.
+pub fn cancel_attendance(conn: &Connection, credentials: Credentials, talk: String) -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, talk_event(conn, &talk)?)?;
+
+    let query = r#"
+        DELETE FROM person_attended_for_talk
+        WHERE person_id = $1
+          AND talk_id = (SELECT id FROM talks
+                         WHERE talk = $2
+                           AND status = $3)"#;
+    let deletes = conn.execute(query, &[&person_id, &talk, &TalkStatus::Accepted])
+        .chain_err(|| "Unable to cancel attendance")?;
+    if deletes != 1 {
+        bail!(ErrorKind::NotFound("There was no attendance to cancel".into()))
+    }
+
+    Ok(())
+}
+
+/// (*U) evaluation <login> <password> <talk> <rating> [<comment>]
+/// ocena referatu <talk> w skali 0-10 przez uczestnika <login>, opcjonalnie z recenzją
 pub fn evaluation(conn: &Connection,
-                  login: String,
-                  password: String,
+                  credentials: Credentials,
                   talk: String,
-                  rating: i16)
+                  rating: i16,
+                  comment: Option<String>)
                   -> Result<()> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, talk_event(conn, &talk)?)?;
 
     let query = r#"
         SELECT id FROM talks
@@ -253,25 +705,85 @@ pub fn evaluation(conn: &Connection,
         .iter()
         .map(|row| row.get("id"))
         .next()
-        .ok_or_else(|| "talk doesn't exist or is not accepted")?;
+        .ok_or_else(|| ErrorKind::NotFound("talk doesn't exist or is not accepted".into()))?;
+
+    // historia odnotowuje jedynie zmiany oceny, nie samej recenzji
+    let query = r#"
+        SELECT rating FROM person_rated_talk
+        WHERE person_id = $1
+          AND talk_id = $2
+        FOR UPDATE"#;
+    let previous: Option<i16> = conn.query(query, &[&person_id, &talk_id])
+        .chain_err(|| "Unable to load the evaluation")?
+        .iter()
+        .map(|row| row.get("rating"))
+        .next();
+
+    // ponowna ocena zastępuje poprzednią wraz z recenzją
+    let query = r#"
+        INSERT INTO person_rated_talk (person_id, talk_id, rating, comment)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (person_id, talk_id) DO UPDATE
+        SET rating = EXCLUDED.rating,
+            comment = EXCLUDED.comment"#;
+    conn.execute(query, &[&person_id, &talk_id, &rating, &comment])
+        .chain_err(|| "Unable to evaluate the talk")?;
+    if previous == Some(rating) {
+        return Ok(());
+    }
+
+    record_rating(conn, person_id, talk_id, Some(rating))
+}
+
+/// (U) retract_evaluation <login> <password> <talk>
+/// wycofanie oceny referatu <talk> wystawionej przez uczestnika <login>
+pub fn retract_evaluation(conn: &Connection, credentials: Credentials, talk: String) -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, talk_event(conn, &talk)?)?;
+
+    let query = r#"
+        DELETE FROM person_rated_talk
+        WHERE person_id = $1
+          AND talk_id = (SELECT id FROM talks
+                         WHERE talk = $2)
+        RETURNING talk_id"#;
+    let talk_id: i32 = conn.query(query, &[&person_id, &talk])
+        .chain_err(|| "Unable to retract the evaluation")?
+        .iter()
+        .map(|row| row.get("talk_id"))
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound("There was no evaluation to retract".into()))?;
+
+    record_rating(conn, person_id, talk_id, None)
+}
 
+/// Odnotowuje zmianę oceny w historii, `None` oznacza wycofanie oceny.
+fn record_rating(conn: &Connection,
+                 person_id: i32,
+                 talk_id: i32,
+                 rating: Option<i16>)
+                 -> Result<()> {
     let query = r#"
-        INSERT INTO person_rated_talk (person_id, talk_id, rating)
+        INSERT INTO rating_history (person_id, talk_id, rating)
         VALUES ($1, $2, $3)"#;
     conn.execute(query, &[&person_id, &talk_id, &rating])
         .map(|_| ())
-        .chain_err(|| "Unable to evaluate the talk")
+        .chain_err(|| "Unable to record the rating")
 }
 
 /// (O) reject <login> <password> <talk>
 /// usuwa referat spontaniczny <talk> z listy zaproponowanych,
 pub fn reject_spontaneous_talk(conn: &Connection,
-                               login: String,
-                               password: String,
+                               credentials: Credentials,
                                talk: String)
                                -> Result<()> {
 
-    authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize_organizer(conn,
+                        person_id,
+                        Permission::ReviewProposals,
+                        talk_event(conn, &talk)?)?;
 
     // update a proposal
     let query = r#"
@@ -283,36 +795,115 @@ pub fn reject_spontaneous_talk(conn: &Connection,
                                &[&TalkStatus::Rejected, &talk, &TalkStatus::Proposed])
         .chain_err(|| "Unable to reject a proposal")?;
     if updates != 1 {
-        bail!("There was no proposal to reject")
+        bail!(ErrorKind::NotFound("There was no proposal to reject".into()))
     }
 
     Ok(())
 }
 
+/// (O) accept <login> <password> <talk> [<room> <initial_evaluation>]
+/// zatwierdza referat spontaniczny <talk> z zaproponowanym tytułem, czasem, prelegentami
+/// i wydarzeniem, jak talk
+///
+/// <room> zastępuje salę wskazaną w propozycji, a bez żadnej z nich referatu nie można
+/// zatwierdzić. Pominięty <initial_evaluation> oznacza zatwierdzenie bez oceny początkowej.
+pub fn accept_talk(conn: &Connection,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+
+    let query = r#"
+        SELECT title, talks.start_timestamp, talks.end_timestamp, room,
+               coalesce(eventname, '') AS eventname, speakers
+        FROM talks
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+          LEFT JOIN events ON events.id = talks.event_id
+        WHERE talk = $1
+          AND status = $2"#;
+    let rows = conn.query(query, &[&talk, &TalkStatus::Proposed])
+        .chain_err(|| "Unable to load the proposal")?;
+    let proposal = rows.iter()
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound("There was no proposal to accept".into()))?;
+    let room = room.or_else(|| proposal.get("room"))
+        .ok_or_else(|| ErrorKind::Validation("the proposal has no room, give one".into()))?;
+
+    schedule_talk(conn,
+                  person_id,
+                  speakers(&proposal),
+                  talk,
+                  proposal.get("title"),
+                  proposal.get("start_timestamp"),
+                  Some(proposal.get("end_timestamp")),
+                  room,
+                  initial_evaluation,
+                  proposal.get("eventname"))
+}
+
 /// (U) proposal <login> <password> <talk> <title> <start_timestamp>
 /// propozycja referatu spontanicznego, <talk> - unikalny identyfikator referatu
+///
+/// Opcjonalny <end_timestamp> wyznacza koniec referatu,
+/// domyślnie trwa on DEFAULT_TALK_MINUTES minut.
+///
+/// Opcjonalna lista <speakers> jak w talk, prelegentem głównym jest proponujący.
+///
+/// Opcjonalny <eventname> wskazuje wydarzenie, w którym referat ma się odbyć (musi ono trwać
+/// w chwili <start_timestamp>), a <room> - preferowaną salę; propozycję z wydarzeniem
+/// rozpatrują jego organizatorzy (zob. proposals, accept).
+#[allow(clippy::too_many_arguments)]
 pub fn propose_spontaneous_talk(conn: &Connection,
-                                login: String,
-                                password: String,
+                                credentials: Credentials,
                                 talk: String,
                                 title: String,
-                                start_timestamp: DateTime)
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
                                 -> Result<()> {
 
-    let speaker_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let speaker_id = authenticate(conn, &credentials)?;
+    let event_id = match eventname {
+        Some(eventname) => Some(event_at(conn, &eventname, start_timestamp)?),
+        None => None,
+    };
+    authorize(conn, speaker_id, Permission::Participate, event_id)?;
+    let room = match room {
+        Some(room) => {
+            Some(room_named(conn, &room)?.ok_or_else(|| {
+                ErrorKind::NotFound(format!("room with room=`{}` not found", room))
+            })?)
+        }
+        None => None,
+    };
 
     // insert a new proposal
     let query = r#"
-        INSERT INTO talks (speaker_id, talk, status, title, start_timestamp)
-        VALUES ($1, $2, $3, $4, $5)"#;
-    conn.execute(query,
-                 &[&speaker_id,
-                   &talk,
-                   &TalkStatus::Proposed,
-                   &title,
-                   &start_timestamp])
-        .map(|_| ())
-        .chain_err(|| "Unable to insert a proposal")
+        INSERT INTO talks (speaker_id, talk, status, title, start_timestamp, end_timestamp,
+                           room, event_id)
+        VALUES ($1, $2, $3, $4, $5, coalesce($6, $5::timestamp + make_interval(mins => $7)),
+                $8, $9)
+        RETURNING id"#;
+    let talk_id: i32 = conn.query(query,
+                                  &[&speaker_id,
+                                    &talk,
+                                    &TalkStatus::Proposed,
+                                    &title,
+                                    &start_timestamp,
+                                    &end_timestamp,
+                                    &DEFAULT_TALK_MINUTES,
+                                    &room,
+                                    &event_id])
+        .chain_err(|| "Unable to insert a proposal")?
+        .get(0)
+        .get("id");
+
+    set_speakers(conn, talk_id, speaker_id, &speakers)
 }
 
 /// (U) friends <login1> <password> <login2>
@@ -320,13 +911,13 @@ pub fn propose_spontaneous_talk(conn: &Connection,
 /// za nawiązaną jeśli obaj uczestnicy chcą ją nawiązać tj. po wywołaniach
 /// friends <login1> <password1> <login2> i friends <login2> <password2> <login1>
 pub fn make_friends(conn: &Connection,
-                    login1: String,
-                    password: String,
+                    credentials: Credentials,
                     login2: String)
                     -> Result<()> {
 
-    let person1_id = authorize_person_as(conn, &login1, Some(&password), PersonType::User)?;
-    let person2_id = authorize_person_as(conn, &login2, None, PersonType::User)?;
+    let person1_id = authenticate(conn, &credentials)?;
+    authorize(conn, person1_id, Permission::Participate, None)?;
+    let person2_id = authorize_person(conn, &login2, None)?;
 
     let query = r#"
         INSERT INTO person_knows_person (person1_id, person2_id)
@@ -336,74 +927,153 @@ pub fn make_friends(conn: &Connection,
         .chain_err(|| "These users are already friends?")
 }
 
-/// (*N) user_plan <login> <limit>
+/// (U) unfriend <login1> <password> <login2>
+/// uczestnik <login1> zrywa znajomość z uczestnikiem <login2> lub wycofuje chęć jej nawiązania,
+/// do ponownego nawiązania znajomości obaj uczestnicy muszą znowu wywołać friends
+pub fn unfriend(conn: &Connection, credentials: Credentials, login2: String) -> Result<()> {
+
+    let person1_id = authenticate(conn, &credentials)?;
+    authorize(conn, person1_id, Permission::Participate, None)?;
+    let person2_id = authorize_person(conn, &login2, None)?;
+
+    let query = r#"
+        DELETE FROM person_knows_person
+        WHERE (person1_id, person2_id) IN (($1, $2), ($2, $1))"#;
+    let deletes = conn.execute(query, &[&person1_id, &person2_id])
+        .chain_err(|| "Unable to unfriend")?;
+    if deletes == 0 {
+        bail!(ErrorKind::NotFound("These users are not friends".into()))
+    }
+
+    Ok(())
+}
+
+/// (*N) user_plan <login> <limit> [<cursor>]
 /// zwraca plan najbliższych referatów z wydarzeń, na które dany uczestnik jest zapisany
 /// (wg rejestracji na wydarzenia) posortowany wg czasu rozpoczęcia,
-/// wypisuje pierwsze <limit> referatów, przy czym 0 oznacza, że należy wypisać wszystkie
+/// wypisuje pierwsze <limit> referatów, przy czym 0 oznacza, że należy wypisać wszystkie;
+/// <cursor> to next_cursor poprzedniej strony - wtedy wypisuje referaty następujące po niej
+/// (tak samo w pozostałych listach z <cursor>)
 /// Atrybuty zwracanych krotek:
-///   <login> <talk> <start_timestamp> <title> <room>
-pub fn user_plan(conn: &Connection, login: String, limit: u32) -> Result<Vec<UserPlan>> {
-
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
+///   <login> <talk> <start_timestamp> <end_timestamp> <title> <room> <speakers>
+pub fn user_plan(conn: &Connection, login: String, page: Page) -> Result<Paged<UserPlan>> {
 
-    let query = format!(r#"
-        WITH cte(talk_id, person_id, speakerlogin, talk, start_timestamp, title, room) AS (
-          SELECT talks.id, person_id, login, talk, start_timestamp, title, room
+    let query = r#"
+        WITH cte(talk_id, person_id, speakerlogin, talk, start_timestamp, end_timestamp, title,
+                 room) AS (
+          SELECT talks.id, person_id, login, talk, start_timestamp, end_timestamp, title, room
           FROM person_registered_for_event prfe
             JOIN talks USING(event_id)
-            JOIN persons speakers ON speaker_id=speakers.id
+            JOIN persons primaries ON speaker_id = primaries.id
           WHERE status = $1
             AND start_timestamp >= now()
         )
-        SELECT speakerlogin, talk, start_timestamp, title, room
+        SELECT talk_id, speakerlogin, talk, start_timestamp, end_timestamp, title, room, speakers
         FROM persons
           JOIN cte ON cte.person_id = persons.id
-        WHERE persons.login = $2
-        ORDER BY start_timestamp, talk_id
-        {}"#,
-                        limit);
-    let plans: Vec<_> = conn.query(&query[..], &[&TalkStatus::Accepted, &login])
-        .chain_err(|| "Unable to load person's plan")?
-        .iter()
-        .map(|row| {
-            UserPlan {
-                login: row.get("speakerlogin"),
-                talk: row.get("talk"),
-                start_timestamp: row.get("start_timestamp"),
-                title: row.get("title"),
-                room: row.get("room"),
-            }
-        })
-        .collect();
-
-    Ok(plans)
+          JOIN talk_speaker_lists USING (talk_id)
+        WHERE persons.login = $2"#;
+    query_page(conn,
+               &USER_PLAN,
+               query,
+               &[&TalkStatus::Accepted, &login],
+               &page,
+               |row| {
+        UserPlan {
+            login: row.get("speakerlogin"),
+            talk: row.get("talk"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load person's plan")
 }
 
 /// (*N) day_plan <timestamp>
 /// zwraca listę wszystkich referatów zaplanowanych na dany dzień posortowaną rosnąco wg sal,
 ///     w drugiej kolejności wg czasu rozpoczęcia
-///  <talk> <start_timestamp> <title> <room>
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <venue> <capacity> <floor>
+///  <speakers>
 pub fn day_plan(conn: &Connection, date: Date) -> Result<Vec<DayPlan>> {
 
     let query = r#"
-        SELECT talk, start_timestamp, title, room
+        SELECT talk, start_timestamp, end_timestamp, title, talks.room, venue, capacity, floor,
+               speakers
         FROM talks
+        JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+        LEFT JOIN rooms ON rooms.room = talks.room
+        LEFT JOIN venues ON venues.id = rooms.venue_id
         WHERE status = $1
           AND start_timestamp::date = $2
-        ORDER BY room, start_timestamp, talks.id"#;
-    let plans: Vec<_> = conn.query(&query[..], &[&TalkStatus::Accepted, &date])
+        ORDER BY talks.room, start_timestamp, talks.id"#;
+    let plans: Vec<_> = conn.query(query, &[&TalkStatus::Accepted, &date])
         .chain_err(|| "Unable to load day plan")?
         .iter()
         .map(|row| {
                  DayPlan {
                      talk: row.get("talk"),
                      start_timestamp: row.get("start_timestamp"),
+                     end_timestamp: row.get("end_timestamp"),
+                     title: row.get("title"),
+                     room: row.get("room"),
+                     venue: row.get("venue"),
+                     capacity: row.get("capacity"),
+                     floor: row.get("floor"),
+                     speakers: speakers(&row),
+                 }
+             })
+        .collect();
+
+    Ok(plans)
+}
+
+/// zwraca listę wszystkich referatów wydarzenia <eventname> posortowaną wg czasu rozpoczęcia,
+/// na potrzeby eksportu do iCalendar
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <venue> <capacity> <floor>
+///  <speakers>
+pub fn event_plan(conn: &Connection, eventname: String) -> Result<Vec<DayPlan>> {
+
+    let query = r#"
+        SELECT id FROM events
+        WHERE eventname = $1
+        LIMIT 1"#;
+    let event_id: i32 =
+        conn.query(query, &[&eventname])
+            .chain_err(|| "Unable to load event")?
+            .iter()
+            .map(|row| row.get("id"))
+            .next()
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+            })?;
+
+    let query = r#"
+        SELECT talk, start_timestamp, end_timestamp, title, talks.room, venue, capacity, floor,
+               speakers
+        FROM talks
+        JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+        LEFT JOIN rooms ON rooms.room = talks.room
+        LEFT JOIN venues ON venues.id = rooms.venue_id
+        WHERE status = $1
+          AND event_id = $2
+        ORDER BY start_timestamp, talks.id"#;
+    let plans: Vec<_> = conn.query(query, &[&TalkStatus::Accepted, &event_id])
+        .chain_err(|| "Unable to load event plan")?
+        .iter()
+        .map(|row| {
+                 DayPlan {
+                     talk: row.get("talk"),
+                     start_timestamp: row.get("start_timestamp"),
+                     end_timestamp: row.get("end_timestamp"),
                      title: row.get("title"),
                      room: row.get("room"),
+                     venue: row.get("venue"),
+                     capacity: row.get("capacity"),
+                     floor: row.get("floor"),
+                     speakers: speakers(&row),
                  }
              })
         .collect();
@@ -411,131 +1081,129 @@ pub fn day_plan(conn: &Connection, date: Date) -> Result<Vec<DayPlan>> {
     Ok(plans)
 }
 
-/// (*N) best_talks <start_timestamp> <end_timestamp> <limit> <all>
+/// (*N) best_talks <start_timestamp> <end_timestamp> <limit> <all> [<cursor>]
 /// zwraca referaty rozpoczynające się w  danym przedziale czasowym posortowane malejąco
 /// wg średniej oceny uczestników, przy czym jeśli <all> jest równe 1 należy wziąć
 /// pod uwagę wszystkie oceny, w przeciwnym przypadku tylko oceny uczestników, którzy
 /// byli na referacie obecni, wypisuje pierwsze <limit> referatów, przy czym 0 oznacza,
 /// że należy wypisać wszystkie
-///  <talk> <start_timestamp> <title> <room>
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <speakers>
 pub fn best_talks(conn: &Connection,
                   start_timestamp: DateTime,
                   end_timestamp: DateTime,
-                  limit: u32,
+                  page: Page,
                   all: bool)
-                  -> Result<Vec<BestTalk>> {
+                  -> Result<Paged<BestTalk>> {
 
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
+    // oceny organizatorów i osób planujących referaty wydarzenia liczą się zawsze
     let all = if all {
-        ""
+        String::new()
     } else {
-        r#"WHERE is_organizer = TRUE
-             OR (person_id, talk_id) IN (SELECT * FROM person_attended_for_talk)"#
+        format!(r#"WHERE person_id IN (SELECT roles.person_id FROM person_roles roles
+                                       WHERE roles.role IN ({})
+                                         AND (roles.event_id IS NULL
+                                              OR roles.event_id = talks.event_id))
+                     OR person_id IN (SELECT organizers.person_id
+                                      FROM event_organizers organizers
+                                      WHERE organizers.event_id = talks.event_id)
+                     OR (person_id, talk_id) IN (SELECT * FROM person_attended_for_talk)"#,
+                Permission::ScheduleTalks.sql_roles())
     };
 
+    // średnia jako float8, żeby kursor odtwarzał ją dokładnie
     let query = format!(r#"
         WITH cte(talk_id, average_rate) AS (
-          SELECT talk_id, avg(rating)
+          SELECT talk_id, avg(rating)::float8
           FROM person_rated_talk
-            JOIN persons ON persons.id = person_id
             JOIN talks ON talks.id = talk_id
             JOIN events ON events.id = event_id
           {}
           GROUP BY talk_id
         )
-        SELECT talk, start_timestamp, title, room
+        SELECT talk_id, average_rate, talk, start_timestamp, end_timestamp, title, room,
+               speakers
         FROM talks
           JOIN cte ON cte.talk_id = talks.id
+          JOIN talk_speaker_lists USING (talk_id)
         WHERE status = $1
           AND start_timestamp >= $2
-          AND start_timestamp <= $3
-        ORDER BY average_rate DESC, talks.id
-        {}"#,
-                        all,
-                        limit);
-    let talks: Vec<_> = conn.query(&query[..],
-                                   &[&TalkStatus::Accepted, &start_timestamp, &end_timestamp])
-        .chain_err(|| "Unable to load best talks")?
-        .iter()
-        .map(|row| {
-                 BestTalk {
-                     talk: row.get("talk"),
-                     start_timestamp: row.get("start_timestamp"),
-                     title: row.get("title"),
-                     room: row.get("room"),
-                 }
-             })
-        .collect();
-
-    Ok(talks)
+          AND start_timestamp <= $3"#,
+                        all);
+    query_page(conn,
+               &BEST_TALKS,
+               &query,
+               &[&TalkStatus::Accepted, &start_timestamp, &end_timestamp],
+               &page,
+               |row| {
+        BestTalk {
+            talk: row.get("talk"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load best talks")
 }
 
-/// (*N) most_popular_talks <start_timestamp> <end_timestamp> <limit>
+/// (*N) most_popular_talks <start_timestamp> <end_timestamp> <limit> [<cursor>]
 /// zwraca referaty rozpoczynające się w podanym przedziału czasowego posortowane malejąco
 /// wg obecności, wypisuje pierwsze <limit> referatów, przy czym 0 oznacza,
 /// że należy wypisać wszystkie
-///  <talk> <start_timestamp> <title> <room>
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <speakers>
 pub fn most_popular_talks(conn: &Connection,
                           start_timestamp: DateTime,
                           end_timestamp: DateTime,
-                          limit: u32)
-                          -> Result<Vec<MostPopularTalk>> {
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>> {
 
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
-    let query = format!(r#"
+    let query = r#"
         WITH cte(talk_id, arrivals) AS (
           SELECT talk_id, COUNT(person_id)
           FROM person_attended_for_talk
           GROUP BY talk_id
         )
-        SELECT talk, start_timestamp, title, room
+        SELECT talk_id, arrivals, talk, start_timestamp, end_timestamp, title, room, speakers
         FROM talks
           JOIN cte ON cte.talk_id = talks.id
+          JOIN talk_speaker_lists USING (talk_id)
         WHERE status = $1
           AND start_timestamp >= $2
-          AND start_timestamp <= $3
-        ORDER BY arrivals DESC, talks.id
-        {}"#,
-                        limit);
-    let talks: Vec<_> = conn.query(&query[..],
-                                   &[&TalkStatus::Accepted, &start_timestamp, &end_timestamp])
-        .chain_err(|| "Unable to load most popular talks")?
-        .iter()
-        .map(|row| {
-                 MostPopularTalk {
-                     talk: row.get("talk"),
-                     start_timestamp: row.get("start_timestamp"),
-                     title: row.get("title"),
-                     room: row.get("room"),
-                 }
-             })
-        .collect();
-
-    Ok(talks)
+          AND start_timestamp <= $3"#;
+    query_page(conn,
+               &MOST_POPULAR_TALKS,
+               query,
+               &[&TalkStatus::Accepted, &start_timestamp, &end_timestamp],
+               &page,
+               |row| {
+        MostPopularTalk {
+            talk: row.get("talk"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load most popular talks")
 }
 
 /// (*U) attended_talks <login> <password>
 /// zwraca dla danego uczestnika referaty, na których był obecny
-///  <talk> <start_timestamp> <title> <room>
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <speakers>
 pub fn attended_talks(conn: &Connection,
-                      login: String,
-                      password: String)
+                      credentials: Credentials)
                       -> Result<Vec<AttendedTalk>> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, None)?;
 
     let query = r#"
-        SELECT talk, start_timestamp, title, room
+        SELECT talk, start_timestamp, end_timestamp, title, room, speakers
         FROM person_attended_for_talk paft
           JOIN talks ON paft.talk_id = talks.id
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
         WHERE paft.person_id = $1"#;
     let talks: Vec<_> = conn.query(query, &[&person_id])
         .chain_err(|| "Unable to load person's talks")?
@@ -544,8 +1212,10 @@ pub fn attended_talks(conn: &Connection,
                  AttendedTalk {
                      talk: row.get("talk"),
                      start_timestamp: row.get("start_timestamp"),
+                     end_timestamp: row.get("end_timestamp"),
                      title: row.get("title"),
                      room: row.get("room"),
+                     speakers: speakers(&row),
                  }
              })
         .collect();
@@ -553,31 +1223,28 @@ pub fn attended_talks(conn: &Connection,
     Ok(talks)
 }
 
-/// (*O) abandoned_talks <login> <password> <limit>
+/// (*O) abandoned_talks <login> <password> <limit> [<cursor>]
 /// zwraca listę referatów posortowaną malejąco wg liczby uczestników
 /// <number> zarejestrowanych na wydarzenie obejmujące referat,
 /// którzy nie byli na tym referacie obecni, wypisuje pierwsze <limit> referatów,
 /// przy czym 0 oznacza, że należy wypisać wszystkie
-///  <talk> <start_timestamp> <title> <room> <number>
+///  <talk> <start_timestamp> <end_timestamp> <title> <room> <number> <speakers>
+///
+/// Obejmuje tylko wydarzenia, które wywołujący organizuje lub w których ma uprawnienie
+/// ViewFeedback.
 pub fn abandoned_talks(conn: &Connection,
-                       login: String,
-                       password: String,
-                       limit: u32)
-                       -> Result<Vec<AbandonedTalk>> {
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>> {
 
-    authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
-
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
+    let person_id = authenticate(conn, &credentials)?;
 
     let query = format!(r#"
         WITH person_registered_for_talk(person_id, talk_id) AS (
           SELECT person_id, talks.id
           FROM person_registered_for_event
             JOIN talks USING(event_id)
+          WHERE status = $2
         ),
         cte(talk_id, absent) AS (
           SELECT talk_id, count(person_id)
@@ -585,105 +1252,176 @@ pub fn abandoned_talks(conn: &Connection,
           WHERE (person_id, talk_id) NOT IN (SELECT * FROM person_attended_for_talk)
           GROUP BY talk_id
         )
-        SELECT talk, start_timestamp, title, room, absent
+        SELECT talk_id, talk, start_timestamp, end_timestamp, title, room, absent, speakers
         FROM talks
           JOIN cte ON cte.talk_id = talks.id
-        ORDER BY absent DESC, talks.id
-        {}"#,
-                        limit);
-    let talks: Vec<_> = conn.query(&query[..], &[])
-        .chain_err(|| "Unable to load abandoned talks")?
-        .iter()
-        .map(|row| {
-            AbandonedTalk {
-                talk: row.get("talk"),
-                start_timestamp: row.get("start_timestamp"),
-                title: row.get("title"),
-                room: row.get("room"),
-                number: row.get("absent"),
-            }
-        })
-        .collect();
-
-    Ok(talks)
+          JOIN talk_speaker_lists USING (talk_id)
+        WHERE talks.event_id IN ({})"#,
+                        organized_events(Permission::ViewFeedback));
+    query_page(conn,
+               &ABANDONED_TALKS,
+               &query,
+               &[&person_id, &TalkStatus::Accepted],
+               &page,
+               |row| {
+        AbandonedTalk {
+            talk: row.get("talk"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            number: row.get("absent"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load abandoned talks")
 }
 
-/// (N) recently_added_talks <limit>
+/// (N) recently_added_talks <limit> [<cursor>]
 /// zwraca listę ostatnio zarejestrowanych referatów, wypisuje ostatnie <limit> referatów
 /// wg daty zarejestrowania, przy czym 0 oznacza, że należy wypisać wszystkie
-///  <talk> <speakerlogin> <start_timestamp> <title> <room>
-pub fn recently_added_talks(conn: &Connection, limit: u32) -> Result<Vec<RecentlyAddedTalk>> {
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <room> <speakers>
+pub fn recently_added_talks(conn: &Connection, page: Page) -> Result<Paged<RecentlyAddedTalk>> {
 
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
-
-    let query = format!(r#"
-        SELECT talk, login AS speakerlogin, start_timestamp, title, room
+    let query = r#"
+        SELECT talks.id AS talk_id, modified_at, talk, login AS speakerlogin, start_timestamp,
+               end_timestamp, title, room, speakers
         FROM talks
           JOIN persons ON persons.id = talks.speaker_id
-        WHERE status = $1
-        ORDER BY modified_at DESC, talks.id
-        {}"#,
-                        limit);
-    let talks: Vec<_> = conn.query(&query[..], &[&TalkStatus::Accepted])
-        .chain_err(|| "Unable to load recently added talks")?
-        .iter()
-        .map(|row| {
-            RecentlyAddedTalk {
-                talk: row.get("talk"),
-                speakerlogin: row.get("speakerlogin"),
-                start_timestamp: row.get("start_timestamp"),
-                title: row.get("title"),
-                room: row.get("room"),
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+        WHERE status = $1"#;
+    query_page(conn,
+               &RECENTLY_ADDED_TALKS,
+               query,
+               &[&TalkStatus::Accepted],
+               &page,
+               |row| {
+        RecentlyAddedTalk {
+            talk: row.get("talk"),
+            speakerlogin: row.get("speakerlogin"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load recently added talks")
+}
+
+/// (N/O) search_talks <query> [<start_timestamp> <end_timestamp> <status> <limit> <cursor>]
+/// zwraca referaty pasujące do zapytania <query> w kolejności trafności, przeszukując tytuł,
+/// loginy prelegentów, nazwę wydarzenia i salę (w tej kolejności ważności),
+/// opcjonalnie tylko zaczynające się między <start_timestamp> a <end_timestamp>,
+/// <status> jest jednym z proposed, accepted, rejected - domyślnie accepted, przy czym
+/// referaty o innym statusie może wyszukiwać tylko organizator,
+/// <limit> jak w recently_added_talks
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <room> <score> <speakers>
+///
+/// Zapytanie ma składnię `websearch_to_tsquery` (cudzysłowy, `or`, `-`).
+pub fn search_talks(conn: &Connection,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>> {
+
+    if status != TalkStatusName::Accepted {
+        match credentials {
+            Some(credentials) => {
+                let person_id = authenticate(conn, &credentials)?;
+                authorize(conn, person_id, Permission::ReviewProposals, None)?;
             }
-        })
-        .collect();
+            None => {
+                bail!(ErrorKind::Unauthorized("only organizers can search talks that are not \
+                                               accepted"
+                                                      .into()))
+            }
+        }
+    }
 
-    Ok(talks)
+    let sql = r#"
+        SELECT talks.id AS talk_id, talk, login AS speakerlogin, talks.start_timestamp,
+               talks.end_timestamp, title, room, ts_rank(document, query)::float8 AS score,
+               speakers
+        FROM talks
+          JOIN persons ON persons.id = talks.speaker_id
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+          LEFT JOIN events ON events.id = talks.event_id
+          CROSS JOIN LATERAL (
+            SELECT setweight(to_tsvector('simple', title), 'A')
+                || setweight(to_tsvector('simple', logins), 'B')
+                || setweight(to_tsvector('simple', coalesce(eventname, '')), 'C')
+                || setweight(to_tsvector('simple', coalesce(room, '')), 'D') AS document
+          ) documents
+          CROSS JOIN websearch_to_tsquery('simple', $1) query
+        WHERE document @@ query
+          AND status = $2
+          AND ($3::timestamp IS NULL OR talks.start_timestamp >= $3)
+          AND ($4::timestamp IS NULL OR talks.start_timestamp <= $4)"#;
+    let status = TalkStatus::from(status);
+    query_page(conn,
+               &SEARCH_TALKS,
+               sql,
+               &[&query, &status, &start_timestamp, &end_timestamp],
+               &page,
+               |row| {
+        FoundTalk {
+            talk: row.get("talk"),
+            speakerlogin: row.get("speakerlogin"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            score: row.get("score"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to search talks")
 }
 
 /// (U/O) rejected_talks <login> <password>
-/// jeśli wywołujący ma uprawnienia organizatora zwraca listę wszystkich odrzuconych referatów
-/// spontanicznych, w przeciwnym przypadku listę odrzuconych referatów wywołującego ją uczestnika
-///  <talk> <speakerlogin> <start_timestamp> <title>
+/// jeśli wywołujący ma uprawnienie ReviewProposals zwraca listę wszystkich odrzuconych
+/// referatów spontanicznych, w przeciwnym przypadku listę odrzuconych referatów, których
+/// wywołujący ją uczestnik jest jednym z prelegentów
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <speakers>
 pub fn rejected_talks(conn: &Connection,
-                      login: String,
-                      password: String)
+                      credentials: Credentials)
                       -> Result<Vec<RejectedTalk>> {
 
-    let (typ, person_id) = try_authorize_person(conn, login, password)?;
+    let person_id = authenticate(conn, &credentials)?;
+    let reviewer = has_permission(conn, person_id, Permission::ReviewProposals, None)?;
 
-    let talks: Vec<_> = match typ {
-            PersonType::Organizer => {
-                let query = r#"
-                SELECT talk, login AS speakerlogin, start_timestamp, title
-                FROM talks
-                  JOIN persons ON persons.id = talks.speaker_id
-                WHERE status = $1"#;
-                conn.query(&query[..], &[&TalkStatus::Rejected])
-            }
-            PersonType::User => {
-                let query = r#"
-                SELECT talk, login AS speakerlogin, start_timestamp, title
-                FROM talks
-                  JOIN persons ON persons.id = talks.speaker_id
-                WHERE status = $1
-                  AND talks.speaker_id = $2"#;
-                conn.query(&query[..], &[&TalkStatus::Rejected, &person_id])
-            }
-            PersonType::Whatever => unreachable!(),
-        }
-        .chain_err(|| "Unable to load rejected talks")?
+    let rows = if reviewer {
+        let query = r#"
+            SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title, speakers
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE status = $1"#;
+        conn.query(query, &[&TalkStatus::Rejected])
+    } else {
+        let query = r#"
+            SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title, speakers
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE status = $1
+              AND talks.id IN (SELECT talk_id FROM talk_speakers WHERE person_id = $2)"#;
+        conn.query(query, &[&TalkStatus::Rejected, &person_id])
+    };
+    let talks: Vec<_> = rows.chain_err(|| "Unable to load rejected talks")?
         .iter()
         .map(|row| {
                  RejectedTalk {
                      talk: row.get("talk"),
                      speakerlogin: row.get("speakerlogin"),
                      start_timestamp: row.get("start_timestamp"),
+                     end_timestamp: row.get("end_timestamp"),
                      title: row.get("title"),
+                     speakers: speakers(&row),
                  }
              })
         .collect();
@@ -695,17 +1433,25 @@ pub fn rejected_talks(conn: &Connection,
 /// zwraca listę propozycji referatów spontanicznych do zatwierdzenia lub odrzucenia,
 /// zatwierdzenie lub odrzucenie referatu polega na wywołaniu przez organizatora
 /// funkcji talk lub reject z odpowiednimi parametrami
-///  <talk> <speakerlogin> <start_timestamp> <title>
-pub fn proposals(conn: &Connection, login: String, password: String) -> Result<Vec<Proposal>> {
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <speakers>
+///
+/// Obejmuje propozycje dla wydarzeń, które wywołujący organizuje lub w których ma
+/// uprawnienie ReviewProposals, a propozycje bez wydarzenia - jeśli ma je we wszystkich
+/// wydarzeniach.
+pub fn proposals(conn: &Connection, credentials: Credentials) -> Result<Vec<Proposal>> {
 
-    authorize_person_as(conn, &login, Some(&password), PersonType::Organizer)?;
+    let person_id = authenticate(conn, &credentials)?;
+    let reviewer = has_permission(conn, person_id, Permission::ReviewProposals, None)?;
 
-    let query = r#"
-        SELECT talk, login AS speakerlogin, start_timestamp, title
+    let query = format!(r#"
+        SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title, speakers
         FROM talks
           JOIN persons ON persons.id = talks.speaker_id
-        WHERE status = $1"#;
-    let talks: Vec<_> = conn.query(query, &[&TalkStatus::Proposed])
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+        WHERE status = $2
+          AND (talks.event_id IN ({}) OR talks.event_id IS NULL AND $3)"#,
+                        organized_events(Permission::ReviewProposals));
+    let talks: Vec<_> = conn.query(&query, &[&person_id, &TalkStatus::Proposed, &reviewer])
         .chain_err(|| "Unable to load proposals")?
         .iter()
         .map(|row| {
@@ -713,7 +1459,9 @@ pub fn proposals(conn: &Connection, login: String, password: String) -> Result<V
                      talk: row.get("talk"),
                      speakerlogin: row.get("speakerlogin"),
                      start_timestamp: row.get("start_timestamp"),
+                     end_timestamp: row.get("end_timestamp"),
                      title: row.get("title"),
+                     speakers: speakers(&row),
                  }
              })
         .collect();
@@ -721,28 +1469,24 @@ pub fn proposals(conn: &Connection, login: String, password: String) -> Result<V
     Ok(talks)
 }
 
-/// (U) friends_talks <login> <password> <start_timestamp> <end_timestamp> <limit>
+/// (U) friends_talks <login> <password> <start_timestamp> <end_timestamp> <limit> [<cursor>]
 /// lista referatów  rozpoczynających się w podanym przedziale czasowym wygłaszanych
 /// przez znajomych danego uczestnika posortowana wg czasu rozpoczęcia,
 /// wypisuje pierwsze <limit> referatów, przy czym 0 oznacza, że należy wypisać wszystkie
-///  <talk> <speakerlogin> <start_timestamp> <title> <room>
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <room> <speakers>
+///
+/// Referat wygłasza znajomy, jeśli jest którymkolwiek z jego prelegentów.
 pub fn friends_talks(conn: &Connection,
-                     login: String,
-                     password: String,
+                     credentials: Credentials,
                      start_timestamp: DateTime,
                      end_timestamp: DateTime,
-                     limit: u32)
-                     -> Result<Vec<FriendsTalk>> {
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, None)?;
 
-    let limit = if limit == 0 {
-        "".to_owned()
-    } else {
-        format!("LIMIT {}", limit)
-    };
-
-    let query = format!(r#"
+    let query = r#"
         WITH friends(id) AS (
             SELECT person2_id
             FROM person_knows_person
@@ -752,47 +1496,48 @@ pub fn friends_talks(conn: &Connection,
             ) AS reversed USING(person1_id, person2_id)
             WHERE person1_id = $1
         )
-        SELECT talk, login AS speakerlogin, start_timestamp, title, room
+        SELECT talks.id AS talk_id, talk, login AS speakerlogin, start_timestamp,
+               end_timestamp, title, room, speakers
         FROM talks
           JOIN persons ON persons.id = talks.speaker_id
-          JOIN friends ON friends.id = talks.speaker_id
-        WHERE status = $2
+          JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+        WHERE talks.id IN (
+                SELECT talk_id
+                FROM talk_speakers
+                  JOIN friends ON friends.id = person_id)
+          AND status = $2
           AND start_timestamp >= $3
-          AND start_timestamp <= $4
-        ORDER BY start_timestamp, talks.id
-        {}"#,
-                        limit);
-    let talks: Vec<_> = conn.query(&query[..],
-                                   &[&person_id,
-                                     &TalkStatus::Accepted,
-                                     &start_timestamp,
-                                     &end_timestamp])
-        .chain_err(|| "Unable to load friends talks")?
-        .iter()
-        .map(|row| {
-            FriendsTalk {
-                talk: row.get("talk"),
-                speakerlogin: row.get("speakerlogin"),
-                start_timestamp: row.get("start_timestamp"),
-                title: row.get("title"),
-                room: row.get("room"),
-            }
-        })
-        .collect();
-
-    Ok(talks)
+          AND start_timestamp <= $4"#;
+    query_page(conn,
+               &FRIENDS_TALKS,
+               query,
+               &[&person_id, &TalkStatus::Accepted, &start_timestamp, &end_timestamp],
+               &page,
+               |row| {
+        FriendsTalk {
+            talk: row.get("talk"),
+            speakerlogin: row.get("speakerlogin"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            speakers: speakers(row),
+        }
+    })
+        .chain_err(|| "Unable to load friends talks")
 }
 
 /// (U) friends_events <login> <password> <eventname>
 /// lista znajomych uczestniczących w danym wydarzeniu
 ///  <login> <eventname> <friendlogin>
 pub fn friends_events(conn: &Connection,
-                      login: String,
-                      password: String,
+                      credentials: Credentials,
                       eventname: String)
                       -> Result<Vec<FriendsEvent>> {
 
-    let person_id = authorize_person_as(conn, &login, Some(&password), PersonType::User)?;
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, event_named(conn, &eventname)?)?;
+    let login = person_login(conn, person_id)?;
 
     let query = r#"
         WITH friends(id) AS (
@@ -809,7 +1554,7 @@ pub fn friends_events(conn: &Connection,
           JOIN persons ON persons.id = person_id
           JOIN friends ON friends.id = person_id
           JOIN events ON events.id = event_id"#;
-    let talks: Vec<_> = conn.query(&query[..], &[&person_id])
+    let talks: Vec<_> = conn.query(query, &[&person_id])
         .chain_err(|| "Unable to load friends events")?
         .iter()
         .map(|row| {
@@ -824,64 +1569,1213 @@ pub fn friends_events(conn: &Connection,
     Ok(talks)
 }
 
-/// (U) recommended_talks <login> <password> <start_timestamp> <end_timestamp> <limit>
+/// (U) recommended_talks <login> <password> <start_timestamp> <end_timestamp> <limit> [<cursor>]
 /// zwraca referaty rozpoczynające się w podanym przedziale czasowym, które mogą zainteresować
 /// danego uczestnika (zaproponuj parametr <score> obliczany na podstawie dostępnych danych
 /// – ocen, obecności, znajomości itp.), wypisuje pierwsze <limit> referatów wg nalepszego <score>,
 /// przy czym 0 oznacza, że należy wypisać wszystkie
-///  <talk> <speakerlogin> <start_timestamp> <title> <room> <score>
-
-enum PersonType {
-    Whatever,
-    User,
-    Organizer,
-}
-
-fn authorize_person_as(conn: &Connection,
-                       login: &str,
-                       password: Option<&str>,
-                       person_type: PersonType)
-                       -> Result<i32> {
-    use self::PersonType::*;
-    let organizer_part = match person_type {
-        Whatever => "",
-        User => "AND is_organizer=FALSE",
-        Organizer => "AND is_organizer=TRUE",
-    };
+///  <talk> <speakerlogin> <start_timestamp> <end_timestamp> <title> <room> <score> <speakers>
+///
+/// Pomijane są referaty, na których uczestnik już był, oraz te, których jest prelegentem.
+/// Parametr <score> jest sumą ważoną:
+///   10 * średnia ocena referatu,
+///   5 * średnia ocena wcześniejszych referatów tego samego prelegenta,
+///   5 * liczba znajomych obecnych na referacie lub zapisanych na jego wydarzenie,
+///   5 * liczba referatów tego prelegenta, na których uczestnik był obecny,
+///   2 * liczba referatów z tego samego wydarzenia, na których uczestnik był obecny.
+pub fn recommended_talks(conn: &Connection,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, None)?;
 
-    match password {
-            Some(ref password) => {
-                let query = format!(r#"
-                    SELECT id FROM persons
-                    WHERE login = $1 AND password = $2
-                      {}
-                    LIMIT 1"#,
-                                    organizer_part);
-                conn.query(&query[..], &[&login, &password])
-            }
-            None => {
-                let query = format!(r#"
-                    SELECT id FROM persons
-                    WHERE login = $1
-                      {}
-                    LIMIT 1"#,
-                                    organizer_part);
-                conn.query(&query[..], &[&login])
-            }
+    let query = r#"
+        WITH friends(id) AS (
+            SELECT person2_id
+            FROM person_knows_person
+            JOIN (
+                SELECT person2_id AS person1_id, person1_id AS person2_id
+                FROM person_knows_person
+            ) AS reversed USING(person1_id, person2_id)
+            WHERE person1_id = $1
+        ),
+        attended(talk_id, speaker_id, event_id) AS (
+          SELECT talks.id, speaker_id, event_id
+          FROM person_attended_for_talk paft
+            JOIN talks ON paft.talk_id = talks.id
+          WHERE paft.person_id = $1
+        ),
+        cte(talk_id, score) AS (
+          SELECT talks.id, round(
+              10 * coalesce((
+                SELECT avg(rating)
+                FROM person_rated_talk
+                WHERE talk_id = talks.id), 0)
+            + 5 * coalesce((
+                SELECT avg(rating)
+                FROM person_rated_talk
+                  JOIN talks past ON past.id = talk_id
+                WHERE past.speaker_id = talks.speaker_id
+                  AND past.start_timestamp < talks.start_timestamp), 0)
+            + 5 * (
+                SELECT count(*)
+                FROM friends
+                WHERE friends.id IN (
+                    SELECT person_id
+                    FROM person_attended_for_talk
+                    WHERE talk_id = talks.id)
+                  OR friends.id IN (
+                    SELECT person_id
+                    FROM person_registered_for_event
+                    WHERE event_id = talks.event_id))
+            + 5 * (
+                SELECT count(*)
+                FROM attended
+                WHERE attended.speaker_id = talks.speaker_id)
+            + 2 * (
+                SELECT count(*)
+                FROM attended
+                WHERE attended.event_id = talks.event_id)
+          )::integer
+          FROM talks
+          WHERE status = $2
+            AND start_timestamp >= $3
+            AND start_timestamp <= $4
+            AND talks.id NOT IN (SELECT talk_id FROM talk_speakers WHERE person_id = $1)
+            AND talks.id NOT IN (SELECT talk_id FROM attended)
+        )
+        SELECT talk_id, talk, login AS speakerlogin, start_timestamp, end_timestamp, title, room,
+               score, speakers
+        FROM talks
+          JOIN cte ON cte.talk_id = talks.id
+          JOIN talk_speaker_lists USING (talk_id)
+          JOIN persons ON persons.id = talks.speaker_id"#;
+    query_page(conn,
+               &RECOMMENDED_TALKS,
+               query,
+               &[&person_id, &TalkStatus::Accepted, &start_timestamp, &end_timestamp],
+               &page,
+               |row| {
+        RecommendedTalk {
+            talk: row.get("talk"),
+            speakerlogin: row.get("speakerlogin"),
+            start_timestamp: row.get("start_timestamp"),
+            end_timestamp: row.get("end_timestamp"),
+            title: row.get("title"),
+            room: row.get("room"),
+            score: row.get::<_, i32>("score") as u32,
+            speakers: speakers(row),
         }
+    })
+        .chain_err(|| "Unable to load recommended talks")
+}
+
+/// (U/O) waitlist <login> <password> <eventname>
+/// jeśli wywołujący ma uprawnienie ViewWaitlist zwraca całą listę oczekujących na wydarzenie
+/// <eventname>, w przeciwnym przypadku jedynie miejsce wywołującego, o ile na niej jest
+///  <login> <eventname> <position>
+pub fn waitlist(conn: &Connection,
+                credentials: Credentials,
+                eventname: String)
+                -> Result<Vec<WaitlistEntry>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+
+    let query = r#"
+        SELECT id FROM events
+        WHERE eventname = $1
+        LIMIT 1"#;
+    let event_id: i32 =
+        conn.query(query, &[&eventname])
+            .chain_err(|| "Unable to load event")?
+            .iter()
+            .map(|row| row.get("id"))
+            .next()
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+            })?;
+
+    // widzący całą listę widzą wszystkich
+    let everyone = has_permission(conn, person_id, Permission::ViewWaitlist, Some(event_id))?;
+    let person_id = if everyone { None } else { Some(person_id) };
+    let query = r#"
+        WITH waitlist(person_id, position) AS (
+          SELECT person_id, row_number() OVER (ORDER BY id)
+          FROM event_waitlist
+          WHERE event_id = $1
+        )
+        SELECT login, position
+        FROM waitlist
+          JOIN persons ON persons.id = person_id
+        WHERE $2::integer IS NULL
+           OR person_id = $2
+        ORDER BY position"#;
+    let entries: Vec<_> = conn.query(query, &[&event_id, &person_id])
+        .chain_err(|| "Unable to load the waitlist")?
+        .iter()
+        .map(|row| {
+                 WaitlistEntry {
+                     login: row.get("login"),
+                     eventname: eventname.clone(),
+                     position: row.get("position"),
+                 }
+             })
+        .collect();
+
+    Ok(entries)
+}
+
+/// (O) rating_history <login> <password> <talk>
+/// historia ocen referatu <talk>: kolejne zmiany ocen uczestników wraz ze średnią ocen
+/// referatu tuż po każdej zmianie; wycofana ocena ma wartość null
+///  <login> <rating> <changed_at> <average>
+pub fn rating_history(conn: &Connection,
+                      credentials: Credentials,
+                      talk: String)
+                      -> Result<Vec<RatingChange>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ViewFeedback, talk_event(conn, &talk)?)?;
+
+    let query = r#"
+        SELECT id FROM talks
+        WHERE talk = $1
+        LIMIT 1"#;
+    let talk_id: i32 = conn.query(query, &[&talk])
+        .chain_err(|| "Unable to load the talk")?
+        .iter()
+        .map(|row| row.get("id"))
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+
+    // średnia z ostatnich ocen każdego uczestnika do danej zmiany włącznie
+    let query = r#"
+        SELECT login, rating, changed_at,
+               (SELECT avg(latest.rating)::float8
+                FROM rating_history latest
+                WHERE latest.talk_id = change.talk_id
+                  AND latest.id = (SELECT max(h.id)
+                                   FROM rating_history h
+                                   WHERE h.person_id = latest.person_id
+                                     AND h.talk_id = latest.talk_id
+                                     AND h.id <= change.id)) AS average
+        FROM rating_history change
+          JOIN persons ON persons.id = change.person_id
+        WHERE change.talk_id = $1
+        ORDER BY change.id"#;
+    let changes: Vec<_> = conn.query(query, &[&talk_id])
+        .chain_err(|| "Unable to load the rating history")?
+        .iter()
+        .map(|row| {
+                 RatingChange {
+                     login: row.get("login"),
+                     rating: row.get("rating"),
+                     changed_at: row.get("changed_at"),
+                     average: row.get("average"),
+                 }
+             })
+        .collect();
+
+    Ok(changes)
+}
+
+/// (O) talk_reviews <login> <password> <talk>
+/// oceny i recenzje referatu <talk>; <attended> wskazuje recenzje uczestników,
+/// których obecność na referacie została odnotowana
+///  <login> <rating> <comment> <attended>
+pub fn talk_reviews(conn: &Connection,
+                    credentials: Credentials,
+                    talk: String)
+                    -> Result<Vec<TalkReview>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::ViewFeedback, talk_event(conn, &talk)?)?;
+
+    let query = r#"
+        SELECT id FROM talks
+        WHERE talk = $1
+        LIMIT 1"#;
+    let talk_id: i32 = conn.query(query, &[&talk])
+        .chain_err(|| "Unable to load the talk")?
+        .iter()
+        .map(|row| row.get("id"))
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+
+    let query = r#"
+        SELECT login, rating, comment,
+               EXISTS (SELECT 1
+                       FROM person_attended_for_talk attended
+                       WHERE attended.person_id = rated.person_id
+                         AND attended.talk_id = rated.talk_id) AS attended
+        FROM person_rated_talk rated
+          JOIN persons ON persons.id = rated.person_id
+        WHERE rated.talk_id = $1
+        ORDER BY login"#;
+    let reviews: Vec<_> = conn.query(query, &[&talk_id])
+        .chain_err(|| "Unable to load the reviews")?
+        .iter()
+        .map(|row| {
+                 TalkReview {
+                     login: row.get("login"),
+                     rating: row.get("rating"),
+                     comment: row.get("comment"),
+                     attended: row.get("attended"),
+                 }
+             })
+        .collect();
+
+    Ok(reviews)
+}
+
+/// (U) my_feedback <login> <password>
+/// anonimowe recenzje referatów, których uczestnik <login> jest jednym z prelegentów,
+/// pomija oceny bez recenzji
+///  <talk> <rating> <comment>
+pub fn my_feedback(conn: &Connection, credentials: Credentials) -> Result<Vec<Feedback>> {
+
+    let person_id = authenticate(conn, &credentials)?;
+    authorize(conn, person_id, Permission::Participate, None)?;
+
+    // bez loginów, kolejność nie zdradza autorów
+    let query = r#"
+        SELECT talk, rating, comment
+        FROM person_rated_talk
+          JOIN talks ON talks.id = person_rated_talk.talk_id
+        WHERE talks.id IN (SELECT talk_id FROM talk_speakers WHERE person_id = $1)
+          AND comment IS NOT NULL
+        ORDER BY talk, rating DESC, comment"#;
+    let feedback: Vec<_> = conn.query(query, &[&person_id])
+        .chain_err(|| "Unable to load the feedback")?
+        .iter()
+        .map(|row| {
+                 Feedback {
+                     talk: row.get("talk"),
+                     rating: row.get("rating"),
+                     comment: row.get("comment"),
+                 }
+             })
+        .collect();
+
+    Ok(feedback)
+}
+
+/// (*) login <login> <password>
+/// otwiera sesję uczestnika lub organizatora, zwraca token, który może zastąpić parę
+/// <login> <password> w kolejnych zapytaniach, aż do upływu <expires_at>
+///  <token> <expires_at>
+pub fn open_session(conn: &Connection, login: String, password: String) -> Result<Vec<Session>> {
+
+    let person_id = authorize_person(conn, &login, Some(&password))?;
+
+    // forget expired sessions
+    let query = r#"
+        DELETE FROM sessions
+        WHERE expires_at <= now()"#;
+    conn.execute(query, &[])
+        .chain_err(|| "Unable to remove expired sessions")?;
+
+    let token = new_session_token();
+    let query = r#"
+        INSERT INTO sessions (token, person_id, expires_at)
+        VALUES ($1, $2, now() + make_interval(hours => $3))
+        RETURNING token, expires_at"#;
+    let sessions: Vec<_> = conn.query(query, &[&token, &person_id, &SESSION_TTL_HOURS])
+        .chain_err(|| "Unable to open a session")?
+        .iter()
+        .map(|row| {
+                 Session {
+                     token: row.get("token"),
+                     expires_at: row.get("expires_at"),
+                 }
+             })
+        .collect();
+
+    Ok(sessions)
+}
+
+/// (*) logout <token>
+/// zamyka sesję o podanym tokenie
+pub fn close_session(conn: &Connection, token: String) -> Result<()> {
+
+    let query = r#"
+        DELETE FROM sessions
+        WHERE token = $1"#;
+    let deletes = conn.execute(query, &[&token])
+        .chain_err(|| "Unable to close a session")?;
+    if deletes != 1 {
+        bail!(ErrorKind::NotFound("There was no session to close".into()))
+    }
+
+    Ok(())
+}
+
+/// (*) revoke_sessions <login> <password>
+/// unieważnia wszystkie sesje wywołującego, np. po wycieku tokenu
+pub fn revoke_sessions(conn: &Connection, credentials: Credentials) -> Result<()> {
+
+    let person_id = authenticate(conn, &credentials)?;
+
+    let query = r#"
+        DELETE FROM sessions
+        WHERE person_id = $1"#;
+    conn.execute(query, &[&person_id])
+        .map(|_| ())
+        .chain_err(|| "Unable to revoke sessions")
+}
+
+/// (O) grant_role <login> <password> <personlogin> <role> [<eventname>]
+/// nadaje uczestnikowi <personlogin> rolę <role> (admin, program_committee, track_chair,
+/// volunteer, speaker lub attendee) w wydarzeniu <eventname>, a jeśli go pominięto -
+/// we wszystkich wydarzeniach
+///
+/// Wywołujący musi mieć uprawnienie ManageRoles w tym samym zakresie.
+pub fn grant_role(conn: &Connection,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()> {
+
+    let granter_id = authenticate(conn, &credentials)?;
+    let event_id = role_event(conn, eventname)?;
+    authorize(conn, granter_id, Permission::ManageRoles, event_id)?;
+    let person_id = authorize_person(conn, &personlogin, None)?;
+
+    insert_role(conn, person_id, role, event_id)
+}
+
+/// (O) revoke_role <login> <password> <personlogin> <role> [<eventname>]
+/// odbiera uczestnikowi <personlogin> rolę <role> nadaną w tym samym zakresie co grant_role
+pub fn revoke_role(conn: &Connection,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()> {
+
+    let granter_id = authenticate(conn, &credentials)?;
+    let event_id = role_event(conn, eventname)?;
+    authorize(conn, granter_id, Permission::ManageRoles, event_id)?;
+    let person_id = authorize_person(conn, &personlogin, None)?;
+
+    let query = r#"
+        DELETE FROM person_roles
+        WHERE person_id = $1
+          AND role = $2
+          AND event_id IS NOT DISTINCT FROM $3"#;
+    let deletes = conn.execute(query, &[&person_id, &role.as_str(), &event_id])
+        .chain_err(|| "Unable to revoke the role")?;
+    if deletes == 0 {
+        bail!(ErrorKind::NotFound("The person has no such role".into()))
+    }
+
+    Ok(())
+}
+
+/// (O) grant_event_organizer <login> <password> <personlogin> <eventname> [<owner>]
+/// czyni uczestnika <personlogin> organizatorem wydarzenia <eventname>, a jeśli <owner>
+/// - również jego właścicielem
+///
+/// Organizatorzy zatwierdzają i odrzucają referaty wydarzenia (talk, reject) oraz widzą
+/// jego propozycje i porzucone referaty (proposals, abandoned_talks). Organizatorów
+/// wybierają wyłącznie właściciele wydarzenia, także spośród administratorów.
+pub fn grant_event_organizer(conn: &Connection,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()> {
+
+    let granter_id = authenticate(conn, &credentials)?;
+    let event_id = authorize_owner(conn, granter_id, &eventname)?;
+    let person_id = authorize_person(conn, &personlogin, None)?;
+
+    insert_organizer(conn, event_id, person_id, owner)
+}
+
+/// (O) revoke_event_organizer <login> <password> <personlogin> <eventname>
+/// odbiera uczestnikowi <personlogin> organizację wydarzenia <eventname>,
+/// wydarzenie nie może stracić ostatniego właściciela
+pub fn revoke_event_organizer(conn: &Connection,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()> {
+
+    let granter_id = authenticate(conn, &credentials)?;
+    let event_id = authorize_owner(conn, granter_id, &eventname)?;
+    let person_id = authorize_person(conn, &personlogin, None)?;
+
+    let query = r#"
+        DELETE FROM event_organizers
+        WHERE event_id = $1
+          AND person_id = $2
+        RETURNING is_owner"#;
+    let was_owner: bool = conn.query(query, &[&event_id, &person_id])
+        .chain_err(|| "Unable to revoke the organizer")?
+        .iter()
+        .map(|row| row.get("is_owner"))
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound("The person does not organize the event".into()))?;
+
+    let query = r#"
+        SELECT count(*) AS owners FROM event_organizers
+        WHERE event_id = $1
+          AND is_owner"#;
+    let owners: i64 = conn.query(query, &[&event_id])
+        .chain_err(|| "Unable to count the owners")?
+        .get(0)
+        .get("owners");
+    if was_owner && owners == 0 {
+        bail!(ErrorKind::Conflict("The event would have no owner".into()))
+    }
+
+    Ok(())
+}
+
+impl Storage for Connection {
+    fn open(config: &ConnectionConfig) -> Result<Connection> {
+        establish_connection(config)
+    }
+
+    fn setup(&self) -> Result<()> {
+        setup_database(self)
+    }
+
+    fn check_schema(&self) -> Result<()> {
+        migrate::check(self)
+    }
+
+    fn atomically<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Result<T>
+    {
+        let tx = self.transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    fn create_organizer_account(&self, newlogin: String, newpassword: String) -> Result<()> {
+        create_organizer_account(self, newlogin, newpassword)
+    }
+
+    fn use_organizer_secret(&self, secret: &str) -> Result<bool> {
+        use_organizer_secret(self, secret)
+    }
+
+    fn record_organizer_attempt(&self, newlogin: &str, outcome: OrganizerOutcome) -> Result<()> {
+        record_organizer_attempt(self, newlogin, outcome)
+    }
+
+    fn organizer_attempts(&self) -> Result<Vec<OrganizerAttempt>> {
+        organizer_attempts(self)
+    }
+
+    fn create_event(&self,
+                    credentials: Credentials,
+                    eventname: String,
+                    start_timestamp: DateTime,
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
+                    -> Result<()> {
+        create_event(self, credentials, eventname, start_timestamp, end_timestamp, capacity)
+    }
+
+    fn set_event_capacity(&self,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()> {
+        set_event_capacity(self, credentials, eventname, capacity)
+    }
+
+    fn create_room(&self,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()> {
+        create_room(self, credentials, room, venue, capacity, floor)
+    }
+
+    fn list_rooms(&self, credentials: Credentials, venue: Option<String>) -> Result<Vec<Room>> {
+        list_rooms(self, credentials, venue)
+    }
+
+    fn create_user(&self,
+                   credentials: Credentials,
+                   newlogin: String,
+                   newpassword: String)
+                   -> Result<()> {
+        create_user(self, credentials, newlogin, newpassword)
+    }
+
+    fn register_or_accept_talk(&self,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
+                               talk: String,
+                               title: String,
+                               start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
+                               room: String,
+                               initial_evaluation: i16,
+                               eventname: String)
+                               -> Result<()> {
+        register_or_accept_talk(self,
+                                credentials,
+                                speakers,
+                                talk,
+                                title,
+                                start_timestamp,
+                                end_timestamp,
+                                room,
+                                initial_evaluation,
+                                eventname)
+    }
+
+    fn register_user_for_event(&self, credentials: Credentials, eventname: String) -> Result<()> {
+        register_user_for_event(self, credentials, eventname)
+    }
+
+    fn unregister_user_from_event(&self,
+                                  credentials: Credentials,
+                                  eventname: String)
+                                  -> Result<()> {
+        unregister_user_from_event(self, credentials, eventname)
+    }
+
+    fn attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        attendance(self, credentials, talk)
+    }
+
+    fn cancel_attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        cancel_attendance(self, credentials, talk)
+    }
+
+    fn evaluation(&self,
+                  credentials: Credentials,
+                  talk: String,
+                  rating: i16,
+                  comment: Option<String>)
+                  -> Result<()> {
+        evaluation(self, credentials, talk, rating, comment)
+    }
+
+    fn retract_evaluation(&self, credentials: Credentials, talk: String) -> Result<()> {
+        retract_evaluation(self, credentials, talk)
+    }
+
+    fn reject_spontaneous_talk(&self, credentials: Credentials, talk: String) -> Result<()> {
+        reject_spontaneous_talk(self, credentials, talk)
+    }
+
+    fn accept_talk(&self,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()> {
+        accept_talk(self, credentials, talk, room, initial_evaluation)
+    }
+
+    fn propose_spontaneous_talk(&self,
+                                credentials: Credentials,
+                                talk: String,
+                                title: String,
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
+                                -> Result<()> {
+        propose_spontaneous_talk(self,
+                                 credentials,
+                                 talk,
+                                 title,
+                                 start_timestamp,
+                                 end_timestamp,
+                                 speakers,
+                                 eventname,
+                                 room)
+    }
+
+    fn make_friends(&self, credentials: Credentials, login2: String) -> Result<()> {
+        make_friends(self, credentials, login2)
+    }
+
+    fn unfriend(&self, credentials: Credentials, login2: String) -> Result<()> {
+        unfriend(self, credentials, login2)
+    }
+
+    fn user_plan(&self, login: String, page: Page) -> Result<Paged<UserPlan>> {
+        user_plan(self, login, page)
+    }
+
+    fn day_plan(&self, date: Date) -> Result<Vec<DayPlan>> {
+        day_plan(self, date)
+    }
+
+    fn event_plan(&self, eventname: String) -> Result<Vec<DayPlan>> {
+        event_plan(self, eventname)
+    }
+
+    fn best_talks(&self,
+                  start_timestamp: DateTime,
+                  end_timestamp: DateTime,
+                  page: Page,
+                  all: bool)
+                  -> Result<Paged<BestTalk>> {
+        best_talks(self, start_timestamp, end_timestamp, page, all)
+    }
+
+    fn most_popular_talks(&self,
+                          start_timestamp: DateTime,
+                          end_timestamp: DateTime,
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>> {
+        most_popular_talks(self, start_timestamp, end_timestamp, page)
+    }
+
+    fn attended_talks(&self, credentials: Credentials) -> Result<Vec<AttendedTalk>> {
+        attended_talks(self, credentials)
+    }
+
+    fn abandoned_talks(&self,
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>> {
+        abandoned_talks(self, credentials, page)
+    }
+
+    fn recently_added_talks(&self, page: Page) -> Result<Paged<RecentlyAddedTalk>> {
+        recently_added_talks(self, page)
+    }
+
+    fn search_talks(&self,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>> {
+        search_talks(self, credentials, query, start_timestamp, end_timestamp, status, page)
+    }
+
+    fn rejected_talks(&self, credentials: Credentials) -> Result<Vec<RejectedTalk>> {
+        rejected_talks(self, credentials)
+    }
+
+    fn proposals(&self, credentials: Credentials) -> Result<Vec<Proposal>> {
+        proposals(self, credentials)
+    }
+
+    fn friends_talks(&self,
+                     credentials: Credentials,
+                     start_timestamp: DateTime,
+                     end_timestamp: DateTime,
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>> {
+        friends_talks(self, credentials, start_timestamp, end_timestamp, page)
+    }
+
+    fn friends_events(&self,
+                      credentials: Credentials,
+                      eventname: String)
+                      -> Result<Vec<FriendsEvent>> {
+        friends_events(self, credentials, eventname)
+    }
+
+    fn recommended_talks(&self,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>> {
+        recommended_talks(self, credentials, start_timestamp, end_timestamp, page)
+    }
+
+    fn waitlist(&self, credentials: Credentials, eventname: String) -> Result<Vec<WaitlistEntry>> {
+        waitlist(self, credentials, eventname)
+    }
+
+    fn rating_history(&self, credentials: Credentials, talk: String) -> Result<Vec<RatingChange>> {
+        rating_history(self, credentials, talk)
+    }
+
+    fn talk_reviews(&self, credentials: Credentials, talk: String) -> Result<Vec<TalkReview>> {
+        talk_reviews(self, credentials, talk)
+    }
+
+    fn my_feedback(&self, credentials: Credentials) -> Result<Vec<Feedback>> {
+        my_feedback(self, credentials)
+    }
+
+    fn open_session(&self, login: String, password: String) -> Result<Vec<Session>> {
+        open_session(self, login, password)
+    }
+
+    fn close_session(&self, token: String) -> Result<()> {
+        close_session(self, token)
+    }
+
+    fn revoke_sessions(&self, credentials: Credentials) -> Result<()> {
+        revoke_sessions(self, credentials)
+    }
+
+    fn grant_role(&self,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()> {
+        grant_role(self, credentials, personlogin, role, eventname)
+    }
+
+    fn revoke_role(&self,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()> {
+        revoke_role(self, credentials, personlogin, role, eventname)
+    }
+
+    fn grant_event_organizer(&self,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()> {
+        grant_event_organizer(self, credentials, personlogin, eventname, owner)
+    }
+
+    fn revoke_event_organizer(&self,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()> {
+        revoke_event_organizer(self, credentials, personlogin, eventname)
+    }
+}
+
+/// Szuka osoby <login>, a jeśli podano <password>, sprawdza jej hasło.
+fn authorize_person(conn: &Connection, login: &str, password: Option<&str>) -> Result<i32> {
+    let query = r#"
+        SELECT id, password FROM persons
+        WHERE login = $1
+        LIMIT 1"#;
+    let person: Option<(i32, String)> = conn.query(query, &[&login])
         .chain_err(|| "Unable to authorize person")?
         .iter()
+        .map(|row| (row.get("id"), row.get("password")))
+        .next();
+    let (id, password_hash) = match (person, password) {
+        (Some(person), _) => person,
+        // nie zdradzamy, czy login istnieje - ani odpowiedzią, ani jej czasem
+        (None, Some(password)) => {
+            verify_unknown_login(password);
+            bail!(ErrorKind::Unauthorized("Invalid login or password".into()))
+        }
+        (None, None) => bail!(ErrorKind::NotFound("Requested person not found".into())),
+    };
+
+    if let Some(password) = password {
+        if !verify_password(conn, id, password, &password_hash)? {
+            bail!(ErrorKind::Unauthorized("Invalid login or password".into()))
+        }
+    }
+
+    Ok(id)
+}
+
+/// Czy osoba ma uprawnienie `permission` dzięki roli nadanej dla wszystkich wydarzeń
+/// albo dla wydarzenia `event_id`.
+fn has_permission(conn: &Connection,
+                  person_id: i32,
+                  permission: Permission,
+                  event_id: Option<i32>)
+                  -> Result<bool> {
+    let query = r#"
+        SELECT role FROM person_roles
+        WHERE person_id = $1
+          AND (event_id IS NULL OR event_id = $2)"#;
+    let roles = conn.query(query, &[&person_id, &event_id])
+        .chain_err(|| "Unable to load roles")?;
+    Ok(roles.iter().any(|row| permission.is_granted_by(&row.get::<_, String>("role"))))
+}
+
+/// Jak `has_permission`, ale brak uprawnienia jest błędem.
+fn authorize(conn: &Connection,
+             person_id: i32,
+             permission: Permission,
+             event_id: Option<i32>)
+             -> Result<()> {
+    if !has_permission(conn, person_id, permission, event_id)? {
+        return Err(permission.denied());
+    }
+    Ok(())
+}
+
+/// Czy osoba organizuje wydarzenie `event_id` albo ma uprawnienie `permission` dzięki roli
+/// nadanej właśnie dla niego; role nadane dla wszystkich wydarzeń liczą się tylko wtedy,
+/// gdy nie ma wydarzenia.
+fn may_organize(conn: &Connection,
+                person_id: i32,
+                permission: Permission,
+                event_id: Option<i32>)
+                -> Result<bool> {
+    let event_id = match event_id {
+        Some(event_id) => event_id,
+        None => return has_permission(conn, person_id, permission, None),
+    };
+    let query = format!(r#"
+        SELECT event_id FROM ({}) AS organized
+        WHERE event_id = $2"#,
+                        organized_events(permission));
+    let rows = conn.query(&query, &[&person_id, &event_id])
+        .chain_err(|| "Unable to load organized events")?;
+    Ok(!rows.is_empty())
+}
+
+/// Jak `may_organize`, ale brak uprawnienia jest błędem.
+fn authorize_organizer(conn: &Connection,
+                       person_id: i32,
+                       permission: Permission,
+                       event_id: Option<i32>)
+                       -> Result<()> {
+    if !may_organize(conn, person_id, permission, event_id)? {
+        return Err(match event_id {
+                       Some(_) => permission.denied_in_event(),
+                       None => permission.denied(),
+                   });
+    }
+    Ok(())
+}
+
+/// Podzapytanie o wydarzenia, które osoba `$1` organizuje albo w których ma uprawnienie
+/// `permission` dzięki roli nadanej właśnie dla nich.
+fn organized_events(permission: Permission) -> String {
+    format!(r#"
+        SELECT event_id FROM event_organizers
+        WHERE person_id = $1
+        UNION
+        SELECT event_id FROM person_roles
+        WHERE person_id = $1
+          AND event_id IS NOT NULL
+          AND role IN ({})"#,
+            permission.sql_roles())
+}
+
+/// Wydarzenie <eventname>, o ile osoba jest jego właścicielem.
+fn authorize_owner(conn: &Connection, person_id: i32, eventname: &str) -> Result<i32> {
+    let event_id = event_named(conn, eventname)?.ok_or_else(|| {
+        ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+    })?;
+    let query = r#"
+        SELECT 1 FROM event_organizers
+        WHERE event_id = $1
+          AND person_id = $2
+          AND is_owner"#;
+    let owner = !conn.query(query, &[&event_id, &person_id])
+        .chain_err(|| "Unable to load the owners")?
+        .is_empty();
+    if !owner {
+        bail!(ErrorKind::Unauthorized("requires owning the event".into()))
+    }
+    Ok(event_id)
+}
+
+/// Czyni osobę organizatorem (a jeśli `owner` - właścicielem) wydarzenia `event_id`.
+fn insert_organizer(conn: &Connection, event_id: i32, person_id: i32, owner: bool) -> Result<()> {
+    let query = r#"
+        INSERT INTO event_organizers (event_id, person_id, is_owner)
+        VALUES ($1, $2, $3)"#;
+    conn.execute(query, &[&event_id, &person_id, &owner])
+        .map(|_| ())
+        .chain_err(|| "Unable to add the organizer")
+}
+
+/// Wydarzenie referatu <talk>; `None` także wtedy, gdy referatu nie ma - zapytanie
+/// zgłosi to samo.
+fn talk_event(conn: &Connection, talk: &str) -> Result<Option<i32>> {
+    let query = r#"
+        SELECT event_id FROM talks
+        WHERE talk = $1"#;
+    Ok(conn.query(query, &[&talk])
+           .chain_err(|| "Unable to load the talk")?
+           .iter()
+           .filter_map(|row| row.get("event_id"))
+           .next())
+}
+
+/// Wydarzenie <eventname> trwające w chwili `at`.
+fn event_at(conn: &Connection, eventname: &str, at: DateTime) -> Result<i32> {
+    let query = r#"
+        SELECT id FROM events
+        WHERE eventname = $1
+          AND start_timestamp <= $2
+          AND end_timestamp >= $2
+        LIMIT 1"#;
+    conn.query(query, &[&eventname, &at])
+        .chain_err(|| "Unable to load event")?
+        .iter()
         .map(|row| row.get("id"))
         .next()
-        .ok_or_else(|| "Requested person not found".into())
+        .ok_or_else(|| {
+            ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname)).into()
+        })
+}
+
+/// Wydarzenie <eventname>; `None` także wtedy, gdy wydarzenia nie ma - zapytanie
+/// zgłosi to samo.
+fn event_named(conn: &Connection, eventname: &str) -> Result<Option<i32>> {
+    let query = r#"
+        SELECT id FROM events
+        WHERE eventname = $1"#;
+    Ok(conn.query(query, &[&eventname])
+           .chain_err(|| "Unable to load event")?
+           .iter()
+           .map(|row| row.get("id"))
+           .next())
+}
+
+/// Zakres roli z grant_role i revoke_role: wydarzenie <eventname> albo wszystkie wydarzenia.
+fn role_event(conn: &Connection, eventname: Option<String>) -> Result<Option<i32>> {
+    match eventname {
+        Some(eventname) => {
+            let event_id = event_named(conn, &eventname)?.ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+            })?;
+            Ok(Some(event_id))
+        }
+        None => Ok(None),
+    }
 }
 
-fn try_authorize_person(conn: &Connection,
-                        login: String,
-                        password: String)
-                        -> Result<(PersonType, i32)> {
-    use self::PersonType::*;
-    authorize_person_as(conn, &login, Some(&password), Organizer)
-        .map(|id| (Organizer, id))
-        .or_else(|_| authorize_person_as(conn, &login, Some(&password), User).map(|id| (User, id)))
+/// Nadaje osobie rolę `role` dla wszystkich wydarzeń albo dla wydarzenia `event_id`.
+fn insert_role(conn: &Connection, person_id: i32, role: Role, event_id: Option<i32>) -> Result<()> {
+    let query = r#"
+        INSERT INTO person_roles (person_id, role, event_id)
+        VALUES ($1, $2, $3)"#;
+    conn.execute(query, &[&person_id, &role.as_str(), &event_id])
+        .map(|_| ())
+        .chain_err(|| "Unable to grant the role")
+}
+
+const USER_PLAN: Keyset = Keyset {
+    name: "user_plan",
+    keys: &[asc("start_timestamp", KeyType::Timestamp), asc("talk_id", KeyType::Int)],
+};
+
+const BEST_TALKS: Keyset = Keyset {
+    name: "best_talks",
+    keys: &[desc("average_rate", KeyType::Float), asc("talk_id", KeyType::Int)],
+};
+
+const MOST_POPULAR_TALKS: Keyset = Keyset {
+    name: "most_popular_talks",
+    keys: &[desc("arrivals", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const ABANDONED_TALKS: Keyset = Keyset {
+    name: "abandoned_talks",
+    keys: &[desc("absent", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const RECENTLY_ADDED_TALKS: Keyset = Keyset {
+    name: "recently_added_talks",
+    keys: &[desc("modified_at", KeyType::Timestamp), asc("talk_id", KeyType::Int)],
+};
+
+const SEARCH_TALKS: Keyset = Keyset {
+    name: "search_talks",
+    keys: &[desc("score", KeyType::Float),
+            asc("start_timestamp", KeyType::Timestamp),
+            asc("talk_id", KeyType::Int)],
+};
+
+const FRIENDS_TALKS: Keyset = Keyset {
+    name: "friends_talks",
+    keys: &[asc("start_timestamp", KeyType::Timestamp), asc("talk_id", KeyType::Int)],
+};
+
+const RECOMMENDED_TALKS: Keyset = Keyset {
+    name: "recommended_talks",
+    keys: &[desc("score", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+/// Strona `page` wierszy zapytania `query` posortowanych wg `keyset`; kolumny zapytania
+/// muszą obejmować klucze porządku, a parametry kursora następują po `params`.
+fn query_page<T, F>(conn: &Connection,
+                    keyset: &Keyset,
+                    query: &str,
+                    params: &[&dyn ToSql],
+                    page: &Page,
+                    f: F)
+                    -> Result<Paged<T>>
+    where F: Fn(&Row) -> T
+{
+    let after = match page.cursor {
+        Some(ref cursor) => keyset.decode(cursor)?,
+        None => Vec::new(),
+    };
+    let query = keyset.select(query,
+                              !after.is_empty(),
+                              page.limit,
+                              params.len() + 1,
+                              cursor_param);
+    let mut params = params.to_vec();
+    params.extend(after.iter().map(|key| match *key {
+                                       Key::Int(ref i) => i as &dyn ToSql,
+                                       Key::Float(ref f) => f as &dyn ToSql,
+                                       Key::Text(ref s) => s as &dyn ToSql,
+                                       Key::Timestamp(ref t) => t as &dyn ToSql,
+                                   }));
+    let rows = conn.query(&query, &params)?
+        .iter()
+        .map(|row| (f(&row), sort_keys(&row, keyset)))
+        .collect();
+    Ok(keyset.paginate(rows, page.limit))
+}
+
+/// Parametr z wartością klucza kursora, z jawnym typem, bo Postgres nie zawsze wywnioskuje
+/// go z porównania.
+fn cursor_param(i: usize, typ: KeyType) -> String {
+    let typ = match typ {
+        KeyType::Int => "bigint",
+        KeyType::Float => "float8",
+        KeyType::Text => "text",
+        KeyType::Timestamp => "timestamp",
+    };
+    format!("${}::{}", i, typ)
+}
+
+/// Wartości kluczy porządku wiersza; identyfikatory są typu integer, liczności bigint.
+fn sort_keys(row: &Row, keyset: &Keyset) -> Vec<Key> {
+    keyset.keys
+        .iter()
+        .map(|key| match key.typ {
+                 KeyType::Int => {
+                     match row.get_opt::<_, i32>(key.column) {
+                         Some(Ok(i)) => Key::Int(i as i64),
+                         _ => Key::Int(row.get(key.column)),
+                     }
+                 }
+                 KeyType::Float => Key::Float(row.get(key.column)),
+                 KeyType::Text => Key::Text(row.get(key.column)),
+                 KeyType::Timestamp => Key::Timestamp(row.get(key.column)),
+             })
+        .collect()
+}
+
+/// Autoryzuje wywołującego na podstawie loginu i hasła albo tokenu otwartej sesji.
+/// Uprawnienia sprawdza osobno `authorize`.
+fn authenticate(conn: &Connection, credentials: &Credentials) -> Result<i32> {
+    match *credentials {
+        Credentials::Password { ref login, ref password } => {
+            authorize_person(conn, login, Some(password))
+        }
+        Credentials::Token { ref token } => {
+            let query = r#"
+                SELECT person_id FROM sessions
+                WHERE token = $1
+                  AND expires_at > now()
+                LIMIT 1"#;
+            conn.query(query, &[token])
+                .chain_err(|| "Unable to authorize person")?
+                .iter()
+                .map(|row| row.get("person_id"))
+                .next()
+                .ok_or_else(|| {
+                    ErrorKind::Unauthorized("Session expired or not found".into()).into()
+                })
+        }
+    }
+}
+
+/// Zastępuje prelegentów referatu prelegentem głównym `primary_id` i kolejnymi `others`.
+fn set_speakers(conn: &Connection,
+                talk_id: i32,
+                primary_id: i32,
+                others: &[Speaker])
+                -> Result<()> {
+    let query = r#"
+        DELETE FROM talk_speakers
+        WHERE talk_id = $1"#;
+    conn.execute(query, &[&talk_id])
+        .chain_err(|| "Unable to remove the speakers")?;
+
+    let query = r#"
+        INSERT INTO talk_speakers (talk_id, person_id, role, position)
+        VALUES ($1, $2, $3, $4)"#;
+    conn.execute(query, &[&talk_id, &primary_id, &SpeakerRole::Primary.as_str(), &0i16])
+        .chain_err(|| "Unable to add a speaker")?;
+    for (position, speaker) in others.iter().enumerate() {
+        let person_id = authorize_person(conn, &speaker.login, None)?;
+        conn.execute(query,
+                     &[&talk_id, &person_id, &speaker.role.as_str(), &(position as i16 + 1)])
+            .chain_err(|| "Unable to add a speaker")?;
+    }
+    Ok(())
+}
+
+/// Prelegenci referatu z kolumny `speakers` widoku talk_speaker_lists.
+fn speakers(row: &Row) -> Vec<Speaker> {
+    let speakers: String = row.get("speakers");
+    serde_json::from_str(&speakers).expect("talk_speaker_lists lists speakers as JSON")
+}
+
+/// Nazwa sali `room` w pisowni z create_room, o ile sala istnieje.
+fn room_named(conn: &Connection, room: &str) -> Result<Option<String>> {
+    let query = r#"
+        SELECT room FROM rooms
+        WHERE lower(room) = lower($1)"#;
+    Ok(conn.query(query, &[&room])
+           .chain_err(|| "Unable to load room")?
+           .iter()
+           .map(|row| row.get("room"))
+           .next())
+}
+
+fn person_login(conn: &Connection, person_id: i32) -> Result<String> {
+    let query = r#"
+        SELECT login FROM persons
+        WHERE id = $1"#;
+    conn.query(query, &[&person_id])
+        .chain_err(|| "Unable to load person")?
+        .iter()
+        .map(|row| row.get("login"))
+        .next()
+        .ok_or_else(|| ErrorKind::NotFound("Requested person not found".into()).into())
+}
+
+/// Weryfikuje hasło względem skrótu zapisanego w `persons.password`.
+/// Wiersze sprzed wprowadzenia haszowania przechowują hasło jawnym tekstem -
+/// po udanym logowaniu takie hasło jest od razu zastępowane skrótem.
+fn verify_password(conn: &Connection,
+                   person_id: i32,
+                   password: &str,
+                   password_hash: &str)
+                   -> Result<bool> {
+    match bcrypt::verify(password, password_hash) {
+        Ok(valid) => Ok(valid),
+        Err(_) if constant_time_eq(password_hash.as_bytes(), password.as_bytes()) => {
+            // legacy plaintext row, rehash it
+            let query = r#"
+                UPDATE persons
+                SET password = $1
+                WHERE id = $2"#;
+            conn.execute(query, &[&hash_password(password)?, &person_id])
+                .chain_err(|| "Unable to rehash legacy password")?;
+            Ok(true)
+        }
+        Err(_) => Ok(false),
+    }
 }
@@ -0,0 +1,194 @@
+use errors::*;
+use api::*;
+use routes::Context;
+use storage::Storage;
+use tabular::{self, OutputFormat};
+use super::{response_json, error_json, debug_main_err};
+
+use serde_json::{self, Map, Value};
+use form_urlencoded;
+use std::io::Read;
+use tiny_http::{Header, Method, Server};
+use tiny_http::Request as HttpRequest;
+use tiny_http::Response as HttpResponse;
+
+/// Serves every `Request` variant as an HTTP endpoint named after it, e.g. `POST /talk`
+/// with the request fields as a JSON object in the body, or `GET /day_plan?timestamp=...`
+/// with the fields in the query string. Only requests that don't change anything
+/// may be sent with `GET`.
+///
+/// Requests are handled one by one and share a single `Context`, exactly like
+/// the lines of the stdin protocol. The connection is opened from the settings
+/// before serving, and `open` is rejected, so clients can't repoint it.
+///
+/// A successful `export_ical` responds with the calendar itself rather than JSON,
+/// so that calendar apps can subscribe to e.g. `GET /export_ical?eventname=...`.
+/// Likewise lists in CSV or TSV (`format` of the request, or `format` for all requests)
+/// are sent as they are, with `next_cursor` of a page in the `X-Next-Cursor` header.
+///
+/// Credentials never go in the query string, which ends up in logs and browser history:
+/// a session token may instead be sent as `Authorization: Bearer <token>`.
+pub fn serve<S: Storage>(mut ctx: Context<S>, addr: &str, format: OutputFormat) -> Result<()> {
+    let server = Server::http(addr)
+        .map_err(|e| Error::from(format!("Unable to listen on {}: {}", addr, e)))?;
+
+    for mut request in server.incoming_requests() {
+        let handled = handle(&mut ctx, &mut request, format);
+        let next_cursor = match handled {
+            Ok(Response::Page(_, Some(ref cursor))) => Some(cursor.clone()),
+            _ => None,
+        };
+        let (status, body, content_type) = match handled {
+            Ok(Response::Ok(ResponseInfo::Ical(ics))) => (200, ics, "text/calendar"),
+            Ok(Response::Ok(ResponseInfo::Csv(csv))) |
+            Ok(Response::Page(ResponseInfo::Csv(csv), _)) => (200, csv, "text/csv"),
+            Ok(Response::Ok(ResponseInfo::Tsv(tsv))) |
+            Ok(Response::Page(ResponseInfo::Tsv(tsv), _)) => {
+                (200, tsv, "text/tab-separated-values")
+            }
+            Ok(Response::NotImplemented) => {
+                (501, response_json(Response::NotImplemented).to_string(), "application/json")
+            }
+            Ok(response) => (200, response_json(response).to_string(), "application/json"),
+            Err((status, e)) => (status, e.to_string(), "application/json"),
+        };
+        let content_type = format!("{}; charset=utf-8", content_type);
+        let content_type = Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes())
+            .expect("valid header");
+        let mut response = HttpResponse::from_string(body)
+            .with_status_code(status)
+            .with_header(content_type);
+        if let Some(cursor) = next_cursor {
+            response.add_header(Header::from_bytes(&b"X-Next-Cursor"[..], cursor.as_bytes())
+                                    .expect("valid header"));
+        }
+        if let Err(e) = request.respond(response) {
+            debug_main_err(&Error::with_chain(e, "Unable to send a response"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Largest accepted request body, a larger one is answered with 413.
+const MAX_BODY_BYTES: u64 = 1 << 20;
+
+/// Fields that carry credentials or secrets and are therefore rejected in a query string.
+const SECRET_FIELDS: &[&str] = &["password", "newpassword", "token", "secret"];
+
+/// Resolves the request, or reports the HTTP status and the ERROR response.
+fn handle<S: Storage>(ctx: &mut Context<S>,
+                      request: &mut HttpRequest,
+                      format: OutputFormat)
+                      -> ::std::result::Result<Response, (u16, Value)> {
+    let description = format!("{} {}", request.method(), request.url());
+
+    if *request.method() != Method::Get && *request.method() != Method::Post {
+        let e = Error::from(ErrorKind::Validation("only GET and POST are supported".into()))
+            .chain_err(|| description);
+        return Err(failure(405, &e));
+    }
+    let body = match read_body(request).chain_err(|| description.clone()) {
+        Ok(ref body) if body.len() as u64 > MAX_BODY_BYTES => {
+            let message = format!("request body is larger than {} bytes", MAX_BODY_BYTES);
+            let e = Error::from(ErrorKind::Validation(message)).chain_err(|| description);
+            return Err(failure(413, &e));
+        }
+        Ok(body) => body,
+        Err(e) => return Err(failure(status_of(&e), &e)),
+    };
+    let read = read_request(request, &body).chain_err(|| description.clone());
+    let (call, call_format) = match read {
+        Ok(call) => call,
+        Err(e) => return Err(failure(status_of(&e), &e)),
+    };
+    if let Request::Open(_) = call {
+        let e = Error::from(ErrorKind::Unauthorized("open is not accepted over HTTP".into()))
+            .chain_err(|| description);
+        return Err(failure(status_of(&e), &e));
+    }
+    if *request.method() == Method::Get && call.is_mutating() {
+        let e = Error::from(ErrorKind::Validation("this request changes data, use POST".into()))
+            .chain_err(|| description);
+        return Err(failure(405, &e));
+    }
+
+    call.resolve(ctx)
+        .and_then(|response| tabular::tabulate(response, call_format.unwrap_or(format)))
+        .chain_err(|| description)
+        .map_err(|e| failure(status_of(&e), &e))
+}
+
+/// HTTP status matching the `code` reported in the ERROR response.
+fn status_of(e: &Error) -> u16 {
+    match e.classify().0 {
+        ErrorCode::Validation => 400,
+        ErrorCode::Unauthorized => 401,
+        ErrorCode::NotFound => 404,
+        ErrorCode::Conflict => 409,
+        ErrorCode::NotConnected => 428,
+        ErrorCode::Internal => 500,
+    }
+}
+
+fn failure(status: u16, e: &Error) -> (u16, Value) {
+    debug_main_err(e);
+    (status, error_json(e))
+}
+
+/// Reads at most one byte more than `MAX_BODY_BYTES` of the body,
+/// so that a longer one is recognized without reading all of it.
+fn read_body(request: &mut HttpRequest) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    request.as_reader().take(MAX_BODY_BYTES + 1).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+/// Session token sent as `Authorization: Bearer <token>`, if any.
+fn bearer_token(request: &HttpRequest) -> Option<String> {
+    request.headers()
+        .iter()
+        .find(|header| header.field.equiv("Authorization"))
+        .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+        .map(|token| token.trim().to_owned())
+}
+
+/// Builds the same JSON document as a line of the stdin protocol
+/// and passes it through `read_call`.
+fn read_request(request: &HttpRequest, body: &[u8]) -> Result<(Request, Option<OutputFormat>)> {
+    let url = request.url().to_owned();
+    let (path, query) = match url.find('?') {
+        Some(pos) => (&url[..pos], &url[pos + 1..]),
+        None => (&url[..], ""),
+    };
+    let name = path.trim_matches('/');
+
+    let mut fields = match *request.method() {
+        Method::Get => {
+            let fields: Map<String, Value> = form_urlencoded::parse(query.as_bytes())
+                .map(|(key, value)| (key.into_owned(), Value::String(value.into_owned())))
+                .collect();
+            if let Some(field) = SECRET_FIELDS.iter().find(|&&field| fields.contains_key(field)) {
+                bail!(ErrorKind::Validation(format!("{} is not accepted in the query string, \
+                                                     use POST or an Authorization header",
+                                                    field)))
+            }
+            Value::Object(fields)
+        }
+        Method::Post => {
+            if body.iter().all(u8::is_ascii_whitespace) {
+                Value::Object(Map::new())
+            } else {
+                serde_json::from_slice(body)?
+            }
+        }
+        _ => unreachable!(),
+    };
+    if let (Some(token), Some(fields)) = (bearer_token(request), fields.as_object_mut()) {
+        fields.entry("token").or_insert(Value::String(token));
+    }
+
+    let mut call = Map::new();
+    call.insert(name.to_owned(), fields);
+    read_formatted_call(&Value::Object(call).to_string())
+}
@@ -0,0 +1,153 @@
+use errors::*;
+
+use api::{Date, DateTime, DayPlan};
+use paging::Page;
+use storage::Storage;
+
+use chrono::UTC;
+
+/// Part of the schedule exported by `export_ical`.
+#[derive(Debug, PartialEq)]
+pub enum Schedule {
+    /// All talks of the event.
+    Event(String),
+    /// All talks of the day, like `day_plan`.
+    Day(Date),
+    /// Upcoming talks from the user's events, like `user_plan`.
+    User(String),
+}
+
+const DATETIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// Lines longer than this many octets are folded, see RFC 5545, section 3.1.
+const LINE_OCTETS: usize = 75;
+
+/// Renders the talks of `schedule` as an RFC 5545 calendar, one `VEVENT` per talk.
+pub fn export<S: Storage>(conn: &S, schedule: Schedule) -> Result<String> {
+    let talks = match schedule {
+        Schedule::Event(eventname) => conn.event_plan(eventname)?,
+        Schedule::Day(date) => conn.day_plan(date)?,
+        Schedule::User(login) => {
+            conn.user_plan(login, Page::default())?
+                .rows
+                .into_iter()
+                .map(|plan| {
+                         DayPlan {
+                             talk: plan.talk,
+                             start_timestamp: plan.start_timestamp,
+                             end_timestamp: plan.end_timestamp,
+                             title: plan.title,
+                             room: plan.room,
+                             venue: None,
+                             capacity: None,
+                             floor: None,
+                             speakers: plan.speakers,
+                         }
+                     })
+                .collect()
+        }
+    };
+    Ok(render(&talks, UTC::now().naive_utc()))
+}
+
+/// Talk times are local and written as such ("floating" times), the stamp is in UTC.
+fn render(talks: &[DayPlan], dtstamp: DateTime) -> String {
+    let mut lines = vec!["BEGIN:VCALENDAR".to_owned(),
+                         "VERSION:2.0".to_owned(),
+                         "PRODID:-//rusty_ems//EN".to_owned(),
+                         "CALSCALE:GREGORIAN".to_owned()];
+    for talk in talks {
+        // the talk identifier never changes, so calendar apps can follow updates of the talk
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{}@rusty_ems", escape(&talk.talk)));
+        lines.push(format!("DTSTAMP:{}Z", dtstamp.format(DATETIME_FORMAT)));
+        lines.push(format!("DTSTART:{}", talk.start_timestamp.format(DATETIME_FORMAT)));
+        lines.push(format!("DTEND:{}", talk.end_timestamp.format(DATETIME_FORMAT)));
+        lines.push(format!("SUMMARY:{}", escape(&talk.title)));
+        lines.push(format!("LOCATION:{}", escape(&talk.room)));
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+/// Escapes a TEXT value, see RFC 5545, section 3.3.11.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits a line into chunks of at most `LINE_OCTETS` octets without breaking characters,
+/// each continuation starts with a space.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > LINE_OCTETS {
+            folded.push_str("\r\n ");
+            // the leading space counts towards the limit
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(s: &str) -> DateTime {
+        DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn renders_a_vevent_per_talk() {
+        let talks = vec![DayPlan {
+                             talk: "t1".to_owned(),
+                             start_timestamp: datetime("2030-01-02 10:00:00"),
+                             end_timestamp: datetime("2030-01-02 10:30:00"),
+                             title: "Rust; safely, fast".to_owned(),
+                             room: "A".to_owned(),
+                             venue: None,
+                             capacity: None,
+                             floor: None,
+                             speakers: vec![],
+                         }];
+        let ics = render(&talks, datetime("2029-12-01 08:00:00"));
+        assert_eq!(ics,
+                   "BEGIN:VCALENDAR\r\n\
+                    VERSION:2.0\r\n\
+                    PRODID:-//rusty_ems//EN\r\n\
+                    CALSCALE:GREGORIAN\r\n\
+                    BEGIN:VEVENT\r\n\
+                    UID:t1@rusty_ems\r\n\
+                    DTSTAMP:20291201T080000Z\r\n\
+                    DTSTART:20300102T100000\r\n\
+                    DTEND:20300102T103000\r\n\
+                    SUMMARY:Rust\\; safely\\, fast\r\n\
+                    LOCATION:A\r\n\
+                    END:VEVENT\r\n\
+                    END:VCALENDAR\r\n");
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let line = format!("SUMMARY:{}", "ż".repeat(40));
+        let folded = fold(&line);
+        assert!(folded.split("\r\n").all(|part| part.len() <= LINE_OCTETS));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
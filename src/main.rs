@@ -1,7 +1,14 @@
 // `error_chain!` can recurse deeply
 #![recursion_limit = "1024"]
 
+extern crate bcrypt;
 extern crate chrono;
+extern crate form_urlencoded;
+extern crate native_tls;
+extern crate rand;
+extern crate rusqlite;
+extern crate sha2;
+extern crate tiny_http;
 
 #[macro_use]
 extern crate error_chain;
@@ -18,14 +25,26 @@ extern crate postgres;
 extern crate postgres_derive;
 
 mod api;
+mod connection;
 mod database;
+mod http;
+mod ical;
+#[cfg(test)]
+mod memory;
+mod migrate;
+mod paging;
 mod routes;
+mod sqlite;
+mod storage;
+mod tabular;
 
 // We'll put our errors in an `errors` module, and other modules in
 // this crate will `use errors::*;` to get access to everything
 // `error_chain!` creates.
+#[allow(unexpected_cfgs)]
 mod errors {
     use super::postgres;
+    use super::rusqlite;
     use super::std::num;
     use super::std::io;
     use super::serde_json;
@@ -36,6 +55,193 @@ mod errors {
             Json(serde_json::Error);
             Num(num::ParseIntError);
             Pg(postgres::error::Error);
+            Sqlite(rusqlite::Error);
+        }
+
+        errors {
+            Unauthorized(msg: String) {
+                description("unauthorized")
+                display("{}", msg)
+            }
+            NotFound(msg: String) {
+                description("not found")
+                display("{}", msg)
+            }
+            Conflict(msg: String) {
+                description("conflict")
+                display("{}", msg)
+            }
+            Validation(msg: String) {
+                description("validation failed")
+                display("{}", msg)
+            }
+            NotConnected {
+                description("not connected")
+                display("establish connection first")
+            }
+        }
+    }
+
+    /// Machine-readable category of an error, reported as `code` in ERROR responses.
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+    #[serde(rename_all = "snake_case")]
+    pub enum ErrorCode {
+        Unauthorized,
+        NotFound,
+        Conflict,
+        Validation,
+        NotConnected,
+        Internal,
+    }
+
+    impl Error {
+        /// Walks the error chain from the outermost error and returns
+        /// the first category found together with a message safe to show
+        /// to the client. Uncategorized errors are reported as `internal`.
+        pub fn classify(&self) -> (ErrorCode, String) {
+            use std::error::Error as StdError;
+
+            let mut next: Option<&(dyn StdError + 'static)> = Some(self);
+            while let Some(e) = next {
+                let found = if let Some(e) = e.downcast_ref::<Error>() {
+                    classify_kind(e.kind())
+                } else if let Some(e) = e.downcast_ref::<postgres::error::Error>() {
+                    classify_pg(e)
+                } else if let Some(e) = e.downcast_ref::<rusqlite::Error>() {
+                    classify_sqlite(e)
+                } else if e.is::<num::ParseIntError>() || e.is::<serde_json::Error>() {
+                    Some((ErrorCode::Validation, e.to_string()))
+                } else {
+                    None
+                };
+                if let Some(found) = found {
+                    return found;
+                }
+                next = e.source();
+            }
+            (ErrorCode::Internal, "internal error".to_owned())
+        }
+
+        /// `detail` of the first Postgres error in the chain, which may quote table rows.
+        pub fn pg_detail(&self) -> Option<&str> {
+            use std::error::Error as StdError;
+
+            let mut next: Option<&(dyn StdError + 'static)> = Some(self);
+            while let Some(e) = next {
+                let pg = match e.downcast_ref::<Error>() {
+                    Some(&Error(ErrorKind::Pg(ref pg), _)) => Some(pg),
+                    Some(_) => None,
+                    None => e.downcast_ref::<postgres::error::Error>(),
+                };
+                if let Some(postgres::error::Error::Db(db)) = pg {
+                    return db.detail.as_ref().map(|detail| &detail[..]);
+                }
+                next = e.source();
+            }
+            None
+        }
+    }
+
+    fn classify_kind(kind: &ErrorKind) -> Option<(ErrorCode, String)> {
+        match *kind {
+            ErrorKind::Unauthorized(ref msg) => Some((ErrorCode::Unauthorized, msg.clone())),
+            ErrorKind::NotFound(ref msg) => Some((ErrorCode::NotFound, msg.clone())),
+            ErrorKind::Conflict(ref msg) => Some((ErrorCode::Conflict, msg.clone())),
+            ErrorKind::Validation(ref msg) => Some((ErrorCode::Validation, msg.clone())),
+            ErrorKind::NotConnected => Some((ErrorCode::NotConnected, kind.to_string())),
+            ErrorKind::Json(ref e) => Some((ErrorCode::Validation, e.to_string())),
+            ErrorKind::Num(ref e) => Some((ErrorCode::Validation, e.to_string())),
+            ErrorKind::Pg(ref e) => classify_pg(e),
+            ErrorKind::Sqlite(ref e) => classify_sqlite(e),
+            _ => None,
+        }
+    }
+
+    /// Maps constraint violations and friends onto client-facing codes,
+    /// see https://www.postgresql.org/docs/current/errcodes-appendix.html
+    ///
+    /// The message is fixed per code, as `detail` quotes the offending row;
+    /// `debug_main_err` writes it to stderr instead.
+    fn classify_pg(e: &postgres::error::Error) -> Option<(ErrorCode, String)> {
+        let db = match *e {
+            postgres::error::Error::Db(ref db) => db,
+            _ => return None,
+        };
+        let code = match db.code.code() {
+            "23505" | "23P01" => ErrorCode::Conflict,
+            "23503" => ErrorCode::NotFound,
+            "23502" | "23514" => ErrorCode::Validation,
+            c if c.starts_with("22") => ErrorCode::Validation,
+            c if c.starts_with("28") => ErrorCode::Unauthorized,
+            _ => return None,
+        };
+        Some((code, constraint_message(code).to_owned()))
+    }
+
+    /// The same for SQLite, which reports constraint violations by extended result codes,
+    /// see https://www.sqlite.org/rescode.html
+    ///
+    /// Only the messages our triggers raise are passed on, as SQLite's own name the columns.
+    fn classify_sqlite(e: &rusqlite::Error) -> Option<(ErrorCode, String)> {
+        use self::rusqlite::ffi;
+
+        let (err, msg) = match *e {
+            rusqlite::Error::SqliteFailure(err, ref msg) => (err, msg),
+            _ => return None,
+        };
+        // the triggers standing in for foreign keys raise SQLite's own message
+        let foreign_key = msg.as_ref().is_some_and(|msg| msg == "FOREIGN KEY constraint failed");
+        let code = match err.extended_code {
+            ffi::SQLITE_CONSTRAINT_UNIQUE |
+            ffi::SQLITE_CONSTRAINT_PRIMARYKEY => ErrorCode::Conflict,
+            ffi::SQLITE_CONSTRAINT_FOREIGNKEY => ErrorCode::NotFound,
+            ffi::SQLITE_CONSTRAINT_TRIGGER if foreign_key => ErrorCode::NotFound,
+            ffi::SQLITE_CONSTRAINT_TRIGGER => {
+                return Some((ErrorCode::Conflict,
+                             msg.clone().unwrap_or_else(|| constraint_message(ErrorCode::Conflict)
+                                                               .to_owned())))
+            }
+            ffi::SQLITE_CONSTRAINT_NOTNULL | ffi::SQLITE_CONSTRAINT_CHECK => ErrorCode::Validation,
+            _ => return None,
+        };
+        Some((code, constraint_message(code).to_owned()))
+    }
+
+    /// What the client is told about a database error of category `code`.
+    fn constraint_message(code: ErrorCode) -> &'static str {
+        match code {
+            ErrorCode::Conflict => "conflicts with existing data",
+            ErrorCode::NotFound => "refers to data that does not exist",
+            ErrorCode::Validation => "invalid value",
+            ErrorCode::Unauthorized => "access denied",
+            ErrorCode::NotConnected | ErrorCode::Internal => "internal error",
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use super::rusqlite::ffi;
+
+        fn sqlite_error(code: i32, msg: &str) -> Error {
+            rusqlite::Error::SqliteFailure(ffi::Error::new(code), Some(msg.to_owned())).into()
+        }
+
+        #[test]
+        fn constraint_violations_hide_the_schema() {
+            let unique = sqlite_error(ffi::SQLITE_CONSTRAINT_UNIQUE,
+                                      "UNIQUE constraint failed: persons.login");
+            assert_eq!(unique.classify(),
+                       (ErrorCode::Conflict, "conflicts with existing data".to_owned()));
+            let foreign_key = sqlite_error(ffi::SQLITE_CONSTRAINT_FOREIGNKEY,
+                                           "FOREIGN KEY constraint failed");
+            assert_eq!(foreign_key.classify().0, ErrorCode::NotFound);
+            let room_exists = sqlite_error(ffi::SQLITE_CONSTRAINT_TRIGGER,
+                                           "FOREIGN KEY constraint failed");
+            assert_eq!(room_exists.classify().0, ErrorCode::NotFound);
+            let taken = sqlite_error(ffi::SQLITE_CONSTRAINT_TRIGGER, "room is already taken");
+            assert_eq!(taken.classify(),
+                       (ErrorCode::Conflict, "room is already taken".to_owned()));
         }
     }
 }
@@ -47,19 +253,126 @@ pub use errors::*;
 
 use api::*;
 use routes::Context;
+use storage::{Storage, Backend};
+use connection::ConnectionConfig;
+use tabular::OutputFormat;
+
+const USAGE: &str = "usage: rusty_ems [--config <file>] [--format <json|csv|tsv>] \
+                     [--http <addr> | migrate <status|up|down> | admin audit | \
+                     export_ical <eventname|timestamp|login> <value>]";
 
 // Use this macro to auto-generate the main. You may want to
 // set the `RUST_BACKTRACE` env variable to see a backtrace.
 quick_main!(run);
 
 fn run() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+    let mut http_addr = None;
+    let mut migrate_command = None;
+    let mut admin_command = None;
+    let mut schedule = None;
+    let mut format = OutputFormat::Json;
+    let mut config = ConnectionConfig::from_env()?;
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--http" => http_addr = Some(args.next().ok_or(USAGE)?),
+            "--config" => {
+                let path = args.next().ok_or(USAGE)?;
+                config = config.or(ConnectionConfig::from_file(&path)?);
+            }
+            "--format" => format = args.next().ok_or(USAGE)?.parse()?,
+            "migrate" => migrate_command = Some(args.next().ok_or(USAGE)?),
+            "admin" => admin_command = Some(args.next().ok_or(USAGE)?),
+            "export_ical" => {
+                let field = args.next().ok_or(USAGE)?;
+                let value = args.next().ok_or(USAGE)?;
+                schedule = Some(match &field[..] {
+                    "eventname" => ical::Schedule::Event(value),
+                    "timestamp" => {
+                        let date = Date::parse_from_str(&value, "%Y-%m-%d").chain_err(|| USAGE)?;
+                        ical::Schedule::Day(date)
+                    }
+                    "login" => ical::Schedule::User(value),
+                    _ => bail!(USAGE),
+                });
+            }
+            _ => bail!(USAGE),
+        }
+    }
+
+    match (http_addr, migrate_command, admin_command, schedule) {
+        (None, None, None, None) => run_stdin(Context::<Backend>::new(config), format),
+        (Some(addr), None, None, None) => {
+            let mut ctx = Context::<Backend>::new(config);
+            ctx.connect()?;
+            http::serve(ctx, &addr, format)
+        }
+        (None, Some(command), None, None) => run_migrate(&config, &command),
+        (None, None, Some(command), None) => run_admin(&config, &command),
+        (None, None, None, Some(schedule)) => run_export_ical(&config, schedule),
+        _ => bail!(USAGE),
+    }
+}
+
+/// `audit` lists the attempts to create an organizer account, oldest first.
+fn run_admin(config: &ConnectionConfig, command: &str) -> Result<()> {
+    let db = Backend::connect(config)?;
+    match command {
+        "audit" => {
+            for attempt in db.organizer_attempts()? {
+                println!("{} {} {}",
+                         attempt.attempted_at,
+                         attempt.newlogin,
+                         attempt.outcome.as_str());
+            }
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
+
+/// Prints the calendar, e.g. for a static `.ics` file served next to the conference site.
+fn run_export_ical(config: &ConnectionConfig, schedule: ical::Schedule) -> Result<()> {
+    let db = Backend::connect(config)?;
+    print!("{}", ical::export(&db, schedule)?);
+    Ok(())
+}
+
+fn run_migrate(config: &ConnectionConfig, command: &str) -> Result<()> {
+    let db = Backend::open(config)?;
+    match command {
+        "status" => {
+            for (migration, applied_at) in migrate::status(&db)? {
+                match applied_at {
+                    Some(applied_at) => println!("{} applied {}", migration.version, applied_at),
+                    None => println!("{} pending", migration.version),
+                }
+            }
+        }
+        "up" => {
+            for migration in migrate::up(&db)? {
+                println!("{} applied", migration.version);
+            }
+        }
+        "down" => {
+            match migrate::down(&db)? {
+                Some(migration) => println!("{} reverted", migration.version),
+                None => println!("nothing to revert"),
+            }
+        }
+        _ => bail!(USAGE),
+    }
+    Ok(())
+}
+
+/// Responds in `format` to requests that don't choose their own.
+fn run_stdin<S: Storage>(mut ctx: Context<S>, format: OutputFormat) -> Result<()> {
     use std::io;
     use std::io::BufRead;
 
     let stdin = io::stdin();
-    let mut ctxt: Context = Context::new();
     for (no, line) in stdin.lock().lines().enumerate() {
-        match main_step(&mut ctxt, &line?).chain_err(|| format!("input line {}", no + 1)) {
+        match main_step(&mut ctx, &line?, format).chain_err(|| format!("input line {}", no + 1)) {
             Ok(e) => main_ok(e),
             Err(e) => main_err(&e),
         }
@@ -68,38 +381,42 @@ fn run() -> Result<()> {
     Ok(())
 }
 
-fn main_step(ctx: &mut Context, line: &str) -> Result<Response> {
-    let request: Request = read_call(line)?;
+fn main_step<S: Storage>(ctx: &mut Context<S>,
+                         line: &str,
+                         format: OutputFormat)
+                         -> Result<Response> {
+    let (request, request_format) = read_formatted_call(line)?;
     let response: Response = request.resolve(ctx)?;
-    Ok(response)
+    tabular::tabulate(response, request_format.unwrap_or(format))
 }
 
-fn main_ok(e: Response) -> () {
-    let response_json = match e {
-        Response::Ok(ResponseInfo::Empty) => {
-            let ok_json = json!({ "status": "OK" });
-            ok_json.to_string()
-        }
-        Response::Ok(serializable) => {
-            let ok_json = json!({ "status": "OK", "data": serializable });
-            ok_json.to_string()
-        }
-        Response::NotImplemented => {
-            let not_implemented_json = json!({ "status": "NOT IMPLEMENTED" });
-            not_implemented_json.to_string()
-        }
-    };
-    println!("{}", response_json);
+fn main_ok(e: Response) {
+    println!("{}", response_json(e));
 }
 
-fn main_err(e: &Error) -> () {
-    let error_json = json!({ "status": "ERROR" });
-    println!("{}", error_json.to_string());
+fn main_err(e: &Error) {
+    println!("{}", error_json(e));
     debug_main_err(e);
 }
 
+fn response_json(e: Response) -> serde_json::Value {
+    match e {
+        Response::Ok(ResponseInfo::Empty) => json!({ "status": "OK" }),
+        Response::Ok(serializable) => json!({ "status": "OK", "data": serializable }),
+        Response::Page(serializable, next_cursor) => {
+            json!({ "status": "OK", "data": serializable, "next_cursor": next_cursor })
+        }
+        Response::NotImplemented => json!({ "status": "NOT IMPLEMENTED" }),
+    }
+}
+
+fn error_json(e: &Error) -> serde_json::Value {
+    let (code, message) = e.classify();
+    json!({ "status": "ERROR", "code": code, "message": message })
+}
+
 #[cfg(debug_assertions)]
-fn debug_main_err(e: &Error) -> () {
+fn debug_main_err(e: &Error) {
     use std::io::{stderr, Write};
     let stderr = &mut stderr();
     let errmsg = "Error writing to stderr";
@@ -108,6 +425,10 @@ fn debug_main_err(e: &Error) -> () {
     for e in e.iter().skip(1) {
         writeln!(stderr, "caused by: {}", e).expect(errmsg);
     }
+    // kept out of the response, see `classify_pg`
+    if let Some(detail) = e.pg_detail() {
+        writeln!(stderr, "detail: {}", detail).expect(errmsg);
+    }
     // The backtrace is not always generated. Try to run this example
     // with `RUST_BACKTRACE=1`.
     if let Some(backtrace) = e.backtrace() {
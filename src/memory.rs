@@ -0,0 +1,1600 @@
+use errors::*;
+
+use api::{Date, DateTime, Credentials, Session, WaitlistEntry, RatingChange, TalkReview,
+          Feedback, TalkStatusName, Speaker, SpeakerRole, OrganizerOutcome, OrganizerAttempt,
+          Role};
+use api::{AttendedTalk, UserPlan, DayPlan, BestTalk, MostPopularTalk, AbandonedTalk,
+          RecentlyAddedTalk, RejectedTalk, Proposal, FriendsTalk, FriendsEvent, RecommendedTalk,
+          FoundTalk};
+use connection::ConnectionConfig;
+use paging::{Key, KeyType, Keyset, Page, Paged, asc, desc};
+use storage::{Storage, Permission, SESSION_TTL_HOURS, DEFAULT_TALK_MINUTES, new_session_token, search_rank,
+              secret_digest, constant_time_eq};
+
+use chrono::{Duration, Local};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Keeps everything in memory, so that requests can be routed in tests without
+/// a database server. Mirrors the queries in `database` one by one; passwords
+/// are stored in plain text. Ids are indices into the tables.
+#[derive(Default)]
+pub struct MemoryStorage {
+    tables: RefCell<Tables>,
+}
+
+#[derive(Clone, Default)]
+struct Tables {
+    persons: Vec<Person>,
+    /// (person, role, event), `None` granting the role in every event
+    roles: Vec<(usize, Role, Option<usize>)>,
+    events: Vec<Event>,
+    /// (event, person) -> whether the person owns the event
+    organizers: BTreeMap<(usize, usize), bool>,
+    talks: Vec<Talk>,
+    venues: Vec<String>,
+    rooms: Vec<Room>,
+    /// (person1, person2)
+    knows: BTreeSet<(usize, usize)>,
+    /// (person, event)
+    registered: BTreeSet<(usize, usize)>,
+    /// (person, event) in the order of joining the waitlist
+    waitlist: Vec<(usize, usize)>,
+    /// (person, talk)
+    attended: BTreeSet<(usize, usize)>,
+    /// (person, talk) -> rating
+    ratings: BTreeMap<(usize, usize), i16>,
+    /// (person, talk) -> written review accompanying the rating
+    comments: BTreeMap<(usize, usize), String>,
+    /// (person, talk, rating, changed_at) in the order of changes, `None` when retracted
+    rating_history: Vec<(usize, usize, Option<i16>, DateTime)>,
+    /// token -> (person, expires_at)
+    sessions: BTreeMap<String, (usize, DateTime)>,
+    used_organizer_secrets: BTreeSet<String>,
+    /// (newlogin, outcome, attempted_at) in the order of attempts
+    organizer_attempts: Vec<(String, OrganizerOutcome, DateTime)>,
+    /// Stands in for `talks.modified_at`, which orders talks by the time they were added.
+    clock: u64,
+}
+
+#[derive(Clone)]
+struct Person {
+    login: String,
+    password: String,
+}
+
+#[derive(Clone)]
+struct Event {
+    eventname: String,
+    start_timestamp: DateTime,
+    end_timestamp: DateTime,
+    capacity: Option<i32>,
+}
+
+#[derive(Clone)]
+struct Room {
+    room: String,
+    venue_id: Option<usize>,
+    capacity: Option<i32>,
+    floor: Option<i32>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum TalkStatus {
+    Proposed,
+    Accepted,
+    Rejected,
+}
+
+impl From<TalkStatusName> for TalkStatus {
+    fn from(status: TalkStatusName) -> TalkStatus {
+        match status {
+            TalkStatusName::Proposed => TalkStatus::Proposed,
+            TalkStatusName::Accepted => TalkStatus::Accepted,
+            TalkStatusName::Rejected => TalkStatus::Rejected,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Talk {
+    talk: String,
+    status: TalkStatus,
+    title: String,
+    speaker_id: usize,
+    /// (person, role) in order, starting with the primary speaker
+    speakers: Vec<(usize, SpeakerRole)>,
+    event_id: Option<usize>,
+    room: Option<String>,
+    start_timestamp: DateTime,
+    end_timestamp: DateTime,
+    modified_at: u64,
+}
+
+impl Talk {
+    fn is_given_by(&self, person_id: usize) -> bool {
+        self.speakers.iter().any(|&(p, _)| p == person_id)
+    }
+}
+
+fn now() -> DateTime {
+    Local::now().naive_local()
+}
+
+/// Lists are sorted here, so the keys name no columns, only the values of a row that
+/// order it; ids are indices and `modified_at` is the clock.
+const USER_PLAN: Keyset = Keyset {
+    name: "user_plan",
+    keys: &[asc("start_timestamp", KeyType::Timestamp), asc("talk_id", KeyType::Int)],
+};
+
+const BEST_TALKS: Keyset = Keyset {
+    name: "best_talks",
+    keys: &[desc("average_rate", KeyType::Float), asc("talk_id", KeyType::Int)],
+};
+
+const MOST_POPULAR_TALKS: Keyset = Keyset {
+    name: "most_popular_talks",
+    keys: &[desc("arrivals", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const ABANDONED_TALKS: Keyset = Keyset {
+    name: "abandoned_talks",
+    keys: &[desc("absent", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const RECENTLY_ADDED_TALKS: Keyset = Keyset {
+    name: "recently_added_talks",
+    keys: &[desc("modified_at", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const SEARCH_TALKS: Keyset = Keyset {
+    name: "search_talks",
+    keys: &[desc("score", KeyType::Float),
+            asc("start_timestamp", KeyType::Timestamp),
+            asc("talk_id", KeyType::Int)],
+};
+
+const FRIENDS_TALKS: Keyset = Keyset {
+    name: "friends_talks",
+    keys: &[asc("start_timestamp", KeyType::Timestamp), asc("talk_id", KeyType::Int)],
+};
+
+const RECOMMENDED_TALKS: Keyset = Keyset {
+    name: "recommended_talks",
+    keys: &[desc("score", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+fn in_range(talk: &Talk, start_timestamp: DateTime, end_timestamp: DateTime) -> bool {
+    start_timestamp <= talk.start_timestamp && talk.start_timestamp <= end_timestamp
+}
+
+fn average(ratings: &[i16]) -> Option<f64> {
+    if ratings.is_empty() {
+        None
+    } else {
+        Some(ratings.iter().map(|&r| f64::from(r)).sum::<f64>() / ratings.len() as f64)
+    }
+}
+
+impl Tables {
+    fn authorize_person(&self, login: &str, password: Option<&str>) -> Result<usize> {
+        let id = self.persons
+            .iter()
+            .position(|p| p.login == login)
+            .ok_or_else(|| match password {
+                Some(_) => ErrorKind::Unauthorized("Invalid login or password".into()),
+                None => ErrorKind::NotFound("Requested person not found".into()),
+            })?;
+        if let Some(password) = password {
+            if !constant_time_eq(self.persons[id].password.as_bytes(), password.as_bytes()) {
+                bail!(ErrorKind::Unauthorized("Invalid login or password".into()))
+            }
+        }
+        Ok(id)
+    }
+
+    fn authenticate(&self, credentials: &Credentials) -> Result<usize> {
+        match *credentials {
+            Credentials::Password { ref login, ref password } => {
+                self.authorize_person(login, Some(password))
+            }
+            Credentials::Token { ref token } => {
+                self.sessions
+                    .get(token)
+                    .filter(|&&(_, expires_at)| expires_at > now())
+                    .map(|&(id, _)| id)
+                    .ok_or_else(|| {
+                        ErrorKind::Unauthorized("Session expired or not found".into()).into()
+                    })
+            }
+        }
+    }
+
+    fn has_permission(&self,
+                      person_id: usize,
+                      permission: Permission,
+                      event_id: Option<usize>)
+                      -> bool {
+        self.roles.iter().any(|&(p, role, e)| {
+                                  p == person_id && (e.is_none() || e == event_id) &&
+                                  permission.is_granted_by(role.as_str())
+                              })
+    }
+
+    fn authorize(&self,
+                 person_id: usize,
+                 permission: Permission,
+                 event_id: Option<usize>)
+                 -> Result<()> {
+        if !self.has_permission(person_id, permission, event_id) {
+            return Err(permission.denied());
+        }
+        Ok(())
+    }
+
+    /// Organizing the event, or a role granted for it alone, or without an event a role
+    /// granted in every event.
+    fn may_organize(&self,
+                    person_id: usize,
+                    permission: Permission,
+                    event_id: Option<usize>)
+                    -> bool {
+        match event_id {
+            Some(event_id) => {
+                self.organizers.contains_key(&(event_id, person_id)) ||
+                self.roles.iter().any(|&(p, role, e)| {
+                                          p == person_id && e == Some(event_id) &&
+                                          permission.is_granted_by(role.as_str())
+                                      })
+            }
+            None => self.has_permission(person_id, permission, None),
+        }
+    }
+
+    fn authorize_organizer(&self,
+                           person_id: usize,
+                           permission: Permission,
+                           event_id: Option<usize>)
+                           -> Result<()> {
+        if !self.may_organize(person_id, permission, event_id) {
+            return Err(match event_id {
+                           Some(_) => permission.denied_in_event(),
+                           None => permission.denied(),
+                       });
+        }
+        Ok(())
+    }
+
+    fn authorize_owner(&self, person_id: usize, eventname: &str) -> Result<usize> {
+        let event_id = self.event(eventname)?;
+        if self.organizers.get(&(event_id, person_id)) != Some(&true) {
+            bail!(ErrorKind::Unauthorized("requires owning the event".into()))
+        }
+        Ok(event_id)
+    }
+
+    fn insert_organizer(&mut self, event_id: usize, person_id: usize, owner: bool) -> Result<()> {
+        if self.organizers.contains_key(&(event_id, person_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (event_id, person_id)=({}, {}) already exists.",
+                                              event_id,
+                                              person_id)))
+        }
+        self.organizers.insert((event_id, person_id), owner);
+        Ok(())
+    }
+
+    /// The event of a talk, `None` also for a missing talk, which the request reports itself.
+    fn talk_event(&self, talk: &str) -> Option<usize> {
+        self.talks.iter().find(|t| t.talk == talk).and_then(|t| t.event_id)
+    }
+
+    fn event_named(&self, eventname: &str) -> Option<usize> {
+        self.events.iter().position(|e| e.eventname == eventname)
+    }
+
+    /// The scope of a role in grant_role and revoke_role, `None` meaning every event.
+    fn role_event(&self, eventname: Option<String>) -> Result<Option<usize>> {
+        match eventname {
+            Some(eventname) => Ok(Some(self.event(&eventname)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn insert_role(&mut self, person_id: usize, role: Role, event_id: Option<usize>) -> Result<()> {
+        if self.roles.contains(&(person_id, role, event_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (person_id, role, event_id)=({}, {}, {:?}) \
+                                               already exists.",
+                                              person_id,
+                                              role.as_str(),
+                                              event_id)))
+        }
+        self.roles.push((person_id, role, event_id));
+        Ok(())
+    }
+
+    fn event(&self, eventname: &str) -> Result<usize> {
+        self.event_named(eventname)
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+                    .into()
+            })
+    }
+
+    /// Names of rooms and venues are compared regardless of case.
+    fn room_named(&self, room: &str) -> Option<usize> {
+        self.rooms
+            .iter()
+            .position(|r| r.room.to_lowercase() == room.to_lowercase())
+    }
+
+    fn venue_named(&self, venue: &str) -> Option<usize> {
+        self.venues
+            .iter()
+            .position(|v| v.to_lowercase() == venue.to_lowercase())
+    }
+
+    /// Venue, capacity and floor of the room a talk takes.
+    fn room_details(&self, room: &Option<String>) -> (Option<String>, Option<i32>, Option<i32>) {
+        match room.as_ref().and_then(|room| self.rooms.iter().find(|r| &r.room == room)) {
+            Some(r) => (r.venue_id.map(|v| self.venues[v].clone()), r.capacity, r.floor),
+            None => (None, None, None),
+        }
+    }
+
+    /// The primary speaker `speaker_id` followed by `others`.
+    fn speakers_of(&self,
+                   speaker_id: usize,
+                   others: &[Speaker])
+                   -> Result<Vec<(usize, SpeakerRole)>> {
+        let mut speakers = vec![(speaker_id, SpeakerRole::Primary)];
+        for speaker in others {
+            let person_id = self.authorize_person(&speaker.login, None)?;
+            if speakers.iter().any(|&(p, _)| p == person_id) {
+                bail!(ErrorKind::Conflict(format!("Key (person_id)=({}) already exists.",
+                                                  person_id)))
+            }
+            speakers.push((person_id, speaker.role));
+        }
+        Ok(speakers)
+    }
+
+    fn speakers(&self, talk: &Talk) -> Vec<Speaker> {
+        talk.speakers
+            .iter()
+            .map(|&(p, role)| Speaker { login: self.login(p), role })
+            .collect()
+    }
+
+    fn accepted_talk(&self, talk: &str) -> Option<usize> {
+        self.talks
+            .iter()
+            .position(|t| t.talk == talk && t.status == TalkStatus::Accepted)
+    }
+
+    fn login(&self, person_id: usize) -> String {
+        self.persons[person_id].login.clone()
+    }
+
+    /// Znajomość jest nawiązana, jeśli obaj uczestnicy chcą ją nawiązać.
+    fn friends(&self, person_id: usize) -> BTreeSet<usize> {
+        self.knows
+            .iter()
+            .filter(|&&(p1, p2)| p1 == person_id && self.knows.contains(&(p2, p1)))
+            .map(|&(_, p2)| p2)
+            .collect()
+    }
+
+    /// Creates a person with `role` in every event.
+    fn insert_person(&mut self, login: String, password: String, role: Role) -> Result<()> {
+        if self.persons.iter().any(|p| p.login == login) {
+            bail!(ErrorKind::Conflict(format!("Key (login)=({}) already exists.", login)))
+        }
+        self.persons.push(Person { login, password });
+        let person_id = self.persons.len() - 1;
+        self.insert_role(person_id, role, None)
+    }
+
+    fn rate(&mut self, person_id: usize, talk_id: usize, rating: i16) -> Result<()> {
+        if self.ratings.contains_key(&(person_id, talk_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (person_id, talk_id)=({}, {}) already exists.",
+                                              person_id,
+                                              talk_id)))
+        }
+        self.ratings.insert((person_id, talk_id), rating);
+        self.rating_history.push((person_id, talk_id, Some(rating), now()));
+        Ok(())
+    }
+
+    /// Free seats of the event, `None` if it takes everyone.
+    fn free_seats(&self, event_id: usize) -> Option<i64> {
+        let registered = self.registered.iter().filter(|&&(_, e)| e == event_id).count();
+        self.events[event_id].capacity.map(|c| i64::from(c) - registered as i64)
+    }
+
+    fn promote_waitlisted(&mut self, event_id: usize) {
+        while self.free_seats(event_id).is_none_or(|free| free > 0) {
+            match self.waitlist.iter().position(|&(_, e)| e == event_id) {
+                Some(i) => {
+                    let entry = self.waitlist.remove(i);
+                    self.registered.insert(entry);
+                }
+                None => break,
+            }
+        }
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    /// Talks with the given status in the order of their ids.
+    fn talks_with(&self, status: TalkStatus) -> Vec<(usize, &Talk)> {
+        self.talks
+            .iter()
+            .enumerate()
+            .filter(|&(_, t)| t.status == status)
+            .collect()
+    }
+
+    /// The event running at `at`.
+    fn event_at(&self, eventname: &str, at: DateTime) -> Result<usize> {
+        self.events
+            .iter()
+            .position(|e| {
+                          e.eventname == eventname && e.start_timestamp <= at &&
+                          e.end_timestamp >= at
+                      })
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+                    .into()
+            })
+    }
+
+    /// The part of talk and accept after authentication, the initial evaluation being
+    /// optional.
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_talk(&mut self,
+                     person_id: usize,
+                     speakers: Vec<Speaker>,
+                     talk: String,
+                     title: String,
+                     start_timestamp: DateTime,
+                     end_timestamp: Option<DateTime>,
+                     room: String,
+                     initial_evaluation: Option<i16>,
+                     eventname: String)
+                     -> Result<()> {
+        let (primary, others) = speakers.split_first()
+            .ok_or_else(|| ErrorKind::Validation("a talk needs a speaker".into()))?;
+        let speaker_id = self.authorize_person(&primary.login, None)?;
+        let speakers = self.speakers_of(speaker_id, others)?;
+        let event_id = if !eventname.is_empty() {
+            Some(self.event_at(&eventname, start_timestamp)?)
+        } else {
+            None
+        };
+        self.authorize_organizer(person_id, Permission::ScheduleTalks, event_id)?;
+        // replacing an existing talk moves it out of its event as well
+        if let Some(current) = self.talk_event(&talk) {
+            if Some(current) != event_id {
+                self.authorize_organizer(person_id, Permission::ScheduleTalks, Some(current))?;
+            }
+        }
+
+        let room = match self.room_named(&room) {
+            Some(room_id) => self.rooms[room_id].room.clone(),
+            None => bail!(ErrorKind::NotFound(format!("room with room=`{}` not found", room))),
+        };
+
+        // keep the duration of a proposal unless told otherwise
+        let existing = self.talks.iter().position(|x| x.talk == talk);
+        let duration = existing
+            .map(|i| &self.talks[i])
+            .map(|x| x.end_timestamp.signed_duration_since(x.start_timestamp))
+            .unwrap_or_else(|| Duration::minutes(DEFAULT_TALK_MINUTES.into()));
+        let end_timestamp = end_timestamp.unwrap_or(start_timestamp + duration);
+
+        if let Some(colliding) = self.talks.iter().find(|x| {
+            x.status == TalkStatus::Accepted && x.room.as_ref() == Some(&room) && x.talk != talk &&
+            x.start_timestamp < end_timestamp && start_timestamp < x.end_timestamp
+        }) {
+            bail!(ErrorKind::Conflict(format!("room `{}` is already taken by talk `{}`",
+                                              room,
+                                              colliding.talk)))
+        }
+
+        let accepted = Talk {
+            talk,
+            status: TalkStatus::Accepted,
+            title,
+            speaker_id,
+            speakers,
+            event_id,
+            room: Some(room),
+            start_timestamp,
+            end_timestamp,
+            modified_at: self.tick(),
+        };
+        let talk_id = match existing {
+            Some(i) => {
+                self.talks[i] = accepted;
+                i
+            }
+            None => {
+                self.talks.push(accepted);
+                self.talks.len() - 1
+            }
+        };
+
+        match initial_evaluation {
+            Some(initial_evaluation) => self.rate(person_id, talk_id, initial_evaluation),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn open(_: &ConnectionConfig) -> Result<MemoryStorage> {
+        Ok(MemoryStorage::default())
+    }
+
+    fn setup(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn check_schema(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn atomically<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Result<T>
+    {
+        let snapshot = self.tables.borrow().clone();
+        f().inspect_err(|_| *self.tables.borrow_mut() = snapshot)
+    }
+
+    fn create_organizer_account(&self, newlogin: String, newpassword: String) -> Result<()> {
+        self.tables.borrow_mut().insert_person(newlogin, newpassword, Role::Admin)
+    }
+
+    fn use_organizer_secret(&self, secret: &str) -> Result<bool> {
+        Ok(self.tables.borrow_mut().used_organizer_secrets.insert(secret_digest(secret)))
+    }
+
+    fn record_organizer_attempt(&self, newlogin: &str, outcome: OrganizerOutcome) -> Result<()> {
+        self.tables.borrow_mut().organizer_attempts.push((newlogin.to_owned(), outcome, now()));
+        Ok(())
+    }
+
+    fn organizer_attempts(&self) -> Result<Vec<OrganizerAttempt>> {
+        let t = self.tables.borrow();
+        Ok(t.organizer_attempts
+               .iter()
+               .map(|&(ref newlogin, outcome, attempted_at)| {
+                        OrganizerAttempt { newlogin: newlogin.clone(), outcome, attempted_at }
+                    })
+               .collect())
+    }
+
+    fn create_event(&self,
+                    credentials: Credentials,
+                    eventname: String,
+                    start_timestamp: DateTime,
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
+                    -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ManageEvents, None)?;
+        if t.events.iter().any(|e| e.eventname == eventname) {
+            bail!(ErrorKind::Conflict(format!("Key (eventname)=({}) already exists.", eventname)))
+        }
+        t.events.push(Event { eventname, start_timestamp, end_timestamp, capacity });
+        let event_id = t.events.len() - 1;
+        t.insert_organizer(event_id, person_id, true)
+    }
+
+    fn set_event_capacity(&self,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        let event_id = t.event(&eventname)?;
+        t.authorize(person_id, Permission::ManageEvents, Some(event_id))?;
+        t.events[event_id].capacity = capacity;
+        t.promote_waitlisted(event_id);
+        Ok(())
+    }
+
+    fn create_room(&self,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ManageEvents, None)?;
+        if let Some(existing) = t.room_named(&room) {
+            bail!(ErrorKind::Conflict(format!("room `{}` already exists", t.rooms[existing].room)))
+        }
+        let venue_id = match t.venue_named(&venue) {
+            Some(venue_id) => venue_id,
+            None => {
+                t.venues.push(venue);
+                t.venues.len() - 1
+            }
+        };
+        t.rooms.push(Room { room, venue_id: Some(venue_id), capacity, floor });
+        Ok(())
+    }
+
+    fn list_rooms(&self,
+                  credentials: Credentials,
+                  venue: Option<String>)
+                  -> Result<Vec<::api::Room>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ScheduleTalks, None)?;
+        let venue_id = match venue {
+            Some(venue) => {
+                let venue_id = t.venue_named(&venue).ok_or_else(|| {
+                    ErrorKind::NotFound(format!("venue with venue=`{}` not found", venue))
+                })?;
+                Some(venue_id)
+            }
+            None => None,
+        };
+        let mut rooms: Vec<_> = t.rooms
+            .iter()
+            .filter(|r| venue_id.is_none() || r.venue_id == venue_id)
+            .collect();
+        rooms.sort_by_key(|r| r.room.to_lowercase());
+        Ok(rooms.into_iter()
+               .map(|r| {
+                        ::api::Room {
+                            room: r.room.clone(),
+                            venue: r.venue_id.map(|v| t.venues[v].clone()),
+                            capacity: r.capacity,
+                            floor: r.floor,
+                        }
+                    })
+               .collect())
+    }
+
+    fn create_user(&self,
+                   credentials: Credentials,
+                   newlogin: String,
+                   newpassword: String)
+                   -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ManageAccounts, None)?;
+        t.insert_person(newlogin, newpassword, Role::Attendee)
+    }
+
+    fn register_or_accept_talk(&self,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
+                               talk: String,
+                               title: String,
+                               start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
+                               room: String,
+                               initial_evaluation: i16,
+                               eventname: String)
+                               -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.schedule_talk(person_id,
+                        speakers,
+                        talk,
+                        title,
+                        start_timestamp,
+                        end_timestamp,
+                        room,
+                        Some(initial_evaluation),
+                        eventname)
+    }
+
+    fn register_user_for_event(&self, credentials: Credentials, eventname: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.event_named(&eventname))?;
+        let event_id = t.event(&eventname)?;
+        let full = t.free_seats(event_id).is_some_and(|free| free <= 0);
+        if !full {
+            if !t.registered.insert((person_id, event_id)) {
+                bail!(ErrorKind::Conflict(format!("Key (person_id, event_id)=({}, {}) already \
+                                                   exists.",
+                                                  person_id,
+                                                  event_id)))
+            }
+            return Ok(());
+        }
+
+        if t.registered.contains(&(person_id, event_id)) {
+            bail!(ErrorKind::Conflict(format!("already registered for event `{}`", eventname)))
+        }
+        if t.waitlist.contains(&(person_id, event_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (person_id, event_id)=({}, {}) already exists.",
+                                              person_id,
+                                              event_id)))
+        }
+        t.waitlist.push((person_id, event_id));
+        Ok(())
+    }
+
+    fn unregister_user_from_event(&self,
+                                  credentials: Credentials,
+                                  eventname: String)
+                                  -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.event_named(&eventname))?;
+        let event_id = t.event(&eventname)?;
+        if t.registered.remove(&(person_id, event_id)) {
+            t.promote_waitlisted(event_id);
+            return Ok(());
+        }
+        match t.waitlist.iter().position(|&entry| entry == (person_id, event_id)) {
+            Some(i) => t.waitlist.remove(i),
+            None => bail!(ErrorKind::NotFound("There was no registration to cancel".into())),
+        };
+        Ok(())
+    }
+
+    fn attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.talk_event(&talk))?;
+        let talk_id = t.accepted_talk(&talk)
+            .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+        if !t.attended.insert((person_id, talk_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (person_id, talk_id)=({}, {}) already exists.",
+                                              person_id,
+                                              talk_id)))
+        }
+        Ok(())
+    }
+
+    fn cancel_attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.talk_event(&talk))?;
+        let removed = match t.accepted_talk(&talk) {
+            Some(talk_id) => t.attended.remove(&(person_id, talk_id)),
+            None => false,
+        };
+        if !removed {
+            bail!(ErrorKind::NotFound("There was no attendance to cancel".into()))
+        }
+        Ok(())
+    }
+
+    fn evaluation(&self,
+                  credentials: Credentials,
+                  talk: String,
+                  rating: i16,
+                  comment: Option<String>)
+                  -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.talk_event(&talk))?;
+        let talk_id = t.accepted_talk(&talk)
+            .ok_or_else(|| ErrorKind::NotFound("talk doesn't exist or is not accepted".into()))?;
+        match comment {
+            Some(comment) => t.comments.insert((person_id, talk_id), comment),
+            None => t.comments.remove(&(person_id, talk_id)),
+        };
+        if t.ratings.insert((person_id, talk_id), rating) != Some(rating) {
+            t.rating_history.push((person_id, talk_id, Some(rating), now()));
+        }
+        Ok(())
+    }
+
+    fn retract_evaluation(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.talk_event(&talk))?;
+        let talk_id = t.talks
+            .iter()
+            .position(|x| x.talk == talk)
+            .filter(|&talk_id| t.ratings.contains_key(&(person_id, talk_id)))
+            .ok_or_else(|| ErrorKind::NotFound("There was no evaluation to retract".into()))?;
+        t.ratings.remove(&(person_id, talk_id));
+        t.comments.remove(&(person_id, talk_id));
+        t.rating_history.push((person_id, talk_id, None, now()));
+        Ok(())
+    }
+
+    fn reject_spontaneous_talk(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize_organizer(person_id, Permission::ReviewProposals, t.talk_event(&talk))?;
+        match t.talks
+                  .iter_mut()
+                  .find(|x| x.talk == talk && x.status == TalkStatus::Proposed) {
+            Some(proposal) => proposal.status = TalkStatus::Rejected,
+            None => bail!(ErrorKind::NotFound("There was no proposal to reject".into())),
+        }
+        Ok(())
+    }
+
+    fn accept_talk(&self,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        let proposal = t.talks
+            .iter()
+            .find(|x| x.talk == talk && x.status == TalkStatus::Proposed)
+            .ok_or_else(|| ErrorKind::NotFound("There was no proposal to accept".into()))?
+            .clone();
+        let room = room.or(proposal.room.clone())
+            .ok_or_else(|| ErrorKind::Validation("the proposal has no room, give one".into()))?;
+        let eventname = proposal.event_id
+            .map(|e| t.events[e].eventname.clone())
+            .unwrap_or_default();
+        let speakers = t.speakers(&proposal);
+        t.schedule_talk(person_id,
+                        speakers,
+                        talk,
+                        proposal.title,
+                        proposal.start_timestamp,
+                        Some(proposal.end_timestamp),
+                        room,
+                        initial_evaluation,
+                        eventname)
+    }
+
+    fn propose_spontaneous_talk(&self,
+                                credentials: Credentials,
+                                talk: String,
+                                title: String,
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
+                                -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let speaker_id = t.authenticate(&credentials)?;
+        let event_id = match eventname {
+            Some(eventname) => Some(t.event_at(&eventname, start_timestamp)?),
+            None => None,
+        };
+        t.authorize(speaker_id, Permission::Participate, event_id)?;
+        let room = match room {
+            Some(room) => {
+                match t.room_named(&room) {
+                    Some(room_id) => Some(t.rooms[room_id].room.clone()),
+                    None => {
+                        bail!(ErrorKind::NotFound(format!("room with room=`{}` not found", room)))
+                    }
+                }
+            }
+            None => None,
+        };
+        let speakers = t.speakers_of(speaker_id, &speakers)?;
+        if t.talks.iter().any(|x| x.talk == talk) {
+            bail!(ErrorKind::Conflict(format!("Key (talk)=({}) already exists.", talk)))
+        }
+        let end_timestamp = end_timestamp.unwrap_or_else(|| {
+            start_timestamp + Duration::minutes(DEFAULT_TALK_MINUTES.into())
+        });
+        let modified_at = t.tick();
+        t.talks.push(Talk {
+                         talk,
+                         status: TalkStatus::Proposed,
+                         title,
+                         speaker_id,
+                         speakers,
+                         event_id,
+                         room,
+                         start_timestamp,
+                         end_timestamp,
+                         modified_at,
+                     });
+        Ok(())
+    }
+
+    fn make_friends(&self, credentials: Credentials, login2: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person1_id = t.authenticate(&credentials)?;
+        t.authorize(person1_id, Permission::Participate, None)?;
+        let person2_id = t.authorize_person(&login2, None)?;
+        if !t.knows.insert((person1_id, person2_id)) {
+            bail!(ErrorKind::Conflict(format!("Key (person1_id, person2_id)=({}, {}) already \
+                                               exists.",
+                                              person1_id,
+                                              person2_id)))
+        }
+        Ok(())
+    }
+
+    fn unfriend(&self, credentials: Credentials, login2: String) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person1_id = t.authenticate(&credentials)?;
+        t.authorize(person1_id, Permission::Participate, None)?;
+        let person2_id = t.authorize_person(&login2, None)?;
+        let removed = t.knows.remove(&(person1_id, person2_id));
+        if !t.knows.remove(&(person2_id, person1_id)) && !removed {
+            bail!(ErrorKind::NotFound("These users are not friends".into()))
+        }
+        Ok(())
+    }
+
+    fn user_plan(&self, login: String, page: Page) -> Result<Paged<UserPlan>> {
+        let t = self.tables.borrow();
+        let person_id = match t.persons.iter().position(|p| p.login == login) {
+            Some(person_id) => person_id,
+            None => return USER_PLAN.page(vec![], &page),
+        };
+        let now = now();
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| {
+                        x.event_id.is_some_and(|e| t.registered.contains(&(person_id, e))) &&
+                        x.start_timestamp >= now
+                    })
+            .collect();
+        talks.sort_by_key(|&(id, x)| (x.start_timestamp, id));
+        let plans = talks.into_iter()
+            .map(|(id, x)| {
+                (UserPlan {
+                     login: t.login(x.speaker_id),
+                     talk: x.talk.clone(),
+                     start_timestamp: x.start_timestamp,
+                     end_timestamp: x.end_timestamp,
+                     title: x.title.clone(),
+                     room: x.room.clone().unwrap_or_default(),
+                     speakers: t.speakers(x),
+                 },
+                 vec![Key::Timestamp(x.start_timestamp), Key::Int(id as i64)])
+            })
+            .collect();
+        USER_PLAN.page(plans, &page)
+    }
+
+    fn day_plan(&self, date: Date) -> Result<Vec<DayPlan>> {
+        let t = self.tables.borrow();
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| x.start_timestamp.date() == date)
+            .collect();
+        talks.sort_by_key(|&(id, x)| (x.room.clone(), x.start_timestamp, id));
+        Ok(talks.into_iter()
+               .map(|(_, x)| {
+                        let (venue, capacity, floor) = t.room_details(&x.room);
+                        DayPlan {
+                            talk: x.talk.clone(),
+                            start_timestamp: x.start_timestamp,
+                            end_timestamp: x.end_timestamp,
+                            title: x.title.clone(),
+                            room: x.room.clone().unwrap_or_default(),
+                            venue,
+                            capacity,
+                            floor,
+                            speakers: t.speakers(x),
+                        }
+                    })
+               .collect())
+    }
+
+    fn event_plan(&self, eventname: String) -> Result<Vec<DayPlan>> {
+        let t = self.tables.borrow();
+        let event_id = t.event(&eventname)?;
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| x.event_id == Some(event_id))
+            .collect();
+        talks.sort_by_key(|&(id, x)| (x.start_timestamp, id));
+        Ok(talks.into_iter()
+               .map(|(_, x)| {
+                        let (venue, capacity, floor) = t.room_details(&x.room);
+                        DayPlan {
+                            talk: x.talk.clone(),
+                            start_timestamp: x.start_timestamp,
+                            end_timestamp: x.end_timestamp,
+                            title: x.title.clone(),
+                            room: x.room.clone().unwrap_or_default(),
+                            venue,
+                            capacity,
+                            floor,
+                            speakers: t.speakers(x),
+                        }
+                    })
+               .collect())
+    }
+
+    fn best_talks(&self,
+                  start_timestamp: DateTime,
+                  end_timestamp: DateTime,
+                  page: Page,
+                  all: bool)
+                  -> Result<Paged<BestTalk>> {
+        let t = self.tables.borrow();
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| in_range(x, start_timestamp, end_timestamp) && x.event_id.is_some())
+            .filter_map(|(id, x)| {
+                let ratings: Vec<i16> = t.ratings
+                    .iter()
+                    .filter(|&(&(person_id, talk_id), _)| {
+                                talk_id == id &&
+                                (all ||
+                                 t.has_permission(person_id,
+                                                  Permission::ScheduleTalks,
+                                                  x.event_id) ||
+                                 x.event_id.is_some_and(|event_id| {
+                                     t.organizers.contains_key(&(event_id, person_id))
+                                 }) ||
+                                 t.attended.contains(&(person_id, talk_id)))
+                            })
+                    .map(|(_, &rating)| rating)
+                    .collect();
+                average(&ratings).map(|average| (id, x, average))
+            })
+            .collect();
+        talks.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap().then(a.0.cmp(&b.0)));
+        let talks = talks.into_iter()
+            .map(|(id, x, average)| {
+                     (BestTalk {
+                          talk: x.talk.clone(),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Float(average), Key::Int(id as i64)])
+                 })
+            .collect();
+        BEST_TALKS.page(talks, &page)
+    }
+
+    fn most_popular_talks(&self,
+                          start_timestamp: DateTime,
+                          end_timestamp: DateTime,
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>> {
+        let t = self.tables.borrow();
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| in_range(x, start_timestamp, end_timestamp))
+            .map(|(id, x)| {
+                     let arrivals = t.attended.iter().filter(|&&(_, talk_id)| talk_id == id).count();
+                     (id, x, arrivals)
+                 })
+            .filter(|&(_, _, arrivals)| arrivals > 0)
+            .collect();
+        talks.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        let talks = talks.into_iter()
+            .map(|(id, x, arrivals)| {
+                     (MostPopularTalk {
+                          talk: x.talk.clone(),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Int(arrivals as i64), Key::Int(id as i64)])
+                 })
+            .collect();
+        MOST_POPULAR_TALKS.page(talks, &page)
+    }
+
+    fn attended_talks(&self, credentials: Credentials) -> Result<Vec<AttendedTalk>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, None)?;
+        Ok(t.attended
+               .iter()
+               .filter(|&&(p, _)| p == person_id)
+               .map(|&(_, talk_id)| {
+                        let x = &t.talks[talk_id];
+                        AttendedTalk {
+                            talk: x.talk.clone(),
+                            start_timestamp: x.start_timestamp,
+                            end_timestamp: x.end_timestamp,
+                            title: x.title.clone(),
+                            room: x.room.clone().unwrap_or_default(),
+                            speakers: t.speakers(x),
+                        }
+                    })
+               .collect())
+    }
+
+    fn abandoned_talks(&self,
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        let mut talks: Vec<_> = t.talks
+            .iter()
+            .enumerate()
+            .filter_map(|(id, x)| {
+                let event_id = x.event_id?;
+                if x.status != TalkStatus::Accepted ||
+                   !t.may_organize(person_id, Permission::ViewFeedback, Some(event_id)) {
+                    return None;
+                }
+                let absent = t.registered
+                    .iter()
+                    .filter(|&&(person_id, e)| {
+                                e == event_id && !t.attended.contains(&(person_id, id))
+                            })
+                    .count();
+                if absent > 0 { Some((id, x, absent)) } else { None }
+            })
+            .collect();
+        talks.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        let talks = talks.into_iter()
+            .map(|(id, x, absent)| {
+                     (AbandonedTalk {
+                          talk: x.talk.clone(),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          number: absent as i64,
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Int(absent as i64), Key::Int(id as i64)])
+                 })
+            .collect();
+        ABANDONED_TALKS.page(talks, &page)
+    }
+
+    fn recently_added_talks(&self, page: Page) -> Result<Paged<RecentlyAddedTalk>> {
+        let t = self.tables.borrow();
+        let mut talks = t.talks_with(TalkStatus::Accepted);
+        talks.sort_by(|a, b| b.1.modified_at.cmp(&a.1.modified_at).then(a.0.cmp(&b.0)));
+        let talks = talks.into_iter()
+            .map(|(id, x)| {
+                     (RecentlyAddedTalk {
+                          talk: x.talk.clone(),
+                          speakerlogin: t.login(x.speaker_id),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Int(x.modified_at as i64), Key::Int(id as i64)])
+                 })
+            .collect();
+        RECENTLY_ADDED_TALKS.page(talks, &page)
+    }
+
+    fn search_talks(&self,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>> {
+        let t = self.tables.borrow();
+        if status != TalkStatusName::Accepted {
+            match credentials {
+                Some(credentials) => {
+                    let person_id = t.authenticate(&credentials)?;
+                    t.authorize(person_id, Permission::ReviewProposals, None)?;
+                }
+                None => {
+                    bail!(ErrorKind::Unauthorized("only organizers can search talks that are \
+                                                   not accepted"
+                                                          .into()))
+                }
+            }
+        }
+
+        let mut talks: Vec<_> = t.talks_with(status.into())
+            .into_iter()
+            .filter(|&(_, x)| start_timestamp.is_none_or(|start| x.start_timestamp >= start))
+            .filter(|&(_, x)| end_timestamp.is_none_or(|end| x.start_timestamp <= end))
+            .filter_map(|(id, x)| {
+                let speakers = t.speakers(x);
+                let logins = speakers.iter().map(|s| &s.login[..]).collect::<Vec<_>>().join(" ");
+                let eventname = x.event_id.map(|e| &t.events[e].eventname[..]).unwrap_or("");
+                let fields = [&x.title[..],
+                              &logins[..],
+                              eventname,
+                              x.room.as_ref().map_or("", |room| &room[..])];
+                search_rank(&query, fields).map(|score| {
+                    (id,
+                     FoundTalk {
+                         talk: x.talk.clone(),
+                         speakerlogin: t.login(x.speaker_id),
+                         start_timestamp: x.start_timestamp,
+                         end_timestamp: x.end_timestamp,
+                         title: x.title.clone(),
+                         room: x.room.clone(),
+                         score,
+                         speakers,
+                     })
+                })
+            })
+            .collect();
+        talks.sort_by(|a, b| {
+            b.1.score.partial_cmp(&a.1.score).unwrap()
+                .then(a.1.start_timestamp.cmp(&b.1.start_timestamp))
+                .then(a.0.cmp(&b.0))
+        });
+        let talks = talks.into_iter()
+            .map(|(id, talk)| {
+                let keys = vec![Key::Float(talk.score),
+                                Key::Timestamp(talk.start_timestamp),
+                                Key::Int(id as i64)];
+                (talk, keys)
+            })
+            .collect();
+        SEARCH_TALKS.page(talks, &page)
+    }
+
+    fn rejected_talks(&self, credentials: Credentials) -> Result<Vec<RejectedTalk>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        let reviewer = t.has_permission(person_id, Permission::ReviewProposals, None);
+        Ok(t.talks_with(TalkStatus::Rejected)
+               .into_iter()
+               .filter(|&(_, x)| reviewer || x.is_given_by(person_id))
+               .map(|(_, x)| {
+                        RejectedTalk {
+                            talk: x.talk.clone(),
+                            speakerlogin: t.login(x.speaker_id),
+                            start_timestamp: x.start_timestamp,
+                            end_timestamp: x.end_timestamp,
+                            title: x.title.clone(),
+                            speakers: t.speakers(x),
+                        }
+                    })
+               .collect())
+    }
+
+    fn proposals(&self, credentials: Credentials) -> Result<Vec<Proposal>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        Ok(t.talks_with(TalkStatus::Proposed)
+               .into_iter()
+               .filter(|&(_, x)| t.may_organize(person_id, Permission::ReviewProposals, x.event_id))
+               .map(|(_, x)| {
+                        Proposal {
+                            talk: x.talk.clone(),
+                            speakerlogin: t.login(x.speaker_id),
+                            start_timestamp: x.start_timestamp,
+                            end_timestamp: x.end_timestamp,
+                            title: x.title.clone(),
+                            speakers: t.speakers(x),
+                        }
+                    })
+               .collect())
+    }
+
+    fn friends_talks(&self,
+                     credentials: Credentials,
+                     start_timestamp: DateTime,
+                     end_timestamp: DateTime,
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, None)?;
+        let friends = t.friends(person_id);
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(_, x)| {
+                        x.speakers.iter().any(|&(p, _)| friends.contains(&p)) &&
+                        in_range(x, start_timestamp, end_timestamp)
+                    })
+            .collect();
+        talks.sort_by_key(|&(id, x)| (x.start_timestamp, id));
+        let talks = talks.into_iter()
+            .map(|(id, x)| {
+                     (FriendsTalk {
+                          talk: x.talk.clone(),
+                          speakerlogin: t.login(x.speaker_id),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Timestamp(x.start_timestamp), Key::Int(id as i64)])
+                 })
+            .collect();
+        FRIENDS_TALKS.page(talks, &page)
+    }
+
+    fn friends_events(&self,
+                      credentials: Credentials,
+                      eventname: String)
+                      -> Result<Vec<FriendsEvent>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, t.event_named(&eventname))?;
+        let login = t.login(person_id);
+        let friends = t.friends(person_id);
+        Ok(t.registered
+               .iter()
+               .filter(|&&(p, _)| friends.contains(&p))
+               .map(|&(p, _)| {
+                        FriendsEvent {
+                            login: login.clone(),
+                            eventname: eventname.clone(),
+                            friendlogin: t.login(p),
+                        }
+                    })
+               .collect())
+    }
+
+    fn recommended_talks(&self,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, None)?;
+        let friends = t.friends(person_id);
+        let attended: Vec<&Talk> = t.attended
+            .iter()
+            .filter(|&&(p, _)| p == person_id)
+            .map(|&(_, talk_id)| &t.talks[talk_id])
+            .collect();
+        let ratings_of = |pred: &dyn Fn(usize) -> bool| -> Vec<i16> {
+            t.ratings
+                .iter()
+                .filter(|&(&(_, talk_id), _)| pred(talk_id))
+                .map(|(_, &rating)| rating)
+                .collect()
+        };
+
+        let mut talks: Vec<_> = t.talks_with(TalkStatus::Accepted)
+            .into_iter()
+            .filter(|&(id, x)| {
+                        in_range(x, start_timestamp, end_timestamp) &&
+                        !x.is_given_by(person_id) &&
+                        !t.attended.contains(&(person_id, id))
+                    })
+            .map(|(id, x)| {
+                let talk_rating = average(&ratings_of(&|talk_id| talk_id == id)).unwrap_or(0.0);
+                let speaker_rating = average(&ratings_of(&|talk_id| {
+                                                             let past = &t.talks[talk_id];
+                                                             past.speaker_id == x.speaker_id &&
+                                                             past.start_timestamp <
+                                                             x.start_timestamp
+                                                         }))
+                        .unwrap_or(0.0);
+                let friends_there = friends
+                    .iter()
+                    .filter(|&&f| {
+                                t.attended.contains(&(f, id)) ||
+                                x.event_id.is_some_and(|e| t.registered.contains(&(f, e)))
+                            })
+                    .count();
+                let same_speaker = attended
+                    .iter()
+                    .filter(|a| a.speaker_id == x.speaker_id)
+                    .count();
+                let same_event = attended
+                    .iter()
+                    .filter(|a| a.event_id.is_some() && a.event_id == x.event_id)
+                    .count();
+                let score = 10.0 * talk_rating + 5.0 * speaker_rating +
+                            5.0 * friends_there as f64 +
+                            5.0 * same_speaker as f64 +
+                            2.0 * same_event as f64;
+                (id, x, score.round() as u32)
+            })
+            .collect();
+        talks.sort_by(|a, b| b.2.cmp(&a.2).then(a.0.cmp(&b.0)));
+        let talks = talks.into_iter()
+            .map(|(id, x, score)| {
+                     (RecommendedTalk {
+                          talk: x.talk.clone(),
+                          speakerlogin: t.login(x.speaker_id),
+                          start_timestamp: x.start_timestamp,
+                          end_timestamp: x.end_timestamp,
+                          title: x.title.clone(),
+                          room: x.room.clone().unwrap_or_default(),
+                          score,
+                          speakers: t.speakers(x),
+                      },
+                      vec![Key::Int(score as i64), Key::Int(id as i64)])
+                 })
+            .collect();
+        RECOMMENDED_TALKS.page(talks, &page)
+    }
+
+    fn waitlist(&self, credentials: Credentials, eventname: String) -> Result<Vec<WaitlistEntry>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        let event_id = t.event(&eventname)?;
+        let everyone = t.has_permission(person_id, Permission::ViewWaitlist, Some(event_id));
+        Ok(t.waitlist
+               .iter()
+               .filter(|&&(_, e)| e == event_id)
+               .zip(1..)
+               .filter(|&(&(p, _), _)| everyone || p == person_id)
+               .map(|(&(p, _), position)| {
+                        WaitlistEntry {
+                            login: t.login(p),
+                            eventname: eventname.clone(),
+                            position,
+                        }
+                    })
+               .collect())
+    }
+
+    fn rating_history(&self, credentials: Credentials, talk: String) -> Result<Vec<RatingChange>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ViewFeedback, t.talk_event(&talk))?;
+        let talk_id = t.talks
+            .iter()
+            .position(|x| x.talk == talk)
+            .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+        let mut latest = BTreeMap::new();
+        Ok(t.rating_history
+               .iter()
+               .filter(|&&(_, x, _, _)| x == talk_id)
+               .map(|&(p, _, rating, changed_at)| {
+                        latest.insert(p, rating);
+                        let ratings: Vec<i16> = latest.values().filter_map(|&r| r).collect();
+                        RatingChange {
+                            login: t.login(p),
+                            rating,
+                            changed_at,
+                            average: average(&ratings),
+                        }
+                    })
+               .collect())
+    }
+
+    fn talk_reviews(&self, credentials: Credentials, talk: String) -> Result<Vec<TalkReview>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::ViewFeedback, t.talk_event(&talk))?;
+        let talk_id = t.talks
+            .iter()
+            .position(|x| x.talk == talk)
+            .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+        let mut reviews: Vec<_> = t.ratings
+            .iter()
+            .filter(|&(&(_, x), _)| x == talk_id)
+            .map(|(&key, &rating)| {
+                     TalkReview {
+                         login: t.login(key.0),
+                         rating,
+                         comment: t.comments.get(&key).cloned(),
+                         attended: t.attended.contains(&key),
+                     }
+                 })
+            .collect();
+        reviews.sort_by(|a, b| a.login.cmp(&b.login));
+        Ok(reviews)
+    }
+
+    fn my_feedback(&self, credentials: Credentials) -> Result<Vec<Feedback>> {
+        let t = self.tables.borrow();
+        let person_id = t.authenticate(&credentials)?;
+        t.authorize(person_id, Permission::Participate, None)?;
+        let mut feedback: Vec<_> = t.comments
+            .iter()
+            .filter(|&(&(_, talk_id), _)| t.talks[talk_id].is_given_by(person_id))
+            .map(|(key, comment)| {
+                     Feedback {
+                         talk: t.talks[key.1].talk.clone(),
+                         rating: t.ratings[key],
+                         comment: comment.clone(),
+                     }
+                 })
+            .collect();
+        feedback.sort_by_key(|f| (f.talk.clone(), -f.rating, f.comment.clone()));
+        Ok(feedback)
+    }
+
+    fn open_session(&self, login: String, password: String) -> Result<Vec<Session>> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authorize_person(&login, Some(&password))?;
+        let now = now();
+        t.sessions.retain(|_, &mut (_, expires_at)| expires_at > now);
+        let token = new_session_token();
+        let expires_at = now + Duration::hours(SESSION_TTL_HOURS.into());
+        t.sessions.insert(token.clone(), (person_id, expires_at));
+        Ok(vec![Session { token, expires_at }])
+    }
+
+    fn close_session(&self, token: String) -> Result<()> {
+        match self.tables.borrow_mut().sessions.remove(&token) {
+            Some(_) => Ok(()),
+            None => bail!(ErrorKind::NotFound("There was no session to close".into())),
+        }
+    }
+
+    fn revoke_sessions(&self, credentials: Credentials) -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let person_id = t.authenticate(&credentials)?;
+        t.sessions.retain(|_, &mut (p, _)| p != person_id);
+        Ok(())
+    }
+
+    fn grant_role(&self,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let granter_id = t.authenticate(&credentials)?;
+        let event_id = t.role_event(eventname)?;
+        t.authorize(granter_id, Permission::ManageRoles, event_id)?;
+        let person_id = t.authorize_person(&personlogin, None)?;
+        t.insert_role(person_id, role, event_id)
+    }
+
+    fn revoke_role(&self,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let granter_id = t.authenticate(&credentials)?;
+        let event_id = t.role_event(eventname)?;
+        t.authorize(granter_id, Permission::ManageRoles, event_id)?;
+        let person_id = t.authorize_person(&personlogin, None)?;
+        let before = t.roles.len();
+        t.roles.retain(|&granted| granted != (person_id, role, event_id));
+        if t.roles.len() == before {
+            bail!(ErrorKind::NotFound("The person has no such role".into()))
+        }
+        Ok(())
+    }
+
+    fn grant_event_organizer(&self,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let granter_id = t.authenticate(&credentials)?;
+        let event_id = t.authorize_owner(granter_id, &eventname)?;
+        let person_id = t.authorize_person(&personlogin, None)?;
+        t.insert_organizer(event_id, person_id, owner)
+    }
+
+    fn revoke_event_organizer(&self,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()> {
+        let mut t = self.tables.borrow_mut();
+        let granter_id = t.authenticate(&credentials)?;
+        let event_id = t.authorize_owner(granter_id, &eventname)?;
+        let person_id = t.authorize_person(&personlogin, None)?;
+        let was_owner = t.organizers
+            .remove(&(event_id, person_id))
+            .ok_or_else(|| ErrorKind::NotFound("The person does not organize the event".into()))?;
+        let owners = t.organizers.iter().filter(|&(&(e, _), &owner)| e == event_id && owner);
+        if was_owner && owners.count() == 0 {
+            bail!(ErrorKind::Conflict("The event would have no owner".into()))
+        }
+        Ok(())
+    }
+}
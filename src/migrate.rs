@@ -0,0 +1,266 @@
+use errors::*;
+use api::DateTime;
+
+use postgres::Connection;
+
+/// A schema change shipped with the program. Migrations are applied in the order
+/// of `MIGRATIONS` and recorded by `version`, the name of their directory in `migrations`.
+/// SQLite has its own list, kept in `migrations/sqlite`.
+pub struct Migration {
+    pub version: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+    /// Detects a schema created before `schema_migrations` existed.
+    check: Option<&'static str>,
+}
+
+macro_rules! migration {
+    (sqlite $version:expr) => {
+        Migration {
+            version: $version,
+            up: include_str!(concat!("../migrations/sqlite/", $version, "/up.sql")),
+            down: include_str!(concat!("../migrations/sqlite/", $version, "/down.sql")),
+            check: None,
+        }
+    };
+    ($version:expr) => {
+        migration!($version, None)
+    };
+    ($version:expr, $check:expr) => {
+        Migration {
+            version: $version,
+            up: include_str!(concat!("../migrations/", $version, "/up.sql")),
+            down: include_str!(concat!("../migrations/", $version, "/down.sql")),
+            check: $check,
+        }
+    };
+}
+
+/// A database the migrations can be run against.
+pub trait MigrationTarget {
+    /// Migrations known for this kind of database, ordered by version.
+    fn migrations(&self) -> &'static [Migration];
+
+    /// Whether `schema_migrations` exists, i.e. whether any migration was recorded.
+    fn tracked(&self) -> Result<bool>;
+
+    /// Versions of the applied migrations with the time they were applied.
+    fn applied(&self) -> Result<Vec<(String, DateTime)>>;
+
+    /// Runs `sql` of `migration` in a transaction, recording the migration as applied
+    /// or, when reverting, forgetting it.
+    fn run(&self, migration: &Migration, sql: &str, revert: bool) -> Result<()>;
+}
+
+static MIGRATIONS: &[Migration] =
+    &[migration!("20170529191530_base",
+                 Some(include_str!("../migrations/20170529191530_base/check.sql"))),
+      migration!("20261018120000_sessions",
+                 Some(include_str!("../migrations/20261018120000_sessions/check.sql"))),
+      migration!("20261018130000_talk_end"),
+      migration!("20261018150000_waitlist"),
+      migration!("20261018160000_rating_history"),
+      migration!("20261018170000_review_comments"),
+      migration!("20261018180000_rooms"),
+      migration!("20261018190000_talk_speakers"),
+      migration!("20261018200000_organizer_secret"),
+      migration!("20261018210000_person_roles"),
+      migration!("20261018220000_event_organizers")];
+
+pub static SQLITE_MIGRATIONS: &[Migration] =
+    &[migration!(sqlite "20261018140000_base"),
+      migration!(sqlite "20261018150000_waitlist"),
+      migration!(sqlite "20261018160000_rating_history"),
+      migration!(sqlite "20261018170000_review_comments"),
+      migration!(sqlite "20261018180000_rooms"),
+      migration!(sqlite "20261018190000_talk_speakers"),
+      migration!(sqlite "20261018200000_organizer_secret"),
+      migration!(sqlite "20261018210000_person_roles"),
+      migration!(sqlite "20261018220000_event_organizers")];
+
+/// Lists every known migration with the time it was applied, if it was.
+pub fn status<D>(db: &D) -> Result<Vec<(&'static Migration, Option<DateTime>)>>
+    where D: MigrationTarget
+{
+    let applied = db.applied()?;
+
+    Ok(db.migrations()
+           .iter()
+           .map(|migration| {
+                    let applied_at = applied.iter()
+                        .find(|&(version, _)| version == migration.version)
+                        .map(|&(_, applied_at)| applied_at);
+                    (migration, applied_at)
+                })
+           .collect())
+}
+
+fn pending<D: MigrationTarget>(db: &D) -> Result<Vec<&'static Migration>> {
+    Ok(status(db)?
+           .into_iter()
+           .filter(|&(_, applied_at)| applied_at.is_none())
+           .map(|(migration, _)| migration)
+           .collect())
+}
+
+/// Fails with `validation` if a migration is still pending. Unlike `status`
+/// this doesn't create `schema_migrations`, so it never changes the database.
+pub fn check<D: MigrationTarget>(db: &D) -> Result<()> {
+    let pending = if db.tracked()? {
+        pending(db)?
+    } else {
+        db.migrations().iter().collect()
+    };
+    if let Some(first) = pending.first() {
+        let msg = format!("database schema is behind by {} migration(s), starting with {}; \
+                           run `rusty_ems migrate up`",
+                          pending.len(),
+                          first.version);
+        bail!(ErrorKind::Validation(msg))
+    }
+    Ok(())
+}
+
+/// Applies every pending migration, each one in its own transaction.
+pub fn up<D: MigrationTarget>(db: &D) -> Result<Vec<&'static Migration>> {
+    let pending = pending(db)?;
+
+    for migration in &pending {
+        db.run(migration, migration.up, false)
+            .chain_err(|| format!("Unable to apply migration {}", migration.version))?;
+    }
+
+    Ok(pending)
+}
+
+/// Reverts the most recently applied migration.
+pub fn down<D: MigrationTarget>(db: &D) -> Result<Option<&'static Migration>> {
+    let last = status(db)?
+        .into_iter()
+        .rev()
+        .find(|&(_, applied_at)| applied_at.is_some())
+        .map(|(migration, _)| migration);
+
+    if let Some(migration) = last {
+        db.run(migration, migration.down, true)
+            .chain_err(|| format!("Unable to revert migration {}", migration.version))?;
+    }
+
+    Ok(last)
+}
+
+impl MigrationTarget for Connection {
+    fn migrations(&self) -> &'static [Migration] {
+        MIGRATIONS
+    }
+
+    fn tracked(&self) -> Result<bool> {
+        let query = r#"
+            SELECT to_regclass('schema_migrations') IS NOT NULL AS present"#;
+        Ok(self.query(query, &[])
+               .chain_err(|| "Unable to look for schema_migrations")?
+               .iter()
+               .map(|row| row.get("present"))
+               .next()
+               .unwrap_or(false))
+    }
+
+    fn applied(&self) -> Result<Vec<(String, DateTime)>> {
+        prepare(self)?;
+
+        let query = r#"
+            SELECT version, applied_at
+            FROM schema_migrations"#;
+        Ok(self.query(query, &[])
+               .chain_err(|| "Unable to load applied migrations")?
+               .iter()
+               .map(|row| (row.get("version"), row.get("applied_at")))
+               .collect())
+    }
+
+    fn run(&self, migration: &Migration, sql: &str, revert: bool) -> Result<()> {
+        let tx = self.transaction()?;
+        tx.batch_execute(sql)?;
+        let query = if revert {
+            r#"
+            DELETE FROM schema_migrations
+            WHERE version = $1"#
+        } else {
+            r#"
+            INSERT INTO schema_migrations (version)
+            VALUES ($1)"#
+        };
+        tx.execute(query, &[&migration.version])
+            .chain_err(|| "Unable to record the migration")?;
+        tx.commit()?;
+        Ok(())
+    }
+}
+
+/// Creates `schema_migrations` if needed. A database set up before it existed
+/// gets the migrations whose tables are already in place recorded as applied.
+fn prepare(conn: &Connection) -> Result<()> {
+    if conn.tracked()? {
+        return Ok(());
+    }
+
+    let tx = conn.transaction()?;
+    tx.batch_execute(r#"
+        CREATE TABLE schema_migrations (
+            version varchar PRIMARY KEY,
+            applied_at timestamp NOT NULL DEFAULT now()
+        )"#)
+        .chain_err(|| "Unable to create schema_migrations")?;
+    for migration in MIGRATIONS {
+        let in_place = match migration.check {
+            // a failed check would abort the transaction, hence the savepoint
+            Some(check) => {
+                let savepoint = tx.savepoint("migration_check")?;
+                let in_place = savepoint.batch_execute(check).is_ok();
+                savepoint.finish()?;
+                in_place
+            }
+            None => false,
+        };
+        if !in_place {
+            break;
+        }
+        let query = r#"
+            INSERT INTO schema_migrations (version)
+            VALUES ($1)"#;
+        tx.execute(query, &[&migration.version])
+            .chain_err(|| format!("Unable to record migration {}", migration.version))?;
+    }
+    tx.commit().chain_err(|| "Unable to create schema_migrations")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlite::SqliteStorage;
+
+    #[test]
+    fn migrations_are_ordered_by_version() {
+        for migrations in &[MIGRATIONS, SQLITE_MIGRATIONS] {
+            let versions: Vec<_> = migrations.iter().map(|m| m.version).collect();
+            let mut sorted = versions.clone();
+            sorted.sort();
+            sorted.dedup();
+            assert_eq!(versions, sorted);
+        }
+    }
+
+    #[test]
+    fn check_reports_pending_migrations_without_changes() {
+        let db = SqliteStorage::open_path(":memory:").unwrap();
+        let e = check(&db).unwrap_err();
+        assert_eq!(e.classify().0, ErrorCode::Validation);
+        assert!(e.to_string().contains("migrate up"), "{}", e);
+        assert!(!db.tracked().unwrap());
+
+        assert_eq!(up(&db).unwrap().len(), SQLITE_MIGRATIONS.len());
+        check(&db).unwrap();
+        down(&db).unwrap();
+        assert!(check(&db).is_err());
+    }
+}
@@ -0,0 +1,286 @@
+use errors::*;
+
+use api::DateTime;
+
+use serde_json::{self, Value};
+use std::cmp::Ordering;
+
+/// Format znaczników czasu w kursorach, z ułamkami sekund, żeby kursor wskazywał
+/// dokładnie ten sam wiersz.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+/// Rodzaj wartości klucza porządku listy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyType {
+    Int,
+    Float,
+    /// Wartość porównywana jak tekst, np. znaczniki czasu przechowywane w SQLite jako tekst.
+    Text,
+    Timestamp,
+}
+
+/// Wartość klucza porządku w wierszu listy.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Key {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Timestamp(DateTime),
+}
+
+/// Kolumna, wg której posortowana jest lista.
+pub struct SortKey {
+    pub column: &'static str,
+    pub typ: KeyType,
+    pub descending: bool,
+}
+
+pub const fn asc(column: &'static str, typ: KeyType) -> SortKey {
+    SortKey {
+        column,
+        typ,
+        descending: false,
+    }
+}
+
+pub const fn desc(column: &'static str, typ: KeyType) -> SortKey {
+    SortKey {
+        column,
+        typ,
+        descending: true,
+    }
+}
+
+/// Porządek listy: klucze wyznaczają go jednoznacznie (ostatnim jest zawsze identyfikator
+/// wiersza), więc kursor - wartości kluczy ostatniego wiersza strony - wskazuje, gdzie zaczyna
+/// się następna strona, nawet jeśli w międzyczasie lista się zmieniła. `name` wiąże kursor
+/// z listą, dla której powstał.
+pub struct Keyset {
+    pub name: &'static str,
+    pub keys: &'static [SortKey],
+}
+
+/// Strona listy: co najwyżej `limit` wierszy (0 oznacza wszystkie) następujących po wierszu,
+/// na którym skończyła się strona z `next_cursor` równym `cursor`; bez kursora - od początku.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Page {
+    pub limit: u32,
+    pub cursor: Option<String>,
+}
+
+/// Wiersze strony i kursor następnej, o ile lista ma dalsze wiersze.
+#[derive(Debug, PartialEq)]
+pub struct Paged<T> {
+    pub rows: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl Keyset {
+    /// Wartości kluczy z kursora `cursor`, o ile powstał on dla tej listy.
+    pub fn decode(&self, cursor: &str) -> Result<Vec<Key>> {
+        self.try_decode(cursor).ok_or_else(|| ErrorKind::Validation("invalid cursor".into()).into())
+    }
+
+    fn try_decode(&self, cursor: &str) -> Option<Vec<Key>> {
+        let bytes = from_hex(cursor)?;
+        let values: Vec<Value> = serde_json::from_slice(&bytes).ok()?;
+        let (name, values) = values.split_first()?;
+        if name.as_str()? != self.name || values.len() != self.keys.len() {
+            return None;
+        }
+        self.keys
+            .iter()
+            .zip(values)
+            .map(|(key, value)| match key.typ {
+                     KeyType::Int => value.as_i64().map(Key::Int),
+                     KeyType::Float => value.as_f64().map(Key::Float),
+                     KeyType::Text => value.as_str().map(|s| Key::Text(s.to_owned())),
+                     KeyType::Timestamp => {
+                         let s = value.as_str()?;
+                         DateTime::parse_from_str(s, TIMESTAMP_FORMAT).ok().map(Key::Timestamp)
+                     }
+                 })
+            .collect()
+    }
+
+    pub fn encode(&self, keys: &[Key]) -> String {
+        let mut values = vec![Value::from(self.name)];
+        values.extend(keys.iter().map(|key| match *key {
+                                          Key::Int(i) => Value::from(i),
+                                          Key::Float(f) => Value::from(f),
+                                          Key::Text(ref s) => Value::from(&s[..]),
+                                          Key::Timestamp(t) => {
+                                              Value::from(t.format(TIMESTAMP_FORMAT).to_string())
+                                          }
+                                      }));
+        to_hex(Value::Array(values).to_string().as_bytes())
+    }
+
+    /// Zapytanie wybierające stronę wierszy zapytania `query`, których kolumny obejmują klucze:
+    /// warunek na wiersze po kursorze (jeśli `after`), porządek i limit o jeden większy
+    /// od `limit`, żeby było wiadomo, czy jest następna strona. Wartość `i`-tego klucza
+    /// kursora jest parametrem `placeholder(first + i, typ)`.
+    pub fn select<F>(&self, query: &str, after: bool, limit: u32, first: usize, placeholder: F)
+                     -> String
+        where F: Fn(usize, KeyType) -> String
+    {
+        let condition = if after {
+            let mut condition = String::new();
+            for (i, key) in self.keys.iter().enumerate().rev() {
+                let value = placeholder(first + i, key.typ);
+                let op = if key.descending { "<" } else { ">" };
+                condition = if condition.is_empty() {
+                    format!("{} {} {}", key.column, op, value)
+                } else {
+                    format!("({0} {1} {2} OR ({0} = {2} AND {3}))",
+                            key.column,
+                            op,
+                            value,
+                            condition)
+                };
+            }
+            format!("WHERE {}", condition)
+        } else {
+            "".to_owned()
+        };
+        let order: Vec<String> = self.keys
+            .iter()
+            .map(|key| if key.descending {
+                     format!("{} DESC", key.column)
+                 } else {
+                     key.column.to_owned()
+                 })
+            .collect();
+        let limit = if limit == 0 {
+            "".to_owned()
+        } else {
+            format!("LIMIT {}", limit as u64 + 1)
+        };
+        format!("SELECT * FROM ({}) page {} ORDER BY {} {}",
+                query,
+                condition,
+                order.join(", "),
+                limit)
+    }
+
+    /// Strona z wierszy pobranych przez zapytanie z `select`, wraz z kluczami każdego wiersza.
+    pub fn paginate<T>(&self, mut rows: Vec<(T, Vec<Key>)>, limit: u32) -> Paged<T> {
+        let next_cursor = if limit != 0 && rows.len() > limit as usize {
+            rows.truncate(limit as usize);
+            rows.last().map(|(_, keys)| self.encode(keys))
+        } else {
+            None
+        };
+        Paged {
+            rows: rows.into_iter().map(|(row, _)| row).collect(),
+            next_cursor,
+        }
+    }
+
+    /// Strona całej listy `rows`, posortowanej już wg kluczy - tam, gdzie lista powstaje
+    /// poza bazą.
+    pub fn page<T>(&self, rows: Vec<(T, Vec<Key>)>, page: &Page) -> Result<Paged<T>> {
+        let after = match page.cursor {
+            Some(ref cursor) => Some(self.decode(cursor)?),
+            None => None,
+        };
+        let rows = rows.into_iter()
+            .filter(|(_, keys)| after.as_ref().is_none_or(|after| self.follows(keys, after)))
+            .take(if page.limit == 0 {
+                      usize::MAX
+                  } else {
+                      page.limit as usize + 1
+                  })
+            .collect();
+        Ok(self.paginate(rows, page.limit))
+    }
+
+    /// Czy wiersz o kluczach `keys` jest na liście po wierszu o kluczach `after`.
+    fn follows(&self, keys: &[Key], after: &[Key]) -> bool {
+        for ((key, a), b) in self.keys.iter().zip(keys).zip(after) {
+            let ordering = match (a, b) {
+                (&Key::Int(a), &Key::Int(b)) => a.cmp(&b),
+                (&Key::Float(a), &Key::Float(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+                (Key::Text(a), Key::Text(b)) => a.cmp(b),
+                (&Key::Timestamp(a), &Key::Timestamp(b)) => a.cmp(&b),
+                _ => Ordering::Equal,
+            };
+            match (ordering, key.descending) {
+                (Ordering::Equal, _) => continue,
+                (Ordering::Greater, false) | (Ordering::Less, true) => return true,
+                _ => return false,
+            }
+        }
+        false
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAN: Keyset = Keyset {
+        name: "plan",
+        keys: &[desc("score", KeyType::Float), asc("talk_id", KeyType::Int)],
+    };
+
+    fn rows() -> Vec<(&'static str, Vec<Key>)> {
+        vec![("t3", vec![Key::Float(9.5), Key::Int(3)]),
+             ("t1", vec![Key::Float(7.0), Key::Int(1)]),
+             ("t2", vec![Key::Float(7.0), Key::Int(2)]),
+             ("t4", vec![Key::Float(1.25), Key::Int(4)])]
+    }
+
+    #[test]
+    fn pages_continue_after_the_cursor() {
+        let first = PLAN.page(rows(), &Page { limit: 2, cursor: None }).unwrap();
+        assert_eq!(first.rows, vec!["t3", "t1"]);
+        let second = PLAN.page(rows(),
+                  &Page {
+                       limit: 2,
+                       cursor: first.next_cursor,
+                   })
+            .unwrap();
+        assert_eq!(second,
+                   Paged {
+                       rows: vec!["t2", "t4"],
+                       next_cursor: None,
+                   });
+    }
+
+    #[test]
+    fn cursors_of_other_lists_are_rejected() {
+        let other = Keyset {
+            name: "other",
+            keys: PLAN.keys,
+        };
+        let cursor = other.encode(&[Key::Float(7.0), Key::Int(1)]);
+        assert!(PLAN.decode(&cursor).is_err());
+        assert!(PLAN.decode("not a cursor").is_err());
+        assert_eq!(PLAN.decode(&PLAN.encode(&[Key::Float(7.0), Key::Int(1)])).unwrap(),
+                   vec![Key::Float(7.0), Key::Int(1)]);
+    }
+
+    #[test]
+    fn selects_rows_after_the_cursor_in_sql() {
+        let sql = PLAN.select("SELECT 1", true, 10, 3, |i, _| format!("${}", i));
+        assert_eq!(sql,
+                   "SELECT * FROM (SELECT 1) page \
+                    WHERE (score < $3 OR (score = $3 AND talk_id > $4)) \
+                    ORDER BY score DESC, talk_id LIMIT 11");
+    }
+}
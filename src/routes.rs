@@ -1,14 +1,37 @@
 use errors::*;
 use api::*;
-use database::*;
+use storage::{self, Storage, constant_time_eq};
+use paging::Page;
+use ical::{self, Schedule};
+use connection::ConnectionConfig;
 
-pub struct Context {
-    conn: Option<Connection>,
+use std::rc::Rc;
+
+pub struct Context<S: Storage> {
+    /// Shared with the transaction wrapping a mutating request, see `Request::resolve`.
+    conn: Option<Rc<S>>,
+    /// Connection settings used where the `open` request leaves them out.
+    defaults: ConnectionConfig,
 }
 
-impl Context {
-    pub fn new() -> Context {
-        Context { conn: None }
+impl<S: Storage> Context<S> {
+    pub fn new(defaults: ConnectionConfig) -> Context<S> {
+        Context {
+            conn: None,
+            defaults,
+        }
+    }
+
+    /// Opens the connection from the settings alone, e.g. before serving HTTP,
+    /// where `open` is not accepted.
+    pub fn connect(&mut self) -> Result<()> {
+        let config = self.defaults.clone();
+        self.open(&config)
+    }
+
+    fn open(&mut self, config: &ConnectionConfig) -> Result<()> {
+        self.conn = Some(Rc::new(S::connect(config)?));
+        Ok(())
     }
 }
 
@@ -16,32 +39,127 @@ use std::fmt::Debug;
 pub trait Route
     where Self: Debug + Sized
 {
-    fn route(self, _: &mut Context) -> Result<Response> {
+    fn route<S: Storage>(self, _: &mut Context<S>) -> Result<Response> {
         Ok(Response::NotImplemented)
     }
 }
 
+/// Resolves the end of a talk given either as `end_timestamp` or as `duration` in minutes.
+fn talk_end(start_timestamp: DateTime,
+            end_timestamp: Option<DateTime>,
+            duration: Option<StrOr<i64>>)
+            -> Result<Option<DateTime>> {
+    let end_timestamp = match (end_timestamp, duration) {
+        (Some(_), Some(_)) => {
+            bail!(ErrorKind::Validation("give either end_timestamp or duration".into()))
+        }
+        (Some(end_timestamp), None) => Some(end_timestamp),
+        (None, Some(duration)) => {
+            let minutes = duration.validate()?;
+            if minutes <= 0 {
+                bail!(ErrorKind::Validation("talk must end after it starts".into()))
+            }
+            if minutes > storage::MAX_TALK_MINUTES {
+                bail!(ErrorKind::Validation(format!("duration must be at most {} minutes",
+                                                    storage::MAX_TALK_MINUTES)))
+            }
+            let end_timestamp = start_timestamp
+                .checked_add_signed(::chrono::Duration::minutes(minutes))
+                .ok_or_else(|| ErrorKind::Validation("talk ends too late".into()))?;
+            Some(end_timestamp)
+        }
+        (None, None) => None,
+    };
+    if let Some(end_timestamp) = end_timestamp {
+        if end_timestamp <= start_timestamp {
+            bail!(ErrorKind::Validation("talk must end after it starts".into()))
+        }
+    }
+    Ok(end_timestamp)
+}
+
+/// Resolves the further speakers of a talk, co-speakers unless told otherwise. The primary
+/// speaker is named apart from them, as `primary`.
+fn further_speakers(primary: Option<&str>, speakers: Vec<SpeakerInfo>) -> Result<Vec<Speaker>> {
+    let mut resolved: Vec<Speaker> = Vec::new();
+    for speaker in speakers {
+        let speaker = match speaker {
+            SpeakerInfo::Login(login) => Speaker { login, role: SpeakerRole::CoSpeaker },
+            SpeakerInfo::WithRole { login, role } => Speaker { login, role },
+        };
+        if speaker.role == SpeakerRole::Primary {
+            bail!(ErrorKind::Validation("only speakerlogin is the primary speaker".into()))
+        }
+        if primary == Some(&speaker.login[..]) ||
+           resolved.iter().any(|s| s.login == speaker.login) {
+            bail!(ErrorKind::Validation(format!("speaker `{}` is listed twice", speaker.login)))
+        }
+        resolved.push(speaker);
+    }
+    Ok(resolved)
+}
+
+/// Validates the number of seats of an event or a room, `None` meaning no limit.
+fn capacity(capacity: Option<StrOr<u32>>) -> Result<Option<i32>> {
+    match capacity {
+        Some(capacity) => {
+            match capacity.validate()? {
+                0 => bail!(ErrorKind::Validation("capacity must be positive".into())),
+                c if c > i32::MAX as u32 => {
+                    bail!(ErrorKind::Validation("capacity is too large".into()))
+                }
+                c => Ok(Some(c as i32)),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
 impl Route for OpenInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        ctx.conn = Some(establish_connection(self.login, self.password, self.baza)?);
-        setup_database(ctx.conn.as_ref().unwrap())?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let config = ctx.defaults.for_open(self)?;
+        ctx.open(&config)?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
 impl Route for OrganizerInfo {
-    /// secret has been validated on Request creation
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        create_organizer_account(conn, self.newlogin, self.newpassword)
-            .chain_err(|| "during Request::Organizer")?;
-        Ok(Response::Ok(ResponseInfo::Empty))
+    /// The secret comes from the settings and creates a single organizer. Every attempt
+    /// is recorded, so this runs outside of the transaction wrapping other requests.
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let OrganizerInfo { secret: given, newlogin, newpassword } = self;
+        let result = match ctx.defaults.organizer_secret {
+            None => Ok(OrganizerOutcome::NoSecret),
+            Some(ref secret) if !constant_time_eq(secret.as_bytes(), given.as_bytes()) => {
+                Ok(OrganizerOutcome::InvalidSecret)
+            }
+            Some(ref secret) => {
+                conn.atomically(|| {
+                                    if !conn.use_organizer_secret(secret)? {
+                                        return Ok(OrganizerOutcome::UsedSecret);
+                                    }
+                                    conn.create_organizer_account(newlogin.clone(), newpassword)?;
+                                    Ok(OrganizerOutcome::Created)
+                                })
+            }
+        };
+        let outcome = *result.as_ref().unwrap_or(&OrganizerOutcome::Failed);
+        conn.record_organizer_attempt(&newlogin, outcome)?;
+        let msg = match result.chain_err(|| "during Request::Organizer")? {
+            OrganizerOutcome::Created => return Ok(Response::Ok(ResponseInfo::Empty)),
+            OrganizerOutcome::NoSecret => "no organizer secret is configured",
+            OrganizerOutcome::InvalidSecret => "invalid secret",
+            OrganizerOutcome::UsedSecret => "secret has already been used",
+            OrganizerOutcome::Failed => unreachable!("failures are returned as errors"),
+        };
+        bail!(ErrorKind::Unauthorized(msg.into()))
     }
 }
 
 impl Route for EventInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let start_timestamp = match self.start_timestamp {
             Timestamp::Date(d) => d.and_hms(0, 0, 0),
             Timestamp::DateTime(dt) => dt,
@@ -50,137 +168,237 @@ impl Route for EventInfo {
             Timestamp::Date(d) => d.and_hms(23, 59, 59),
             Timestamp::DateTime(dt) => dt,
         };
-        create_event(conn,
-                     self.login,
-                     self.password,
-                     self.eventname,
-                     start_timestamp,
-                     end_timestamp)
+        let capacity = capacity(self.capacity)?;
+        conn.create_event(self.credentials,
+                          self.eventname,
+                          start_timestamp,
+                          end_timestamp,
+                          capacity)
                 .chain_err(|| "during Request::Event")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for EventCapacityInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let capacity = capacity(self.capacity)?;
+        conn.set_event_capacity(self.credentials, self.eventname, capacity)
+            .chain_err(|| "during Request::EventCapacity")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for CreateRoomInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let capacity = capacity(self.capacity)?;
+        let floor = match self.floor {
+            Some(floor) => Some(floor.validate()?),
+            None => None,
+        };
+        if self.room.trim().is_empty() || self.venue.trim().is_empty() {
+            bail!(ErrorKind::Validation("room and venue must not be blank".into()))
+        }
+        conn.create_room(self.credentials, self.room, self.venue, capacity, floor)
+            .chain_err(|| "during Request::CreateRoom")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for ListRoomsInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let rooms = conn.list_rooms(self.credentials, self.venue)
+            .chain_err(|| "during Request::ListRooms")?;
+        Ok(Response::Ok(ResponseInfo::Rooms(rooms)))
+    }
+}
+
 impl Route for UserInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        create_user(conn,
-                    self.login,
-                    self.password,
-                    self.newlogin,
-                    self.newpassword)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.create_user(self.credentials,
+                         self.newlogin,
+                         self.newpassword)
                 .chain_err(|| "during Request::User")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
 impl Route for TalkInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let initial_evaluation = self.initial_evaluation.validate()?;
-        if 0 > initial_evaluation || initial_evaluation > 10 {
-            bail!("initial_evaluation must be in range 0-10")
+        if !(0..=10).contains(&initial_evaluation) {
+            bail!(ErrorKind::Validation("initial_evaluation must be in range 0-10".into()))
         }
-        register_or_accept_talk(conn,
-                                self.login,
-                                self.password,
-                                self.speakerlogin,
-                                self.talk,
-                                self.title,
-                                self.start_timestamp,
-                                self.room,
-                                initial_evaluation,
-                                self.eventname)
+        let end_timestamp = talk_end(self.start_timestamp, self.end_timestamp, self.duration)?;
+        let mut speakers = vec![Speaker {
+                                    login: self.speakerlogin.clone(),
+                                    role: SpeakerRole::Primary,
+                                }];
+        speakers.extend(further_speakers(Some(&self.speakerlogin), self.speakers)?);
+        conn.register_or_accept_talk(self.credentials,
+                                     speakers,
+                                     self.talk,
+                                     self.title,
+                                     self.start_timestamp,
+                                     end_timestamp,
+                                     self.room,
+                                     initial_evaluation,
+                                     self.eventname)
                 .chain_err(|| "during Request::Talk")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
 impl Route for RegisterUserForEventInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        register_user_for_event(conn, self.login, self.password, self.eventname)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.register_user_for_event(self.credentials, self.eventname)
             .chain_err(|| "during Request::RegisterUserForEvent")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for UnregisterUserFromEventInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.unregister_user_from_event(self.credentials, self.eventname)
+            .chain_err(|| "during Request::UnregisterUserFromEvent")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
 impl Route for AttendanceInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        attendance(conn, self.login, self.password, self.talk)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.attendance(self.credentials, self.talk)
             .chain_err(|| "during Request::Attendance")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for CancelAttendanceInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.cancel_attendance(self.credentials, self.talk)
+            .chain_err(|| "during Request::CancelAttendance")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
 impl Route for EvaluationInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let rating = self.rating.validate()?;
-        if 0 > rating || rating > 10 {
-            bail!("rating must be in range 0-10")
+        if !(0..=10).contains(&rating) {
+            bail!(ErrorKind::Validation("rating must be in range 0-10".into()))
         }
-        evaluation(conn, self.login, self.password, self.talk, rating)
+        // a blank review is no review
+        let comment = self.comment.filter(|comment| !comment.trim().is_empty());
+        conn.evaluation(self.credentials, self.talk, rating, comment)
             .chain_err(|| "during Request::Evaluation")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for RetractEvaluationInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.retract_evaluation(self.credentials, self.talk)
+            .chain_err(|| "during Request::RetractEvaluation")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
 impl Route for RejectInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        reject_spontaneous_talk(conn, self.login, self.password, self.talk)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.reject_spontaneous_talk(self.credentials, self.talk)
             .chain_err(|| "during Request::Reject")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for AcceptInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let initial_evaluation = match self.initial_evaluation {
+            Some(initial_evaluation) => Some(initial_evaluation.validate()?),
+            None => None,
+        };
+        if initial_evaluation.is_some_and(|rating| !(0..=10).contains(&rating)) {
+            bail!(ErrorKind::Validation("initial_evaluation must be in range 0-10".into()))
+        }
+        conn.accept_talk(self.credentials, self.talk, self.room, initial_evaluation)
+            .chain_err(|| "during Request::Accept")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
 impl Route for ProposalInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        propose_spontaneous_talk(conn,
-                                 self.login,
-                                 self.password,
-                                 self.talk,
-                                 self.title,
-                                 self.start_timestamp)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let end_timestamp = talk_end(self.start_timestamp, self.end_timestamp, self.duration)?;
+        let speakers = further_speakers(None, self.speakers)?;
+        conn.propose_spontaneous_talk(self.credentials,
+                                      self.talk,
+                                      self.title,
+                                      self.start_timestamp,
+                                      end_timestamp,
+                                      speakers,
+                                      self.eventname,
+                                      self.room)
                 .chain_err(|| "during Request::Proposal")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
 impl Route for FriendsInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        make_friends(conn, self.login1, self.password, self.login2)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.make_friends(self.credentials, self.login2)
             .chain_err(|| "during Request::Friends")?;
         Ok(Response::Ok(ResponseInfo::Empty))
     }
 }
 
+impl Route for UnfriendInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.unfriend(self.credentials, self.login2)
+            .chain_err(|| "during Request::Unfriend")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
 impl Route for UserPlanInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let limit = self.limit.validate()?;
-        let user_plans = user_plan(conn, self.login, limit)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let user_plans = conn.user_plan(self.login, page)
             .chain_err(|| "during Request::UserPlan")?;
-        Ok(Response::Ok(ResponseInfo::UserPlans(user_plans)))
+        Ok(Response::Page(ResponseInfo::UserPlans(user_plans.rows), user_plans.next_cursor))
     }
 }
 
 impl Route for DayPlanInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let day_plans = day_plan(conn, self.timestamp)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let day_plans = conn.day_plan(self.timestamp)
             .chain_err(|| "during Request::DayPlan")?;
         Ok(Response::Ok(ResponseInfo::DayPlans(day_plans)))
     }
 }
 
 impl Route for BestTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let start_timestamp = match self.start_timestamp {
             Timestamp::Date(d) => d.and_hms(0, 0, 0),
             Timestamp::DateTime(dt) => dt,
@@ -189,17 +407,20 @@ impl Route for BestTalksInfo {
             Timestamp::Date(d) => d.and_hms(23, 59, 59),
             Timestamp::DateTime(dt) => dt,
         };
-        let limit: u32 = self.limit.validate()?;
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
         let all: bool = self.all.validate()? == 1;
-        let best_talks = best_talks(conn, start_timestamp, end_timestamp, limit, all)
+        let best_talks = conn.best_talks(start_timestamp, end_timestamp, page, all)
             .chain_err(|| "during Request::BestTalks")?;
-        Ok(Response::Ok(ResponseInfo::BestTalks(best_talks)))
+        Ok(Response::Page(ResponseInfo::BestTalks(best_talks.rows), best_talks.next_cursor))
     }
 }
 
 impl Route for MostPopularTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let start_timestamp = match self.start_timestamp {
             Timestamp::Date(d) => d.and_hms(0, 0, 0),
             Timestamp::DateTime(dt) => dt,
@@ -208,63 +429,106 @@ impl Route for MostPopularTalksInfo {
             Timestamp::Date(d) => d.and_hms(23, 59, 59),
             Timestamp::DateTime(dt) => dt,
         };
-        let limit: u32 = self.limit.validate()?;
-        let most_popular_talks = most_popular_talks(conn, start_timestamp, end_timestamp, limit)
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let most_popular_talks = conn.most_popular_talks(start_timestamp, end_timestamp, page)
             .chain_err(|| "during Request::MostPopularTalks")?;
-        Ok(Response::Ok(ResponseInfo::MostPopularTalks(most_popular_talks)))
+        Ok(Response::Page(ResponseInfo::MostPopularTalks(most_popular_talks.rows),
+                          most_popular_talks.next_cursor))
     }
 }
 
 impl Route for AttendedTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let talks = attended_talks(conn, self.login, self.password)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let talks = conn.attended_talks(self.credentials)
             .chain_err(|| "during Request::AttendedTalks")?;
         Ok(Response::Ok(ResponseInfo::AttendedTalks(talks)))
     }
 }
 
 impl Route for AbandonedTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let limit: u32 = self.limit.validate()?;
-        let talks = abandoned_talks(conn, self.login, self.password, limit)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let talks = conn.abandoned_talks(self.credentials, page)
             .chain_err(|| "during Request::AbandonedTalks")?;
-        Ok(Response::Ok(ResponseInfo::AbandonedTalks(talks)))
+        Ok(Response::Page(ResponseInfo::AbandonedTalks(talks.rows), talks.next_cursor))
     }
 }
 
 impl Route for RecentlyAddedTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let limit: u32 = self.limit.validate()?;
-        let talks = recently_added_talks(conn, limit)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let talks = conn.recently_added_talks(page)
             .chain_err(|| "during Request::RecentlyAddedTalks")?;
-        Ok(Response::Ok(ResponseInfo::RecentlyAddedTalks(talks)))
+        Ok(Response::Page(ResponseInfo::RecentlyAddedTalks(talks.rows), talks.next_cursor))
+    }
+}
+
+impl Route for SearchTalksInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        if storage::search_words(&self.query).is_empty() {
+            bail!(ErrorKind::Validation("query has no words to search for".into()))
+        }
+        let start_timestamp = self.start_timestamp.map(|start| match start {
+                                                           Timestamp::Date(d) => d.and_hms(0, 0, 0),
+                                                           Timestamp::DateTime(dt) => dt,
+                                                       });
+        let end_timestamp = self.end_timestamp.map(|end| match end {
+                                                       Timestamp::Date(d) => d.and_hms(23, 59, 59),
+                                                       Timestamp::DateTime(dt) => dt,
+                                                   });
+        let status = self.status.unwrap_or(TalkStatusName::Accepted);
+        let page = Page {
+            limit: match self.limit {
+                Some(limit) => limit.validate()?,
+                None => 0,
+            },
+            cursor: self.cursor,
+        };
+        let talks = conn.search_talks(self.credentials,
+                                      self.query,
+                                      start_timestamp,
+                                      end_timestamp,
+                                      status,
+                                      page)
+            .chain_err(|| "during Request::SearchTalks")?;
+        Ok(Response::Page(ResponseInfo::FoundTalks(talks.rows), talks.next_cursor))
     }
 }
 
 impl Route for RejectedTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let talks = rejected_talks(conn, self.login, self.password)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let talks = conn.rejected_talks(self.credentials)
             .chain_err(|| "during Request::RejectedTalks")?;
         Ok(Response::Ok(ResponseInfo::RejectedTalks(talks)))
     }
 }
 
 impl Route for ProposalsInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let talks = proposals(conn, self.login, self.password)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let talks = conn.proposals(self.credentials)
             .chain_err(|| "during Request::Proposals")?;
         Ok(Response::Ok(ResponseInfo::Proposals(talks)))
     }
 }
 
 impl Route for FriendsTalksInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
         let start_timestamp = match self.start_timestamp {
             Timestamp::Date(d) => d.and_hms(0, 0, 0),
             Timestamp::DateTime(dt) => dt,
@@ -273,44 +537,194 @@ impl Route for FriendsTalksInfo {
             Timestamp::Date(d) => d.and_hms(23, 59, 59),
             Timestamp::DateTime(dt) => dt,
         };
-        let limit: u32 = self.limit.validate()?;
-        let talks = friends_talks(conn,
-                                  self.login,
-                                  self.password,
-                                  start_timestamp,
-                                  end_timestamp,
-                                  limit)
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let talks = conn.friends_talks(self.credentials,
+                                       start_timestamp,
+                                       end_timestamp,
+                                       page)
                 .chain_err(|| "during Request::FriendsTalks")?;
-        Ok(Response::Ok(ResponseInfo::FriendsTalks(talks)))
+        Ok(Response::Page(ResponseInfo::FriendsTalks(talks.rows), talks.next_cursor))
     }
 }
 
 impl Route for FriendsEventsInfo {
-    fn route(self, ctx: &mut Context) -> Result<Response> {
-        let conn = ctx.conn.as_ref().ok_or("establish connection first")?;
-        let talks = friends_events(conn, self.login, self.password, self.eventname)
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let talks = conn.friends_events(self.credentials, self.eventname)
             .chain_err(|| "during Request::FriendsEvents")?;
         Ok(Response::Ok(ResponseInfo::FriendsEvents(talks)))
     }
 }
 
-impl Route for RecommendedTalksInfo {}
+impl Route for RecommendedTalksInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let start_timestamp = match self.start_timestamp {
+            Timestamp::Date(d) => d.and_hms(0, 0, 0),
+            Timestamp::DateTime(dt) => dt,
+        };
+        let end_timestamp = match self.end_timestamp {
+            Timestamp::Date(d) => d.and_hms(23, 59, 59),
+            Timestamp::DateTime(dt) => dt,
+        };
+        let page = Page {
+            limit: self.limit.validate()?,
+            cursor: self.cursor,
+        };
+        let talks = conn.recommended_talks(self.credentials,
+                                           start_timestamp,
+                                           end_timestamp,
+                                           page)
+                .chain_err(|| "during Request::RecommendedTalks")?;
+        Ok(Response::Page(ResponseInfo::RecommendedTalks(talks.rows), talks.next_cursor))
+    }
+}
 
-impl Request {
-    pub fn resolve(self, ctx: &mut Context) -> Result<Response> {
+impl Route for WaitlistInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let entries = conn.waitlist(self.credentials, self.eventname)
+            .chain_err(|| "during Request::Waitlist")?;
+        Ok(Response::Ok(ResponseInfo::Waitlist(entries)))
+    }
+}
+
+impl Route for RatingHistoryInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let changes = conn.rating_history(self.credentials, self.talk)
+            .chain_err(|| "during Request::RatingHistory")?;
+        Ok(Response::Ok(ResponseInfo::RatingHistory(changes)))
+    }
+}
+
+impl Route for TalkReviewsInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let reviews = conn.talk_reviews(self.credentials, self.talk)
+            .chain_err(|| "during Request::TalkReviews")?;
+        Ok(Response::Ok(ResponseInfo::TalkReviews(reviews)))
+    }
+}
+
+impl Route for MyFeedbackInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let feedback = conn.my_feedback(self.credentials)
+            .chain_err(|| "during Request::MyFeedback")?;
+        Ok(Response::Ok(ResponseInfo::Feedback(feedback)))
+    }
+}
+
+impl Route for ExportIcalInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let schedule = match (self.eventname, self.timestamp, self.login) {
+            (Some(eventname), None, None) => Schedule::Event(eventname),
+            (None, Some(date), None) => Schedule::Day(date),
+            (None, None, Some(login)) => Schedule::User(login),
+            _ => {
+                let msg = "expected exactly one of eventname, timestamp or login";
+                bail!(ErrorKind::Validation(msg.into()))
+            }
+        };
+        let ics = ical::export(&**conn, schedule).chain_err(|| "during Request::ExportIcal")?;
+        Ok(Response::Ok(ResponseInfo::Ical(ics)))
+    }
+}
+
+impl Route for LoginInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        let sessions = conn.open_session(self.login, self.password)
+            .chain_err(|| "during Request::Login")?;
+        Ok(Response::Ok(ResponseInfo::Sessions(sessions)))
+    }
+}
+
+impl Route for LogoutInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.close_session(self.token)
+            .chain_err(|| "during Request::Logout")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for RevokeSessionsInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.revoke_sessions(self.credentials)
+            .chain_err(|| "during Request::RevokeSessions")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for GrantRoleInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.grant_role(self.credentials, self.personlogin, self.role, self.eventname)
+            .chain_err(|| "during Request::GrantRole")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for RevokeRoleInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.revoke_role(self.credentials, self.personlogin, self.role, self.eventname)
+            .chain_err(|| "during Request::RevokeRole")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for GrantEventOrganizerInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.grant_event_organizer(self.credentials,
+                                   self.personlogin,
+                                   self.eventname,
+                                   self.owner)
+            .chain_err(|| "during Request::GrantEventOrganizer")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for RevokeEventOrganizerInfo {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = ctx.conn.as_ref().ok_or(ErrorKind::NotConnected)?;
+        conn.revoke_event_organizer(self.credentials, self.personlogin, self.eventname)
+            .chain_err(|| "during Request::RevokeEventOrganizer")?;
+        Ok(Response::Ok(ResponseInfo::Empty))
+    }
+}
+
+impl Route for Request {
+    fn route<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
         use Request::*;
         match self {
             Open(info) => info.route(ctx),
             Organizer(info) => info.route(ctx),
             Event(info) => info.route(ctx),
+            EventCapacity(info) => info.route(ctx),
+            CreateRoom(info) => info.route(ctx),
+            ListRooms(info) => info.route(ctx),
             User(info) => info.route(ctx),
             Talk(info) => info.route(ctx),
             RegisterUserForEvent(info) => info.route(ctx),
+            UnregisterUserFromEvent(info) => info.route(ctx),
             Attendance(info) => info.route(ctx),
+            CancelAttendance(info) => info.route(ctx),
             Evaluation(info) => info.route(ctx),
+            RetractEvaluation(info) => info.route(ctx),
             Reject(info) => info.route(ctx),
+            Accept(info) => info.route(ctx),
             Proposal(info) => info.route(ctx),
             Friends(info) => info.route(ctx),
+            Unfriend(info) => info.route(ctx),
             UserPlan(info) => info.route(ctx),
             DayPlan(info) => info.route(ctx),
             BestTalks(info) => info.route(ctx),
@@ -318,11 +732,943 @@ impl Request {
             AttendedTalks(info) => info.route(ctx),
             AbandonedTalks(info) => info.route(ctx),
             RecentlyAddedTalks(info) => info.route(ctx),
+            SearchTalks(info) => info.route(ctx),
             RejectedTalks(info) => info.route(ctx),
             Proposals(info) => info.route(ctx),
             FriendsTalks(info) => info.route(ctx),
             FriendsEvents(info) => info.route(ctx),
             RecommendedTalks(info) => info.route(ctx),
+            Waitlist(info) => info.route(ctx),
+            RatingHistory(info) => info.route(ctx),
+            TalkReviews(info) => info.route(ctx),
+            MyFeedback(info) => info.route(ctx),
+            ExportIcal(info) => info.route(ctx),
+            Login(info) => info.route(ctx),
+            Logout(info) => info.route(ctx),
+            RevokeSessions(info) => info.route(ctx),
+            GrantRole(info) => info.route(ctx),
+            RevokeRole(info) => info.route(ctx),
+            GrantEventOrganizer(info) => info.route(ctx),
+            RevokeEventOrganizer(info) => info.route(ctx),
+        }
+    }
+}
+
+impl Request {
+    /// Routes the request. A request that changes data runs in a transaction,
+    /// which is rolled back if the request fails, so ERROR means nothing has changed.
+    /// `organizer` manages its own transaction, as its attempts are recorded either way.
+    pub fn resolve<S: Storage>(self, ctx: &mut Context<S>) -> Result<Response> {
+        let conn = match self {
+            Request::Open(_) | Request::Organizer(_) => None,
+            ref request if request.is_mutating() => ctx.conn.clone(),
+            _ => None,
+        };
+        match conn {
+            Some(conn) => conn.atomically(|| self.route(ctx)),
+            None => self.route(ctx),
         }
     }
 }
+
+// ================================================
+//                    TESTS
+// ================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use memory::MemoryStorage;
+    use sqlite::SqliteStorage;
+    use connection::Engine;
+    use postgres::Connection;
+    use tabular::{self, OutputFormat};
+    use {response_json, error_json};
+    use serde_json::Value;
+
+    const SECRET: &str = "d8578edf8458ce06fbc5bb76a58c5ca4";
+
+    /// Generates a test per scenario in `$module`, each scenario gets the `open` line
+    /// built by `$open` from its name that connects to a fresh `$storage`.
+    macro_rules! scenarios {
+        ($(#[$attr:meta])* $module:ident, $storage:ty, $open:expr) => {
+            scenarios!(@tests [$(#[$attr])*] $module, $storage, $open =>
+                       talks_are_planned_and_rated,
+                       rooms_are_not_double_booked,
+                       proposals_are_accepted_or_rejected,
+                       failed_requests_change_nothing,
+                       friends_and_sessions,
+                       full_events_keep_a_waitlist,
+                       removals_show_up_in_derived_queries,
+                       ratings_are_changed_or_retracted,
+                       reviews_reach_organizers_and_speakers,
+                       schedules_are_exported_as_ical,
+                       talks_are_found_by_words,
+                       lists_are_paged_by_cursor,
+                       talks_take_known_rooms,
+                       talks_have_several_speakers,
+                       organizer_secret_is_single_use,
+                       roles_are_scoped_to_events,
+                       events_are_administered_by_their_organizers,
+                       event_organizers_rate_talks,
+                       proposals_are_accepted_into_their_event);
+        };
+        (@tests $attrs:tt $module:ident, $storage:ty, $open:expr => $($scenario:ident),*) => {
+            mod $module {
+                use super::*;
+                $(
+                    scenarios!(@test $attrs $storage, $open, $scenario);
+                )*
+            }
+        };
+        (@test [$(#[$attr:meta])*] $storage:ty, $open:expr, $scenario:ident) => {
+            #[test]
+            $(#[$attr])*
+            fn $scenario() {
+                super::$scenario::<$storage>(&$open(stringify!($scenario)));
+            }
+        };
+    }
+
+    scenarios!(memory_storage, MemoryStorage, |_| {
+        r#"{"open": {"baza": "test", "login": "test", "password": "test"}}"#
+    });
+    scenarios!(sqlite_storage, SqliteStorage, |_| {
+        r#"{"open": {"backend": "sqlite", "baza": ":memory:"}}"#
+    });
+    scenarios!(#[ignore = "needs DATABASE_URL of a Postgres server, run with --ignored"]
+               postgres_storage,
+               Connection,
+               postgres_open);
+
+    /// Recreates a database named after the scenario on the server of `DATABASE_URL`
+    /// and opens it, so that scenarios running side by side don't share data.
+    fn postgres_open(scenario: &str) -> String {
+        let url = ::std::env::var("DATABASE_URL").expect("DATABASE_URL is not set");
+        let database = format!("rusty_ems_{}", scenario);
+        let server = Connection::open(&ConnectionConfig {
+                                          url: Some(url.clone()),
+                                          ..ConnectionConfig::default()
+                                      })
+                .unwrap();
+        server.batch_execute(&format!("DROP DATABASE IF EXISTS {}", database)).unwrap();
+        server.batch_execute(&format!("CREATE DATABASE {}", database)).unwrap();
+        json!({"open": {"url": url, "baza": database}}).to_string()
+    }
+
+    fn connected<S: Storage>(open: &str) -> Context<S> {
+        let mut ctx = Context::new(ConnectionConfig {
+                                       organizer_secret: Some(SECRET.to_owned()),
+                                       ..ConnectionConfig::default()
+                                   });
+        assert_eq!(call(&mut ctx, open)["status"], "OK");
+        call(&mut ctx,
+             &format!(r#"{{"organizer": {{"secret": "{}", "newlogin": "o", "newpassword": "o"}}}}"#,
+                      SECRET));
+        call(&mut ctx,
+             r#"{"event": {"login": "o", "password": "o", "eventname": "E",
+                           "start_timestamp": "2030-01-01", "end_timestamp": "2030-01-05"}}"#);
+        for login in &["u1", "u2", "u3"] {
+            call(&mut ctx,
+                 &format!(r#"{{"user": {{"login": "o", "password": "o",
+                                         "newlogin": "{0}", "newpassword": "{0}"}}}}"#,
+                          login));
+        }
+        for room in &["A", "B", "Rust"] {
+            call(&mut ctx,
+                 &format!(r#"{{"create_room": {{"login": "o", "password": "o", "room": "{}",
+                                                "venue": "Main"}}}}"#,
+                          room));
+        }
+        ctx
+    }
+
+    /// Passes a line of the stdin protocol through the same steps as `main_step`.
+    /// A request that changes nothing is also rendered as CSV, which checks
+    /// `ResponseInfo::columns` of every list the scenarios see.
+    fn call<S: Storage>(ctx: &mut Context<S>, line: &str) -> Value {
+        if let Ok(request) = read_call(line) {
+            if !request.is_mutating() {
+                if let Ok(response) = request.resolve(ctx) {
+                    tabular::tabulate(response, OutputFormat::Csv).unwrap();
+                }
+            }
+        }
+        match read_call(line).and_then(|request| request.resolve(ctx)) {
+            Ok(response) => response_json(response),
+            Err(e) => error_json(&e),
+        }
+    }
+
+    fn cursor_of(response: &Value) -> String {
+        response["next_cursor"].as_str().unwrap().to_owned()
+    }
+
+    fn talk<S: Storage>(ctx: &mut Context<S>, talk: &str, start: &str, room: &str) -> Value {
+        call(ctx,
+             &format!(r#"{{"talk": {{"login": "o", "password": "o", "speakerlogin": "u1",
+                                     "talk": "{}", "title": "T", "start_timestamp": "{}",
+                                     "room": "{}", "initial_evaluation": 5,
+                                     "eventname": "E"}}}}"#,
+                      talk,
+                      start,
+                      room))
+    }
+
+    #[test]
+    fn requests_need_an_open_connection() {
+        let mut ctx = Context::<MemoryStorage>::new(ConnectionConfig::default());
+        let response = call(&mut ctx, r#"{"proposals": {"login": "o", "password": "o"}}"#);
+        assert_eq!(response["code"], "not_connected");
+    }
+
+    #[test]
+    fn connections_are_opened_from_the_settings() {
+        let mut ctx = Context::<SqliteStorage>::new(ConnectionConfig {
+                                                        backend: Some(Engine::Sqlite),
+                                                        database: Some(":memory:".to_owned()),
+                                                        ..ConnectionConfig::default()
+                                                    });
+        ctx.connect().unwrap();
+        let response = call(&mut ctx, r#"{"proposals": {"login": "o", "password": "o"}}"#);
+        assert_eq!(response["code"], "unauthorized");
+    }
+
+    fn talks_are_planned_and_rated<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        assert_eq!(talk(&mut ctx, "t2", "2030-01-02 10:00:00", "B")["status"], "OK");
+        for login in &["u2", "u3"] {
+            let attendance = format!(r#"{{"attendance": {{"login": "{0}", "password": "{0}",
+                                                          "talk": "t2"}}}}"#,
+                                     login);
+            assert_eq!(call(&mut ctx, &attendance)["status"], "OK");
+        }
+        let evaluation = r#"{"evaluation": {"login": "u2", "password": "u2",
+                                            "talk": "t2", "rating": 10}}"#;
+        assert_eq!(call(&mut ctx, evaluation)["status"], "OK");
+        assert_eq!(call(&mut ctx, evaluation)["status"], "OK");
+
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-02"}}"#);
+        assert_eq!(plan["data"][0]["talk"], "t1");
+        assert_eq!(plan["data"][0]["end_timestamp"], "2030-01-02 10:30:00");
+        assert_eq!(plan["data"][1]["room"], "B");
+
+        let best = call(&mut ctx,
+                        r#"{"best_talks": {"start_timestamp": "2030-01-01",
+                                           "end_timestamp": "2030-01-05",
+                                           "limit": 0, "all": 1}}"#);
+        assert_eq!(best["data"][0]["talk"], "t2");
+        let popular = call(&mut ctx,
+                           r#"{"most_popular_talks": {"start_timestamp": "2030-01-01",
+                                                      "end_timestamp": "2030-01-05",
+                                                      "limit": 1}}"#);
+        assert_eq!(popular["data"].as_array().unwrap().len(), 1);
+        assert_eq!(popular["data"][0]["talk"], "t2");
+    }
+
+    fn rooms_are_not_double_booked<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        let response = talk(&mut ctx, "t2", "2030-01-02 10:15:00", "A");
+        assert_eq!(response["code"], "conflict");
+        assert_eq!(talk(&mut ctx, "t2", "2030-01-02 10:30:00", "A")["status"], "OK");
+    }
+
+    fn proposals_are_accepted_or_rejected<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        for (talk, start) in &[("p1", "2030-01-03 10:00:00"), ("p2", "2030-01-03 12:00:00")] {
+            let proposal = format!(r#"{{"proposal": {{"login": "u1", "password": "u1",
+                                                      "talk": "{}", "title": "P",
+                                                      "start_timestamp": "{}",
+                                                      "duration": 90}}}}"#,
+                                   talk,
+                                   start);
+            assert_eq!(call(&mut ctx, &proposal)["status"], "OK");
+        }
+        let proposals = r#"{"proposals": {"login": "o", "password": "o"}}"#;
+        assert_eq!(call(&mut ctx, proposals)["data"].as_array().unwrap().len(), 2);
+
+        assert_eq!(talk(&mut ctx, "p1", "2030-01-03 10:00:00", "A")["status"], "OK");
+        let reject = r#"{"reject": {"login": "o", "password": "o", "talk": "p2"}}"#;
+        assert_eq!(call(&mut ctx, reject)["status"], "OK");
+        assert_eq!(call(&mut ctx, reject)["code"], "not_found");
+        assert_eq!(call(&mut ctx, proposals)["data"], json!([]));
+
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-03"}}"#);
+        assert_eq!(plan["data"][0]["end_timestamp"], "2030-01-03 11:30:00");
+        let endless = r#"{"proposal": {"login": "u1", "password": "u1", "talk": "p3",
+                                       "title": "P", "start_timestamp": "2030-01-03 10:00:00",
+                                       "duration": "1000000000000"}}"#;
+        assert_eq!(call(&mut ctx, endless)["code"], "validation");
+        let rejected = call(&mut ctx, r#"{"rejected_talks": {"login": "u1", "password": "u1"}}"#);
+        assert_eq!(rejected["data"][0]["talk"], "p2");
+    }
+
+    fn failed_requests_change_nothing<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        // the organizer has already rated t1, so the whole request fails
+        let response = talk(&mut ctx, "t1", "2030-01-04 10:00:00", "A");
+        assert_eq!(response["code"], "conflict");
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-02"}}"#);
+        assert_eq!(plan["data"][0]["talk"], "t1");
+    }
+
+    fn friends_and_sessions<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let friends = r#"{"friends": {"login1": "u2", "password": "u2", "login2": "u1"}}"#;
+        assert_eq!(call(&mut ctx, friends)["status"], "OK");
+        let friends = r#"{"friends": {"login1": "u1", "password": "u1", "login2": "u2"}}"#;
+        assert_eq!(call(&mut ctx, friends)["status"], "OK");
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+
+        let login = call(&mut ctx, r#"{"login": {"login": "u2", "password": "u2"}}"#);
+        let token = login["data"][0]["token"].as_str().unwrap().to_owned();
+        let friends_talks = format!(r#"{{"friends_talks": {{"token": "{}",
+                                                           "start_timestamp": "2030-01-01",
+                                                           "end_timestamp": "2030-01-05",
+                                                           "limit": 0}}}}"#,
+                                    token);
+        assert_eq!(call(&mut ctx, &friends_talks)["data"][0]["speakerlogin"], "u1");
+
+        let recommended = friends_talks.replace("friends_talks", "recommended_talks");
+        assert_eq!(call(&mut ctx, &recommended)["data"][0]["score"], 50);
+
+        let logout = format!(r#"{{"logout": {{"token": "{}"}}}}"#, token);
+        assert_eq!(call(&mut ctx, &logout)["status"], "OK");
+        assert_eq!(call(&mut ctx, &friends_talks)["code"], "unauthorized");
+    }
+
+    fn full_events_keep_a_waitlist<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let event = r#"{"event": {"login": "o", "password": "o", "eventname": "W",
+                                  "start_timestamp": "2030-02-01", "end_timestamp": "2030-02-02",
+                                  "capacity": 1}}"#;
+        assert_eq!(call(&mut ctx, event)["status"], "OK");
+        let register = |login: &str| {
+            format!(r#"{{"register_user_for_event": {{"login": "{0}", "password": "{0}",
+                                                       "eventname": "W"}}}}"#,
+                    login)
+        };
+        for login in &["u1", "u2", "u3"] {
+            assert_eq!(call(&mut ctx, &register(login))["status"], "OK");
+        }
+        assert_eq!(call(&mut ctx, &register("u1"))["code"], "conflict");
+        assert_eq!(call(&mut ctx, &register("u2"))["code"], "conflict");
+
+        let waitlist = r#"{"waitlist": {"login": "o", "password": "o", "eventname": "W"}}"#;
+        let response = call(&mut ctx, waitlist);
+        assert_eq!(response["data"][0]["login"], "u2");
+        assert_eq!(response["data"][1]["login"], "u3");
+        assert_eq!(response["data"][1]["position"], 2);
+        let own = r#"{"waitlist": {"login": "u3", "password": "u3", "eventname": "W"}}"#;
+        assert_eq!(call(&mut ctx, own)["data"], json!([{"login": "u3", "eventname": "W",
+                                                        "position": 2}]));
+
+        let unregister = register("u1").replace("register_user_for_event",
+                                                "unregister_user_from_event");
+        assert_eq!(call(&mut ctx, &unregister)["status"], "OK");
+        assert_eq!(call(&mut ctx, &unregister)["code"], "not_found");
+        assert_eq!(call(&mut ctx, own)["data"][0]["position"], 1);
+        assert_eq!(call(&mut ctx, &register("u1"))["status"], "OK");
+
+        let capacity = r#"{"event_capacity": {"login": "o", "password": "o", "eventname": "W",
+                                              "capacity": 2}}"#;
+        assert_eq!(call(&mut ctx, capacity)["status"], "OK");
+        assert_eq!(call(&mut ctx, own)["data"], json!([]));
+        let unlimited = r#"{"event_capacity": {"login": "o", "password": "o", "eventname": "W"}}"#;
+        assert_eq!(call(&mut ctx, unlimited)["status"], "OK");
+        assert_eq!(call(&mut ctx, waitlist)["data"], json!([]));
+
+        let none = capacity.replace("2}", "0}");
+        assert_eq!(call(&mut ctx, &none)["code"], "validation");
+    }
+
+    fn removals_show_up_in_derived_queries<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        let as_user = |request: &str, login: &str, field: &str| {
+            format!(r#"{{"{}": {{"login": "{2}", "password": "{2}", {}}}}}"#,
+                    request,
+                    field,
+                    login)
+        };
+
+        let register = as_user("register_user_for_event", "u2", r#""eventname": "E""#);
+        assert_eq!(call(&mut ctx, &register)["status"], "OK");
+        let plan = r#"{"user_plan": {"login": "u2", "limit": 0}}"#;
+        assert_eq!(call(&mut ctx, plan)["data"][0]["talk"], "t1");
+        let unregister = register.replace("register_user_for_event", "unregister_user_from_event");
+        assert_eq!(call(&mut ctx, &unregister)["status"], "OK");
+        assert_eq!(call(&mut ctx, plan)["data"], json!([]));
+        assert_eq!(call(&mut ctx, &unregister)["code"], "not_found");
+
+        assert_eq!(call(&mut ctx, &register)["status"], "OK");
+        let attendance = as_user("attendance", "u2", r#""talk": "t1""#);
+        assert_eq!(call(&mut ctx, &attendance)["status"], "OK");
+        let abandoned = r#"{"abandoned_talks": {"login": "o", "password": "o", "limit": 0}}"#;
+        assert_eq!(call(&mut ctx, abandoned)["data"], json!([]));
+        let cancel = attendance.replace("attendance", "cancel_attendance");
+        assert_eq!(call(&mut ctx, &cancel)["status"], "OK");
+        assert_eq!(call(&mut ctx, abandoned)["data"][0]["number"], 1);
+        assert_eq!(call(&mut ctx, &cancel)["code"], "not_found");
+
+        for &(login, friend) in &[("u1", "u2"), ("u2", "u1")] {
+            let friends = format!(r#"{{"friends": {{"login1": "{0}", "password": "{0}",
+                                                    "login2": "{1}"}}}}"#,
+                                  login,
+                                  friend);
+            assert_eq!(call(&mut ctx, &friends)["status"], "OK");
+        }
+        let friends_talks = as_user("friends_talks",
+                                    "u2",
+                                    r#""start_timestamp": "2030-01-01",
+                                       "end_timestamp": "2030-01-05", "limit": 0"#);
+        assert_eq!(call(&mut ctx, &friends_talks)["data"][0]["talk"], "t1");
+        let unfriend = as_user("unfriend", "u2", r#""login2": "u1""#);
+        assert_eq!(call(&mut ctx, &unfriend)["status"], "OK");
+        assert_eq!(call(&mut ctx, &friends_talks)["data"], json!([]));
+        assert_eq!(call(&mut ctx, &unfriend)["code"], "not_found");
+    }
+
+    fn ratings_are_changed_or_retracted<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        let evaluation = |rating: i16| {
+            format!(r#"{{"evaluation": {{"login": "u2", "password": "u2",
+                                         "talk": "t1", "rating": {}}}}}"#,
+                    rating)
+        };
+        assert_eq!(call(&mut ctx, &evaluation(9))["status"], "OK");
+        // the same rating again is not a change
+        assert_eq!(call(&mut ctx, &evaluation(9))["status"], "OK");
+        assert_eq!(call(&mut ctx, &evaluation(3))["status"], "OK");
+
+        let retract = r#"{"retract_evaluation": {"login": "u2", "password": "u2", "talk": "t1"}}"#;
+        assert_eq!(call(&mut ctx, retract)["status"], "OK");
+        assert_eq!(call(&mut ctx, retract)["code"], "not_found");
+
+        let history = r#"{"rating_history": {"login": "o", "password": "o", "talk": "t1"}}"#;
+        let history = call(&mut ctx, history);
+        let changes: Vec<_> = history["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|c| (c["login"].clone(), c["rating"].clone(), c["average"].clone()))
+            .collect();
+        assert_eq!(changes,
+                   vec![(json!("o"), json!(5), json!(5.0)),
+                        (json!("u2"), json!(9), json!(7.0)),
+                        (json!("u2"), json!(3), json!(4.0)),
+                        (json!("u2"), json!(null), json!(5.0))]);
+
+        let as_user = r#"{"rating_history": {"login": "u2", "password": "u2", "talk": "t1"}}"#;
+        assert_eq!(call(&mut ctx, as_user)["code"], "unauthorized");
+        let unknown = r#"{"rating_history": {"login": "o", "password": "o", "talk": "t9"}}"#;
+        assert_eq!(call(&mut ctx, unknown)["code"], "not_found");
+    }
+
+    fn reviews_reach_organizers_and_speakers<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        let attendance = r#"{"attendance": {"login": "u2", "password": "u2", "talk": "t1"}}"#;
+        assert_eq!(call(&mut ctx, attendance)["status"], "OK");
+        for &(login, rating, comment) in &[("u2", 8, "Great demo"), ("u3", 4, "Too fast")] {
+            let evaluation = format!(r#"{{"evaluation": {{"login": "{0}", "password": "{0}",
+                                                          "talk": "t1", "rating": {1},
+                                                          "comment": "{2}"}}}}"#,
+                                     login,
+                                     rating,
+                                     comment);
+            assert_eq!(call(&mut ctx, &evaluation)["status"], "OK");
+        }
+
+        let reviews = r#"{"talk_reviews": {"login": "o", "password": "o", "talk": "t1"}}"#;
+        assert_eq!(call(&mut ctx, reviews)["data"],
+                   json!([{"login": "o", "rating": 5, "comment": null, "attended": false},
+                          {"login": "u2", "rating": 8, "comment": "Great demo", "attended": true},
+                          {"login": "u3", "rating": 4, "comment": "Too fast", "attended": false}]));
+        let as_user = r#"{"talk_reviews": {"login": "u1", "password": "u1", "talk": "t1"}}"#;
+        assert_eq!(call(&mut ctx, as_user)["code"], "unauthorized");
+
+        let feedback = r#"{"my_feedback": {"login": "u1", "password": "u1"}}"#;
+        assert_eq!(call(&mut ctx, feedback)["data"],
+                   json!([{"talk": "t1", "rating": 8, "comment": "Great demo"},
+                          {"talk": "t1", "rating": 4, "comment": "Too fast"}]));
+        // a new rating without a comment replaces the review
+        let evaluation = r#"{"evaluation": {"login": "u2", "password": "u2",
+                                            "talk": "t1", "rating": 8, "comment": " "}}"#;
+        assert_eq!(call(&mut ctx, evaluation)["status"], "OK");
+        assert_eq!(call(&mut ctx, feedback)["data"].as_array().unwrap().len(), 1);
+        let others = r#"{"my_feedback": {"login": "u2", "password": "u2"}}"#;
+        assert_eq!(call(&mut ctx, others)["data"], json!([]));
+    }
+
+    fn schedules_are_exported_as_ical<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "A")["status"], "OK");
+        assert_eq!(talk(&mut ctx, "t2", "2030-01-03 10:00:00", "B")["status"], "OK");
+        let uids = |response: Value| -> Vec<String> {
+            response["data"]
+                .as_str()
+                .unwrap()
+                .split("\r\n")
+                .filter(|line| line.starts_with("UID:"))
+                .map(|line| line.to_owned())
+                .collect()
+        };
+
+        let event = r#"{"export_ical": {"eventname": "E"}}"#;
+        assert_eq!(uids(call(&mut ctx, event)), vec!["UID:t1@rusty_ems", "UID:t2@rusty_ems"]);
+        let day = r#"{"export_ical": {"timestamp": "2030-01-03"}}"#;
+        assert_eq!(uids(call(&mut ctx, day)), vec!["UID:t2@rusty_ems"]);
+        let register = r#"{"register_user_for_event": {"login": "u2", "password": "u2",
+                                                       "eventname": "E"}}"#;
+        assert_eq!(call(&mut ctx, register)["status"], "OK");
+        let plan = r#"{"export_ical": {"login": "u2"}}"#;
+        assert_eq!(uids(call(&mut ctx, plan)).len(), 2);
+
+        let both = r#"{"export_ical": {"eventname": "E", "login": "u2"}}"#;
+        assert_eq!(call(&mut ctx, both)["code"], "validation");
+        let unknown = r#"{"export_ical": {"eventname": "X"}}"#;
+        assert_eq!(call(&mut ctx, unknown)["code"], "not_found");
+    }
+
+    fn talks_are_found_by_words<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let talks = [("t1", "Rust in production", "2030-01-02 10:00:00", "A"),
+                     ("t2", "Ownership", "2030-01-03 10:00:00", "Rust"),
+                     ("t3", "Databases", "2030-01-04 10:00:00", "B")];
+        for &(talk, title, start, room) in &talks {
+            let register = format!(r#"{{"talk": {{"login": "o", "password": "o",
+                                                  "speakerlogin": "u1", "talk": "{}",
+                                                  "title": "{}", "start_timestamp": "{}",
+                                                  "room": "{}", "initial_evaluation": 5,
+                                                  "eventname": "E"}}}}"#,
+                                   talk,
+                                   title,
+                                   start,
+                                   room);
+            assert_eq!(call(&mut ctx, &register)["status"], "OK");
+        }
+        let proposal = r#"{"proposal": {"login": "u2", "password": "u2", "talk": "p1",
+                                        "title": "Rust macros",
+                                        "start_timestamp": "2030-01-03 12:00:00"}}"#;
+        assert_eq!(call(&mut ctx, proposal)["status"], "OK");
+        let found = |response: Value| -> Vec<String> {
+            response["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|talk| talk["talk"].as_str().unwrap().to_owned())
+                .collect()
+        };
+
+        // a word in the title outweighs the same word in the room
+        let search = r#"{"search_talks": {"query": "rust"}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t1", "t2"]);
+        let search = r#"{"search_talks": {"query": "RUST production"}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t1"]);
+        let search = r#"{"search_talks": {"query": "u1", "start_timestamp": "2030-01-03",
+                                          "end_timestamp": "2030-01-03", "limit": 1}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t2"]);
+
+        // the websearch_to_tsquery operators work the same in every database
+        let search = r#"{"search_talks": {"query": "rust -production"}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t2"]);
+        let search = r#"{"search_talks": {"query": "-rust"}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t3"]);
+        let search = r#"{"search_talks": {"query": "ownership OR databases"}}"#;
+        let mut either = found(call(&mut ctx, search));
+        either.sort();
+        assert_eq!(either, vec!["t2", "t3"]);
+        let search = r#"{"search_talks": {"query": "\"in production\""}}"#;
+        assert_eq!(found(call(&mut ctx, search)), vec!["t1"]);
+        let search = r#"{"search_talks": {"query": "\"production in\""}}"#;
+        assert!(found(call(&mut ctx, search)).is_empty());
+
+        let proposed = r#"{"search_talks": {"query": "rust", "status": "proposed"}}"#;
+        assert_eq!(call(&mut ctx, proposed)["code"], "unauthorized");
+        let proposed = r#"{"search_talks": {"login": "u2", "password": "u2", "query": "rust",
+                                            "status": "proposed"}}"#;
+        assert_eq!(call(&mut ctx, proposed)["code"], "unauthorized");
+        let proposed = r#"{"search_talks": {"login": "o", "password": "o", "query": "rust",
+                                            "status": "proposed"}}"#;
+        assert_eq!(found(call(&mut ctx, proposed)), vec!["p1"]);
+
+        let empty = r#"{"search_talks": {"query": " - "}}"#;
+        assert_eq!(call(&mut ctx, empty)["code"], "validation");
+    }
+
+    fn lists_are_paged_by_cursor<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        for &(name, start) in &[("t1", "2030-01-02 10:00:00"),
+                                ("t2", "2030-01-02 12:00:00"),
+                                ("t3", "2030-01-03 10:00:00")] {
+            assert_eq!(talk(&mut ctx, name, start, "A")["status"], "OK");
+        }
+        let register = r#"{"register_user_for_event": {"login": "u2", "password": "u2",
+                                                       "eventname": "E"}}"#;
+        assert_eq!(call(&mut ctx, register)["status"], "OK");
+        let talks = |response: &Value| -> Vec<String> {
+            response["data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|talk| talk["talk"].as_str().unwrap().to_owned())
+                .collect()
+        };
+
+        let first = call(&mut ctx, r#"{"user_plan": {"login": "u2", "limit": 2}}"#);
+        assert_eq!(talks(&first), vec!["t1", "t2"]);
+        let cursor = first["next_cursor"].as_str().unwrap().to_owned();
+        let next = format!(r#"{{"user_plan": {{"login": "u2", "limit": 2, "cursor": "{}"}}}}"#,
+                           cursor);
+        let second = call(&mut ctx, &next);
+        assert_eq!(talks(&second), vec!["t3"]);
+        assert!(second["next_cursor"].is_null());
+        let all = call(&mut ctx, r#"{"user_plan": {"login": "u2", "limit": 0}}"#);
+        assert_eq!(talks(&all).len(), 3);
+        assert!(all["next_cursor"].is_null());
+
+        // equal averages are ordered, and paged, by the talk
+        let mut seen = Vec::new();
+        let mut cursor = Value::Null;
+        loop {
+            let page = json!({"best_talks": {"start_timestamp": "2030-01-01",
+                                             "end_timestamp": "2030-01-05",
+                                             "limit": 1, "all": 1, "cursor": cursor}});
+            let page = call(&mut ctx, &page.to_string());
+            seen.extend(talks(&page));
+            cursor = page["next_cursor"].clone();
+            if cursor.is_null() {
+                break;
+            }
+        }
+        assert_eq!(seen, vec!["t1", "t2", "t3"]);
+
+        let other = format!(r#"{{"recently_added_talks": {{"limit": 1, "cursor": "{}"}}}}"#,
+                            cursor_of(&first));
+        assert_eq!(call(&mut ctx, &other)["code"], "validation");
+    }
+
+    fn talks_have_several_speakers<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let talk = |speakers: Value| {
+            json!({"talk": {"login": "o", "password": "o", "speakerlogin": "u1", "talk": "t1",
+                            "title": "T", "start_timestamp": "2030-01-02 10:00:00", "room": "A",
+                            "initial_evaluation": 5, "eventname": "E", "speakers": speakers}})
+                .to_string()
+        };
+        let primary = json!([{"login": "u1", "role": "primary"}]);
+        assert_eq!(call(&mut ctx, &talk(primary))["code"], "validation");
+        assert_eq!(call(&mut ctx, &talk(json!(["u2", "u2"])))["code"], "validation");
+        assert_eq!(call(&mut ctx, &talk(json!(["u2", "nobody"])))["code"], "not_found");
+        let speakers = json!(["u2", {"login": "u3", "role": "moderator"}]);
+        assert_eq!(call(&mut ctx, &talk(speakers))["status"], "OK");
+
+        let all = json!([{"login": "u1", "role": "primary"},
+                         {"login": "u2", "role": "co_speaker"},
+                         {"login": "u3", "role": "moderator"}]);
+        let recent = call(&mut ctx, r#"{"recently_added_talks": {"limit": 0}}"#);
+        assert_eq!(recent["data"][0]["speakerlogin"], "u1");
+        assert_eq!(recent["data"][0]["speakers"], all);
+        let search = call(&mut ctx, r#"{"search_talks": {"query": "u3"}}"#);
+        assert_eq!(search["data"][0]["speakers"], all);
+
+        // a co-speaker is as much a friend's talk as the primary one
+        for &(login1, login2) in &[("u2", "u3"), ("u3", "u2")] {
+            let friends = format!(r#"{{"friends": {{"login1": "{0}", "password": "{0}",
+                                                    "login2": "{1}"}}}}"#,
+                                  login1,
+                                  login2);
+            assert_eq!(call(&mut ctx, &friends)["status"], "OK");
+        }
+        let friends_talks = r#"{"friends_talks": {"login": "u3", "password": "u3",
+                                                 "start_timestamp": "2030-01-01",
+                                                 "end_timestamp": "2030-01-05", "limit": 0}}"#;
+        assert_eq!(call(&mut ctx, friends_talks)["data"][0]["talk"], "t1");
+        let recommended = friends_talks.replace("friends_talks", "recommended_talks");
+        assert_eq!(call(&mut ctx, &recommended)["data"], json!([]));
+
+        let proposal = r#"{"proposal": {"login": "u2", "password": "u2", "talk": "p1",
+                                        "title": "P", "start_timestamp": "2030-01-03 10:00:00",
+                                        "speakers": ["u3"]}}"#;
+        assert_eq!(call(&mut ctx, proposal)["status"], "OK");
+        let proposals = call(&mut ctx, r#"{"proposals": {"login": "o", "password": "o"}}"#);
+        assert_eq!(proposals["data"][0]["speakers"],
+                   json!([{"login": "u2", "role": "primary"},
+                          {"login": "u3", "role": "co_speaker"}]));
+        let reject = r#"{"reject": {"login": "o", "password": "o", "talk": "p1"}}"#;
+        assert_eq!(call(&mut ctx, reject)["status"], "OK");
+        let rejected = call(&mut ctx, r#"{"rejected_talks": {"login": "u3", "password": "u3"}}"#);
+        assert_eq!(rejected["data"][0]["talk"], "p1");
+    }
+
+    fn talks_take_known_rooms<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let room = r#"{"create_room": {"login": "o", "password": "o", "room": "C1",
+                                       "venue": "Annex", "capacity": 40, "floor": -1}}"#;
+        assert_eq!(call(&mut ctx, room)["status"], "OK");
+        let again = r#"{"create_room": {"login": "o", "password": "o", "room": "c1",
+                                        "venue": "annex"}}"#;
+        assert_eq!(call(&mut ctx, again)["code"], "conflict");
+        let by_user = r#"{"create_room": {"login": "u1", "password": "u1", "room": "D",
+                                          "venue": "Annex"}}"#;
+        assert_eq!(call(&mut ctx, by_user)["code"], "unauthorized");
+
+        // a room is spelled as it was created, a typo is not a new room
+        assert_eq!(talk(&mut ctx, "t1", "2030-01-02 10:00:00", "c1")["status"], "OK");
+        assert_eq!(talk(&mut ctx, "t2", "2030-01-02 12:00:00", "Room C1")["code"],
+                   "not_found");
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-02"}}"#);
+        assert_eq!(plan["data"],
+                   json!([{"talk": "t1", "start_timestamp": "2030-01-02 10:00:00",
+                           "end_timestamp": "2030-01-02 10:30:00", "title": "T", "room": "C1",
+                           "venue": "Annex", "capacity": 40, "floor": -1,
+                           "speakers": [{"login": "u1", "role": "primary"}]}]));
+
+        let rooms = r#"{"list_rooms": {"login": "o", "password": "o", "venue": "ANNEX"}}"#;
+        assert_eq!(call(&mut ctx, rooms)["data"],
+                   json!([{"room": "C1", "venue": "Annex", "capacity": 40, "floor": -1}]));
+        let rooms = r#"{"list_rooms": {"login": "o", "password": "o"}}"#;
+        let names: Vec<Value> = call(&mut ctx, rooms)["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|room| room["room"].clone())
+            .collect();
+        assert_eq!(names, vec!["A", "B", "C1", "Rust"]);
+        let unknown = r#"{"list_rooms": {"login": "o", "password": "o", "venue": "Nowhere"}}"#;
+        assert_eq!(call(&mut ctx, unknown)["code"], "not_found");
+    }
+
+    fn organizer_secret_is_single_use<S: Storage>(open: &str) {
+        let organizer = |secret: &str, newlogin: &str| {
+            format!(r#"{{"organizer": {{"secret": "{}", "newlogin": "{}",
+                                        "newpassword": "p"}}}}"#,
+                    secret,
+                    newlogin)
+        };
+        let mut ctx = connected::<S>(open);
+        let reused = call(&mut ctx, &organizer(SECRET, "o2"));
+        assert_eq!(reused["code"], "unauthorized");
+        assert_eq!(reused["message"], "secret has already been used");
+        let invalid = call(&mut ctx, &organizer("guess", "o2"));
+        assert_eq!(invalid["message"], "invalid secret");
+
+        // a failed attempt leaves the secret unused
+        ctx.defaults.organizer_secret = Some("rotated".to_owned());
+        assert_eq!(call(&mut ctx, &organizer("rotated", "o"))["code"], "conflict");
+        assert_eq!(call(&mut ctx, &organizer("rotated", "o2"))["status"], "OK");
+
+        let attempts = ctx.conn.as_ref().unwrap().organizer_attempts().unwrap();
+        let attempts: Vec<_> = attempts.iter().map(|a| (&a.newlogin[..], a.outcome)).collect();
+        assert_eq!(attempts,
+                   vec![("o", OrganizerOutcome::Created),
+                        ("o2", OrganizerOutcome::UsedSecret),
+                        ("o2", OrganizerOutcome::InvalidSecret),
+                        ("o", OrganizerOutcome::Failed),
+                        ("o2", OrganizerOutcome::Created)]);
+
+        let mut ctx = Context::<S>::new(ConnectionConfig::default());
+        call(&mut ctx, open);
+        let unconfigured = call(&mut ctx, &organizer(SECRET, "o"));
+        assert_eq!(unconfigured["message"], "no organizer secret is configured");
+    }
+
+    fn roles_are_scoped_to_events<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        call(&mut ctx,
+             r#"{"event": {"login": "o", "password": "o", "eventname": "F",
+                           "start_timestamp": "2030-02-01", "end_timestamp": "2030-02-05"}}"#);
+        let talk_by_u1 = |talk: &str, start: &str, eventname: &str| {
+            format!(r#"{{"talk": {{"login": "u1", "password": "u1", "speakerlogin": "u2",
+                                   "talk": "{}", "title": "T", "start_timestamp": "{}",
+                                   "room": "A", "initial_evaluation": 5,
+                                   "eventname": "{}"}}}}"#,
+                    talk,
+                    start,
+                    eventname)
+        };
+        let denied = call(&mut ctx, &talk_by_u1("t1", "2030-01-02 10:00:00", "E"));
+        assert_eq!(denied["code"], "unauthorized");
+        assert_eq!(denied["message"],
+                   "requires organizing the event or one of the roles admin, program_committee, \
+                    track_chair in it");
+
+        let grant = r#"{"grant_role": {"login": "o", "password": "o", "personlogin": "u1",
+                                       "role": "track_chair", "eventname": "E"}}"#;
+        assert_eq!(call(&mut ctx, grant)["status"], "OK");
+        assert_eq!(call(&mut ctx, grant)["code"], "conflict");
+        assert_eq!(call(&mut ctx, &talk_by_u1("t1", "2030-01-02 10:00:00", "E"))["status"],
+                   "OK");
+        assert_eq!(call(&mut ctx, &talk_by_u1("t2", "2030-02-02 10:00:00", "F"))["code"],
+                   "unauthorized");
+        let grant_by_u1 = r#"{"grant_role": {"login": "u1", "password": "u1",
+                                             "personlogin": "u2", "role": "admin"}}"#;
+        assert_eq!(call(&mut ctx, grant_by_u1)["code"], "unauthorized");
+
+        // organizers may take part in events like anyone else
+        let register = r#"{"register_user_for_event": {"login": "o", "password": "o",
+                                                       "eventname": "E"}}"#;
+        assert_eq!(call(&mut ctx, register)["status"], "OK");
+
+        let revoke = r#"{"revoke_role": {"login": "o", "password": "o", "personlogin": "u1",
+                                         "role": "track_chair", "eventname": "E"}}"#;
+        assert_eq!(call(&mut ctx, revoke)["status"], "OK");
+        assert_eq!(call(&mut ctx, revoke)["code"], "not_found");
+        assert_eq!(call(&mut ctx, &talk_by_u1("t3", "2030-01-03 10:00:00", "E"))["code"],
+                   "unauthorized");
+        let unknown = r#"{"grant_role": {"login": "o", "password": "o", "personlogin": "u1",
+                                         "role": "janitor"}}"#;
+        assert_eq!(call(&mut ctx, unknown)["code"], "validation");
+    }
+
+    fn events_are_administered_by_their_organizers<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        call(&mut ctx,
+             r#"{"grant_role": {"login": "o", "password": "o", "personlogin": "u1",
+                                "role": "admin"}}"#);
+        call(&mut ctx,
+             r#"{"event": {"login": "u1", "password": "u1", "eventname": "F",
+                           "start_timestamp": "2030-02-01", "end_timestamp": "2030-02-05"}}"#);
+        let talk = |login: &str, talk: &str, start: &str, eventname: &str| {
+            format!(r#"{{"talk": {{"login": "{0}", "password": "{0}", "speakerlogin": "u2",
+                                   "talk": "{1}", "title": "T", "start_timestamp": "{2}",
+                                   "room": "A", "initial_evaluation": 5,
+                                   "eventname": "{3}"}}}}"#,
+                    login,
+                    talk,
+                    start,
+                    eventname)
+        };
+        // every admin, but only the creator organizes an event
+        assert_eq!(call(&mut ctx, &talk("u1", "t1", "2030-01-02 10:00:00", "E"))["code"],
+                   "unauthorized");
+        assert_eq!(call(&mut ctx, &talk("o", "t2", "2030-02-02 10:00:00", "F"))["code"],
+                   "unauthorized");
+        assert_eq!(call(&mut ctx, &talk("o", "t1", "2030-01-02 10:00:00", "E"))["status"],
+                   "OK");
+        call(&mut ctx,
+             r#"{"register_user_for_event": {"login": "u3", "password": "u3",
+                                             "eventname": "E"}}"#);
+        let abandoned = |login: &str| {
+            format!(r#"{{"abandoned_talks": {{"login": "{0}", "password": "{0}",
+                                              "limit": 0}}}}"#,
+                    login)
+        };
+        assert_eq!(call(&mut ctx, &abandoned("o"))["data"][0]["talk"], "t1");
+        assert_eq!(call(&mut ctx, &abandoned("u1"))["data"], json!([]));
+
+        let grant = |login: &str, personlogin: &str, owner: bool| {
+            format!(r#"{{"grant_event_organizer": {{"login": "{0}", "password": "{0}",
+                                                    "personlogin": "{1}", "eventname": "F",
+                                                    "owner": {2}}}}}"#,
+                    login,
+                    personlogin,
+                    owner)
+        };
+        let revoke = |login: &str, personlogin: &str| {
+            format!(r#"{{"revoke_event_organizer": {{"login": "{0}", "password": "{0}",
+                                                     "personlogin": "{1}",
+                                                     "eventname": "F"}}}}"#,
+                    login,
+                    personlogin)
+        };
+        let denied = call(&mut ctx, &grant("o", "o", false));
+        assert_eq!(denied["code"], "unauthorized");
+        assert_eq!(denied["message"], "requires owning the event");
+        assert_eq!(call(&mut ctx, &grant("u1", "o", false))["status"], "OK");
+        assert_eq!(call(&mut ctx, &grant("u1", "o", false))["code"], "conflict");
+        assert_eq!(call(&mut ctx, &talk("o", "t2", "2030-02-02 10:00:00", "F"))["status"],
+                   "OK");
+        // an organizer of F alone cannot move a talk of E into F
+        assert_eq!(call(&mut ctx, &talk("u1", "t1", "2030-02-02 12:00:00", "F"))["code"],
+                   "unauthorized");
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-02"}}"#);
+        assert_eq!(plan["data"][0]["talk"], "t1");
+        // an organizer who does not own the event cannot choose the others
+        assert_eq!(call(&mut ctx, &grant("o", "u3", false))["code"], "unauthorized");
+
+        assert_eq!(call(&mut ctx, &revoke("u1", "u1"))["code"], "conflict");
+        assert_eq!(call(&mut ctx, &grant("u1", "u2", true))["status"], "OK");
+        assert_eq!(call(&mut ctx, &revoke("u1", "u1"))["status"], "OK");
+        assert_eq!(call(&mut ctx, &talk("u1", "t3", "2030-02-03 10:00:00", "F"))["code"],
+                   "unauthorized");
+        assert_eq!(call(&mut ctx, &revoke("u2", "o"))["status"], "OK");
+        assert_eq!(call(&mut ctx, &revoke("u2", "o"))["code"], "not_found");
+    }
+
+    fn event_organizers_rate_talks<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        call(&mut ctx,
+             r#"{"grant_event_organizer": {"login": "o", "password": "o", "personlogin": "u3",
+                                           "eventname": "E", "owner": false}}"#);
+        let scheduled = call(&mut ctx,
+                             r#"{"talk": {"login": "u3", "password": "u3", "speakerlogin": "u2",
+                                          "talk": "t1", "title": "T",
+                                          "start_timestamp": "2030-01-02 10:00:00",
+                                          "room": "A", "initial_evaluation": 9,
+                                          "eventname": "E"}}"#);
+        assert_eq!(scheduled["status"], "OK");
+        // the initial evaluation of an organizer without a role counts like any other
+        let best = call(&mut ctx,
+                        r#"{"best_talks": {"start_timestamp": "2030-01-01",
+                                           "end_timestamp": "2030-01-05",
+                                           "limit": 0, "all": 0}}"#);
+        assert_eq!(best["data"][0]["talk"], "t1");
+    }
+
+    fn proposals_are_accepted_into_their_event<S: Storage>(open: &str) {
+        let mut ctx = connected::<S>(open);
+        let propose = |talk: &str, start: &str, extra: &str| {
+            format!(r#"{{"proposal": {{"login": "u1", "password": "u1", "talk": "{}",
+                                       "title": "P", "start_timestamp": "{}",
+                                       "duration": 45, "eventname": "E"{}}}}}"#,
+                    talk,
+                    start,
+                    extra)
+        };
+        assert_eq!(call(&mut ctx, &propose("p0", "2031-01-02 10:00:00", ""))["code"],
+                   "not_found");
+        let unknown_room = propose("p0", "2030-01-02 10:00:00", r#", "room": "Z""#);
+        assert_eq!(call(&mut ctx, &unknown_room)["code"], "not_found");
+        let with_room = r#", "room": "a", "speakers": ["u2"]"#;
+        assert_eq!(call(&mut ctx, &propose("p1", "2030-01-02 10:00:00", with_room))["status"],
+                   "OK");
+        assert_eq!(call(&mut ctx, &propose("p2", "2030-01-02 12:00:00", ""))["status"], "OK");
+        call(&mut ctx,
+             r#"{"register_user_for_event": {"login": "u3", "password": "u3",
+                                             "eventname": "E"}}"#);
+        let abandoned = r#"{"abandoned_talks": {"login": "o", "password": "o", "limit": 0}}"#;
+        assert_eq!(call(&mut ctx, abandoned)["data"], json!([]));
+
+        let accept_p1 = r#"{"accept": {"login": "o", "password": "o", "talk": "p1"}}"#;
+        let accept_by_u1 = r#"{"accept": {"login": "u1", "password": "u1", "talk": "p1"}}"#;
+        assert_eq!(call(&mut ctx, accept_by_u1)["code"], "unauthorized");
+        assert_eq!(call(&mut ctx, accept_p1)["status"], "OK");
+        assert_eq!(call(&mut ctx, accept_p1)["code"], "not_found");
+        let plan = call(&mut ctx, r#"{"day_plan": {"timestamp": "2030-01-02"}}"#);
+        assert_eq!(plan["data"][0]["talk"], "p1");
+        assert_eq!(plan["data"][0]["room"], "A");
+        assert_eq!(plan["data"][0]["end_timestamp"], "2030-01-02 10:45:00");
+        assert_eq!(plan["data"][0]["speakers"],
+                   json!([{"login": "u1", "role": "primary"},
+                          {"login": "u2", "role": "co_speaker"}]));
+        assert_eq!(call(&mut ctx, abandoned)["data"][0]["talk"], "p1");
+
+        let accept_p2 = |extra: &str| {
+            format!(r#"{{"accept": {{"login": "o", "password": "o", "talk": "p2"{}}}}}"#,
+                    extra)
+        };
+        assert_eq!(call(&mut ctx, &accept_p2(""))["code"], "validation");
+        let out_of_range = accept_p2(r#", "room": "B", "initial_evaluation": 11"#);
+        assert_eq!(call(&mut ctx, &out_of_range)["code"], "validation");
+        let rated = accept_p2(r#", "room": "B", "initial_evaluation": 7"#);
+        assert_eq!(call(&mut ctx, &rated)["status"], "OK");
+        let history = r#"{"rating_history": {"login": "o", "password": "o", "talk": "p2"}}"#;
+        assert_eq!(call(&mut ctx, history)["data"][0]["rating"], 7);
+    }
+}
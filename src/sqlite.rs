@@ -0,0 +1,2088 @@
+use errors::*;
+
+use api::{Date, DateTime, Credentials, Session, WaitlistEntry, RatingChange, TalkReview,
+          Feedback, TalkStatusName, Room, Speaker, SpeakerRole, OrganizerOutcome,
+          OrganizerAttempt, Role};
+use api::{AttendedTalk, UserPlan, DayPlan, BestTalk, MostPopularTalk, AbandonedTalk,
+          RecentlyAddedTalk, RejectedTalk, Proposal, FriendsTalk, FriendsEvent, RecommendedTalk,
+          FoundTalk};
+
+use connection::ConnectionConfig;
+use database::TalkStatus;
+use migrate::{self, Migration, MigrationTarget, SQLITE_MIGRATIONS};
+use paging::{Key, KeyType, Keyset, Page, Paged, asc, desc};
+use storage::{Storage, Permission, SESSION_TTL_HOURS, DEFAULT_TALK_MINUTES, new_session_token, hash_password,
+              search_rank, secret_digest, verify_unknown_login};
+
+use bcrypt;
+use chrono::{Duration, Local};
+use rusqlite::{self, params_from_iter, Params, Row, ToSql};
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, Type, ValueRef};
+use serde_json;
+
+/// Format znaczników czasu przechowywanych w kolumnach tekstowych,
+/// porządek leksykograficzny odpowiada chronologicznemu.
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Odczyt dopuszcza ułamki sekund, które wstawia `strftime('%f')`.
+const TIMESTAMP_PARSE_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Baza SQLite w pliku, dla instalacji bez serwera baz danych.
+///
+/// Każde zapytanie ma tę samą semantykę co jego odpowiednik w `database`. Czas bieżący
+/// pochodzi z programu, a nie z bazy, bo SQLite nie ma odpowiednika `now()` w strefie lokalnej
+/// zwracającego znacznik czasu w formacie kolumn.
+pub struct SqliteStorage {
+    conn: rusqlite::Connection,
+}
+
+/// `DateTime` zapisywany jako tekst w formacie `TIMESTAMP_FORMAT`.
+struct Timestamp(DateTime);
+
+impl ToSql for Timestamp {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.0.format(TIMESTAMP_FORMAT).to_string().into())
+    }
+}
+
+impl FromSql for Timestamp {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        DateTime::parse_from_str(value.as_str()?, TIMESTAMP_PARSE_FORMAT)
+            .map(Timestamp)
+            .map_err(|e| FromSqlError::Other(Box::new(e)))
+    }
+}
+
+impl ToSql for TalkStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let status = match *self {
+            TalkStatus::Proposed => "Proposed",
+            TalkStatus::Accepted => "Accepted",
+            TalkStatus::Rejected => "Rejected",
+        };
+        Ok(status.into())
+    }
+}
+
+fn now() -> Timestamp {
+    Timestamp(Local::now().naive_local())
+}
+
+impl ToSql for Key {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        match *self {
+            Key::Int(i) => Ok(i.into()),
+            Key::Float(f) => Ok(f.into()),
+            Key::Text(ref s) => Ok(s.as_str().into()),
+            Key::Timestamp(t) => Ok(t.format(TIMESTAMP_FORMAT).to_string().into()),
+        }
+    }
+}
+
+/// Znaczniki czasu są kluczami tekstowymi, porównywanymi tak jak w kolumnach.
+const USER_PLAN: Keyset = Keyset {
+    name: "user_plan",
+    keys: &[asc("start_timestamp", KeyType::Text), asc("talk_id", KeyType::Int)],
+};
+
+const BEST_TALKS: Keyset = Keyset {
+    name: "best_talks",
+    keys: &[desc("average_rate", KeyType::Float), asc("talk_id", KeyType::Int)],
+};
+
+const MOST_POPULAR_TALKS: Keyset = Keyset {
+    name: "most_popular_talks",
+    keys: &[desc("arrivals", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const ABANDONED_TALKS: Keyset = Keyset {
+    name: "abandoned_talks",
+    keys: &[desc("absent", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+const RECENTLY_ADDED_TALKS: Keyset = Keyset {
+    name: "recently_added_talks",
+    keys: &[desc("modified_at", KeyType::Text), asc("talk_id", KeyType::Int)],
+};
+
+/// Wyniki wyszukiwania są sortowane w programie, więc klucze nie odpowiadają kolumnom.
+const SEARCH_TALKS: Keyset = Keyset {
+    name: "search_talks",
+    keys: &[desc("score", KeyType::Float),
+            asc("start_timestamp", KeyType::Timestamp),
+            asc("talk_id", KeyType::Int)],
+};
+
+const FRIENDS_TALKS: Keyset = Keyset {
+    name: "friends_talks",
+    keys: &[asc("start_timestamp", KeyType::Text), asc("talk_id", KeyType::Int)],
+};
+
+const RECOMMENDED_TALKS: Keyset = Keyset {
+    name: "recommended_talks",
+    keys: &[desc("score", KeyType::Int), asc("talk_id", KeyType::Int)],
+};
+
+fn timestamp(row: &Row, column: &str) -> rusqlite::Result<DateTime> {
+    row.get::<_, Timestamp>(column).map(|t| t.0)
+}
+
+/// Prelegenci referatu z kolumny `speakers` widoku talk_speaker_lists.
+fn speakers(row: &Row) -> rusqlite::Result<Vec<Speaker>> {
+    let speakers: String = row.get("speakers")?;
+    serde_json::from_str(&speakers)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(0, Type::Text, Box::new(e)))
+}
+
+/// Wynik próby utworzenia organizatora z kolumny `outcome`.
+fn organizer_outcome(row: &Row) -> rusqlite::Result<OrganizerOutcome> {
+    let outcome: String = row.get("outcome")?;
+    outcome.parse().map_err(|e: Error| {
+        rusqlite::Error::FromSqlConversionFailure(0, Type::Text, e.to_string().into())
+    })
+}
+
+/// Jak `database::organized_events`, z osobą w parametrze `?1`.
+fn organized_events(permission: Permission) -> String {
+    format!(r#"
+        SELECT event_id FROM event_organizers
+        WHERE person_id = ?1
+        UNION
+        SELECT event_id FROM person_roles
+        WHERE person_id = ?1
+          AND event_id IS NOT NULL
+          AND role IN ({})"#,
+            permission.sql_roles())
+}
+
+fn sort_keys(row: &Row, keyset: &Keyset) -> rusqlite::Result<Vec<Key>> {
+    keyset.keys
+        .iter()
+        .map(|key| match key.typ {
+                 KeyType::Int => row.get(key.column).map(Key::Int),
+                 KeyType::Float => row.get(key.column).map(Key::Float),
+                 KeyType::Text => row.get(key.column).map(Key::Text),
+                 KeyType::Timestamp => timestamp(row, key.column).map(Key::Timestamp),
+             })
+        .collect()
+}
+
+impl SqliteStorage {
+    /// Otwiera (lub tworzy) bazę w pliku `path`, `:memory:` oznacza bazę w pamięci.
+    pub fn open_path(path: &str) -> Result<SqliteStorage> {
+        let conn = rusqlite::Connection::open(path)
+            .chain_err(|| format!("Error opening SQLite database {}", path))?;
+        conn.execute_batch("PRAGMA foreign_keys = ON")?;
+        Ok(SqliteStorage { conn })
+    }
+
+    fn query<T, P, F>(&self, query: &str, params: P, f: F) -> rusqlite::Result<Vec<T>>
+        where P: Params,
+              F: FnMut(&Row) -> rusqlite::Result<T>
+    {
+        let mut statement = self.conn.prepare(query)?;
+        let rows = statement.query_map(params, f)?.collect();
+        rows
+    }
+
+    /// Strona `page` wierszy zapytania `query` posortowanych wg `keyset`, jak
+    /// `database::query_page`.
+    fn query_page<T, F>(&self,
+                        keyset: &Keyset,
+                        query: &str,
+                        params: &[&dyn ToSql],
+                        page: &Page,
+                        f: F)
+                        -> Result<Paged<T>>
+        where F: Fn(&Row) -> rusqlite::Result<T>
+    {
+        let after = match page.cursor {
+            Some(ref cursor) => keyset.decode(cursor)?,
+            None => Vec::new(),
+        };
+        let query = keyset.select(query,
+                                  !after.is_empty(),
+                                  page.limit,
+                                  params.len() + 1,
+                                  |i, _| format!("?{}", i));
+        let mut params = params.to_vec();
+        params.extend(after.iter().map(|key| key as &dyn ToSql));
+        let rows = self.query(&query,
+                              params_from_iter(params),
+                              |row| Ok((f(row)?, sort_keys(row, keyset)?)))?;
+        Ok(keyset.paginate(rows, page.limit))
+    }
+
+    /// Szuka osoby <login>, a jeśli podano <password>, sprawdza jej hasło.
+    fn authorize_person(&self, login: &str, password: Option<&str>) -> Result<i64> {
+        let query = r#"
+            SELECT id, password FROM persons
+            WHERE login = ?1
+            LIMIT 1"#;
+        let person: Option<(i64, String)> =
+            self.query(query, [login], |row| Ok((row.get("id")?, row.get("password")?)))
+                .chain_err(|| "Unable to authorize person")?
+                .into_iter()
+                .next();
+        let (id, password_hash) = match (person, password) {
+            (Some(person), _) => person,
+            // nie zdradzamy, czy login istnieje - ani odpowiedzią, ani jej czasem
+            (None, Some(password)) => {
+                verify_unknown_login(password);
+                bail!(ErrorKind::Unauthorized("Invalid login or password".into()))
+            }
+            (None, None) => bail!(ErrorKind::NotFound("Requested person not found".into())),
+        };
+
+        if let Some(password) = password {
+            if !bcrypt::verify(password, &password_hash).unwrap_or(false) {
+                bail!(ErrorKind::Unauthorized("Invalid login or password".into()))
+            }
+        }
+
+        Ok(id)
+    }
+
+    fn authenticate(&self, credentials: &Credentials) -> Result<i64> {
+        match *credentials {
+            Credentials::Password { ref login, ref password } => {
+                self.authorize_person(login, Some(password))
+            }
+            Credentials::Token { ref token } => {
+                let query = r#"
+                    SELECT person_id FROM sessions
+                    WHERE token = ?1
+                      AND expires_at > ?2
+                    LIMIT 1"#;
+                self.query(query, (token, now()), |row| row.get("person_id"))
+                    .chain_err(|| "Unable to authorize person")?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| {
+                        ErrorKind::Unauthorized("Session expired or not found".into()).into()
+                    })
+            }
+        }
+    }
+
+    /// Jak `database::has_permission`.
+    fn has_permission(&self,
+                      person_id: i64,
+                      permission: Permission,
+                      event_id: Option<i64>)
+                      -> Result<bool> {
+        let query = r#"
+            SELECT role FROM person_roles
+            WHERE person_id = ?1
+              AND (event_id IS NULL OR event_id = ?2)"#;
+        let roles: Vec<String> = self.query(query, (person_id, event_id), |row| row.get("role"))
+            .chain_err(|| "Unable to load roles")?;
+        Ok(roles.iter().any(|role| permission.is_granted_by(role)))
+    }
+
+    fn authorize(&self,
+                 person_id: i64,
+                 permission: Permission,
+                 event_id: Option<i64>)
+                 -> Result<()> {
+        if !self.has_permission(person_id, permission, event_id)? {
+            return Err(permission.denied());
+        }
+        Ok(())
+    }
+
+    /// Jak `database::may_organize`.
+    fn may_organize(&self,
+                    person_id: i64,
+                    permission: Permission,
+                    event_id: Option<i64>)
+                    -> Result<bool> {
+        let event_id = match event_id {
+            Some(event_id) => event_id,
+            None => return self.has_permission(person_id, permission, None),
+        };
+        let query = format!(r#"
+            SELECT event_id FROM ({}) AS organized
+            WHERE event_id = ?2"#,
+                            organized_events(permission));
+        let rows: Vec<i64> = self.query(&query, (person_id, event_id), |row| row.get("event_id"))
+            .chain_err(|| "Unable to load organized events")?;
+        Ok(!rows.is_empty())
+    }
+
+    fn authorize_organizer(&self,
+                           person_id: i64,
+                           permission: Permission,
+                           event_id: Option<i64>)
+                           -> Result<()> {
+        if !self.may_organize(person_id, permission, event_id)? {
+            return Err(match event_id {
+                           Some(_) => permission.denied_in_event(),
+                           None => permission.denied(),
+                       });
+        }
+        Ok(())
+    }
+
+    /// Jak `database::authorize_owner`.
+    fn authorize_owner(&self, person_id: i64, eventname: &str) -> Result<i64> {
+        let event_id = self.event_id(eventname)?;
+        let query = r#"
+            SELECT person_id FROM event_organizers
+            WHERE event_id = ?1
+              AND person_id = ?2
+              AND is_owner"#;
+        let owners: Vec<i64> = self.query(query, (event_id, person_id), |row| row.get(0))
+            .chain_err(|| "Unable to load the owners")?;
+        if owners.is_empty() {
+            bail!(ErrorKind::Unauthorized("requires owning the event".into()))
+        }
+        Ok(event_id)
+    }
+
+    fn insert_organizer(&self, event_id: i64, person_id: i64, owner: bool) -> Result<()> {
+        let query = r#"
+            INSERT INTO event_organizers (event_id, person_id, is_owner)
+            VALUES (?1, ?2, ?3)"#;
+        self.conn
+            .execute(query, (event_id, person_id, owner))
+            .map(|_| ())
+            .chain_err(|| "Unable to add the organizer")
+    }
+
+    fn person_login(&self, person_id: i64) -> Result<String> {
+        let query = r#"
+            SELECT login FROM persons
+            WHERE id = ?1"#;
+        self.query(query, [person_id], |row| row.get("login"))
+            .chain_err(|| "Unable to load person")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorKind::NotFound("Requested person not found".into()).into())
+    }
+
+    fn accepted_talk_id(&self, talk: &str) -> Result<Option<i64>> {
+        let query = r#"
+            SELECT id FROM talks
+            WHERE talk = ?1
+              AND status = ?2
+            LIMIT 1"#;
+        Ok(self.query(query, (talk, TalkStatus::Accepted), |row| row.get("id"))
+               .chain_err(|| "Unable to load the talk")?
+               .into_iter()
+               .next())
+    }
+
+    fn talk_id(&self, talk: &str) -> Result<i64> {
+        let query = r#"
+            SELECT id FROM talks
+            WHERE talk = ?1
+            LIMIT 1"#;
+        self.query(query, [talk], |row| row.get("id"))
+            .chain_err(|| "Unable to load the talk")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)).into()
+            })
+    }
+
+    /// Wydarzenie referatu <talk>, jak `database::talk_event`.
+    fn talk_event(&self, talk: &str) -> Result<Option<i64>> {
+        let query = r#"
+            SELECT event_id FROM talks
+            WHERE talk = ?1"#;
+        Ok(self.query(query, [talk], |row| row.get::<_, Option<i64>>("event_id"))
+               .chain_err(|| "Unable to load the talk")?
+               .into_iter()
+               .flatten()
+               .next())
+    }
+
+    fn event_named(&self, eventname: &str) -> Result<Option<i64>> {
+        let query = r#"
+            SELECT id FROM events
+            WHERE eventname = ?1
+            LIMIT 1"#;
+        Ok(self.query(query, [eventname], |row| row.get("id"))
+               .chain_err(|| "Unable to load event")?
+               .into_iter()
+               .next())
+    }
+
+    fn event_id(&self, eventname: &str) -> Result<i64> {
+        self.event_named(eventname)?
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+                    .into()
+            })
+    }
+
+    /// Zakres roli z grant_role i revoke_role, jak `database::role_event`.
+    fn role_event(&self, eventname: Option<String>) -> Result<Option<i64>> {
+        match eventname {
+            Some(eventname) => Ok(Some(self.event_id(&eventname)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Nazwa sali `room` w pisowni z create_room, o ile sala istnieje; kolumna `room`
+    /// nie rozróżnia wielkości liter.
+    fn room_named(&self, room: &str) -> Result<Option<String>> {
+        let query = r#"
+            SELECT room FROM rooms
+            WHERE room = ?1"#;
+        Ok(self.query(query, [room], |row| row.get("room"))
+               .chain_err(|| "Unable to load room")?
+               .into_iter()
+               .next())
+    }
+
+    /// Zastępuje prelegentów referatu prelegentem głównym `primary_id` i kolejnymi `others`.
+    fn set_speakers(&self, talk_id: i64, primary_id: i64, others: &[Speaker]) -> Result<()> {
+        let query = r#"
+            DELETE FROM talk_speakers
+            WHERE talk_id = ?1"#;
+        self.conn
+            .execute(query, [talk_id])
+            .chain_err(|| "Unable to remove the speakers")?;
+
+        let query = r#"
+            INSERT INTO talk_speakers (talk_id, person_id, role, position)
+            VALUES (?1, ?2, ?3, ?4)"#;
+        self.conn
+            .execute(query, (talk_id, primary_id, SpeakerRole::Primary.as_str(), 0))
+            .chain_err(|| "Unable to add a speaker")?;
+        for (position, speaker) in others.iter().enumerate() {
+            let person_id = self.authorize_person(&speaker.login, None)?;
+            self.conn
+                .execute(query, (talk_id, person_id, speaker.role.as_str(), position + 1))
+                .chain_err(|| "Unable to add a speaker")?;
+        }
+        Ok(())
+    }
+
+    /// Odnotowuje zmianę oceny w historii, `None` oznacza wycofanie oceny.
+    fn record_rating(&self, person_id: i64, talk_id: i64, rating: Option<i16>) -> Result<()> {
+        let query = r#"
+            INSERT INTO rating_history (person_id, talk_id, rating, changed_at)
+            VALUES (?1, ?2, ?3, ?4)"#;
+        self.conn
+            .execute(query, (person_id, talk_id, rating, now()))
+            .map(|_| ())
+            .chain_err(|| "Unable to record the rating")
+    }
+
+    /// Free seats of the event, `None` if it takes everyone.
+    fn free_seats(&self, event_id: i64) -> Result<Option<i64>> {
+        let query = r#"
+            SELECT capacity - (SELECT count(*)
+                               FROM person_registered_for_event
+                               WHERE event_id = events.id) AS free
+            FROM events
+            WHERE id = ?1"#;
+        self.conn
+            .query_row(query, [event_id], |row| row.get("free"))
+            .chain_err(|| "Unable to load event")
+    }
+
+    fn promote_waitlisted(&self, event_id: i64) -> Result<()> {
+        let free = self.free_seats(event_id)?;
+        if free.is_some_and(|free| free <= 0) {
+            return Ok(());
+        }
+
+        // LIMIT -1 takes everyone
+        let query = r#"
+            INSERT INTO person_registered_for_event (person_id, event_id)
+            SELECT person_id, event_id FROM event_waitlist
+            WHERE event_id = ?1
+            ORDER BY id
+            LIMIT coalesce(?2, -1)"#;
+        self.conn
+            .execute(query, (event_id, free))
+            .chain_err(|| "Unable to promote waitlisted persons")?;
+        let query = r#"
+            DELETE FROM event_waitlist
+            WHERE event_id = ?1
+              AND person_id IN (SELECT person_id
+                                FROM person_registered_for_event
+                                WHERE event_id = ?1)"#;
+        self.conn
+            .execute(query, [event_id])
+            .map(|_| ())
+            .chain_err(|| "Unable to promote waitlisted persons")
+    }
+
+    /// Tworzy osobę z rolą `role` we wszystkich wydarzeniach.
+    fn insert_person(&self, login: &str, password: &str, role: Role) -> Result<()> {
+        let query = r#"
+            INSERT INTO persons (login, password)
+            VALUES (?1, ?2)"#;
+        self.conn
+            .execute(query, (login, hash_password(password)?))
+            .chain_err(|| "Unable to insert person")?;
+        self.insert_role(self.conn.last_insert_rowid(), role, None)
+    }
+
+    fn insert_role(&self, person_id: i64, role: Role, event_id: Option<i64>) -> Result<()> {
+        let query = r#"
+            INSERT INTO person_roles (person_id, role, event_id)
+            VALUES (?1, ?2, ?3)"#;
+        self.conn
+            .execute(query, (person_id, role.as_str(), event_id))
+            .map(|_| ())
+            .chain_err(|| "Unable to grant the role")
+    }
+
+    /// Jak `database::event_at`.
+    fn event_at(&self, eventname: &str, at: DateTime) -> Result<i64> {
+        let query = r#"
+            SELECT id FROM events
+            WHERE eventname = ?1
+              AND start_timestamp <= ?2
+              AND end_timestamp >= ?2
+            LIMIT 1"#;
+        self.query(query, (eventname, Timestamp(at)), |row| row.get("id"))
+            .chain_err(|| "Unable to load event")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                ErrorKind::NotFound(format!("event with eventname=`{}` not found", eventname))
+                    .into()
+            })
+    }
+
+    /// Jak `database::schedule_talk`.
+    #[allow(clippy::too_many_arguments)]
+    fn schedule_talk(&self,
+                     person_id: i64,
+                     speakers: Vec<Speaker>,
+                     talk: String,
+                     title: String,
+                     start_timestamp: DateTime,
+                     end_timestamp: Option<DateTime>,
+                     room: String,
+                     initial_evaluation: Option<i16>,
+                     eventname: String)
+                     -> Result<()> {
+        let (primary, others) = speakers.split_first()
+            .ok_or_else(|| ErrorKind::Validation("a talk needs a speaker".into()))?;
+        let speaker_id = self.authorize_person(&primary.login, None)?;
+        let event_id = if !eventname.is_empty() {
+            Some(self.event_at(&eventname, start_timestamp)?)
+        } else {
+            None
+        };
+        self.authorize_organizer(person_id, Permission::ScheduleTalks, event_id)?;
+        // the upsert below moves an existing talk out of its event as well
+        if let Some(current) = self.talk_event(&talk)? {
+            if Some(current) != event_id {
+                self.authorize_organizer(person_id, Permission::ScheduleTalks, Some(current))?;
+            }
+        }
+
+        let room = self.room_named(&room)?
+            .ok_or_else(|| ErrorKind::NotFound(format!("room with room=`{}` not found", room)))?;
+
+        // keep the duration of a proposal unless told otherwise
+        let end_timestamp = match end_timestamp {
+            Some(end_timestamp) => end_timestamp,
+            None => {
+                let query = r#"
+                    SELECT start_timestamp, end_timestamp FROM talks
+                    WHERE talk = ?1"#;
+                let duration = self.query(query, [&talk], |row| {
+                        Ok(timestamp(row, "end_timestamp")?
+                               .signed_duration_since(timestamp(row, "start_timestamp")?))
+                    })
+                    .chain_err(|| "Unable to compute the end of a talk")?
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| Duration::minutes(i64::from(DEFAULT_TALK_MINUTES)));
+                start_timestamp + duration
+            }
+        };
+
+        // the trigger on talks only says that there is a collision
+        let query = r#"
+            SELECT talk FROM talks
+            WHERE status = ?1
+              AND room = ?2
+              AND talk <> ?3
+              AND start_timestamp < ?5
+              AND ?4 < end_timestamp
+            LIMIT 1"#;
+        let colliding: Option<String> = self.query(query,
+                                                   (TalkStatus::Accepted,
+                                                    &room,
+                                                    &talk,
+                                                    Timestamp(start_timestamp),
+                                                    Timestamp(end_timestamp)),
+                                                   |row| row.get("talk"))
+            .chain_err(|| "Unable to check the room")?
+            .into_iter()
+            .next();
+        if let Some(colliding) = colliding {
+            bail!(ErrorKind::Conflict(format!("room `{}` is already taken by talk `{}`",
+                                              room,
+                                              colliding)))
+        }
+
+        // upsert a talk
+        let query = r#"
+            INSERT INTO talks (talk, speaker_id, status, title, start_timestamp, end_timestamp,
+                               room, event_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT (talk) DO UPDATE SET
+              speaker_id = excluded.speaker_id,
+              status = excluded.status,
+              title = excluded.title,
+              start_timestamp = excluded.start_timestamp,
+              end_timestamp = excluded.end_timestamp,
+              room = excluded.room,
+              event_id = excluded.event_id,
+              modified_at = strftime('%Y-%m-%d %H:%M:%f', 'now', 'localtime')
+            RETURNING id"#;
+        let talk_id: i64 = self.query(query,
+                                      (&talk,
+                                       speaker_id,
+                                       TalkStatus::Accepted,
+                                       &title,
+                                       Timestamp(start_timestamp),
+                                       Timestamp(end_timestamp),
+                                       &room,
+                                       event_id),
+                                      |row| row.get("id"))
+            .chain_err(|| "Unable to upsert a talk")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+
+        self.set_speakers(talk_id, speaker_id, others)?;
+
+        // initial evaluation
+        if let Some(initial_evaluation) = initial_evaluation {
+            let query = r#"
+                INSERT INTO person_rated_talk (person_id, talk_id, rating)
+                VALUES (?1, ?2, ?3)"#;
+            self.conn
+                .execute(query, (person_id, talk_id, initial_evaluation))
+                .chain_err(|| "Unable to evaluate the talk")?;
+            self.record_rating(person_id, talk_id, Some(initial_evaluation))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn open(config: &ConnectionConfig) -> Result<SqliteStorage> {
+        let path = config.sqlite_path()?
+            .ok_or_else(|| ErrorKind::Validation("SQLite needs a database file".into()))?;
+        SqliteStorage::open_path(&path)
+    }
+
+    fn setup(&self) -> Result<()> {
+        migrate::up(self)
+            .map(|_| ())
+            .chain_err(|| "Unable to setup a database")
+    }
+
+    fn check_schema(&self) -> Result<()> {
+        migrate::check(self)
+    }
+
+    fn atomically<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Result<T>
+    {
+        let tx = self.conn.unchecked_transaction()?;
+        let result = f()?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    fn create_organizer_account(&self, newlogin: String, newpassword: String) -> Result<()> {
+        self.insert_person(&newlogin, &newpassword, Role::Admin)
+    }
+
+    fn use_organizer_secret(&self, secret: &str) -> Result<bool> {
+        let query = r#"
+            INSERT OR IGNORE INTO used_organizer_secrets (secret_digest, used_at)
+            VALUES (?1, ?2)"#;
+        self.conn
+            .execute(query, (secret_digest(secret), now()))
+            .map(|inserted| inserted == 1)
+            .chain_err(|| "Unable to mark the organizer secret as used")
+    }
+
+    fn record_organizer_attempt(&self, newlogin: &str, outcome: OrganizerOutcome) -> Result<()> {
+        let query = r#"
+            INSERT INTO organizer_attempts (newlogin, outcome, attempted_at)
+            VALUES (?1, ?2, ?3)"#;
+        self.conn
+            .execute(query, (newlogin, outcome.as_str(), now()))
+            .map(|_| ())
+            .chain_err(|| "Unable to record the organizer attempt")
+    }
+
+    fn organizer_attempts(&self) -> Result<Vec<OrganizerAttempt>> {
+        let query = r#"
+            SELECT newlogin, outcome, attempted_at
+            FROM organizer_attempts
+            ORDER BY id"#;
+        self.query(query, [], |row| {
+                Ok(OrganizerAttempt {
+                       newlogin: row.get("newlogin")?,
+                       outcome: organizer_outcome(row)?,
+                       attempted_at: timestamp(row, "attempted_at")?,
+                   })
+            })
+            .chain_err(|| "Unable to load organizer attempts")
+    }
+
+    fn create_event(&self,
+                    credentials: Credentials,
+                    eventname: String,
+                    start_timestamp: DateTime,
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
+                    -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ManageEvents, None)?;
+
+        let query = r#"
+            INSERT INTO events (eventname, start_timestamp, end_timestamp, capacity)
+            VALUES (?1, ?2, ?3, ?4)"#;
+        self.conn
+            .execute(query,
+                     (&eventname,
+                      Timestamp(start_timestamp),
+                      Timestamp(end_timestamp),
+                      capacity))
+            .chain_err(|| "Unable to insert event")?;
+
+        self.insert_organizer(self.conn.last_insert_rowid(), person_id, true)
+    }
+
+    fn set_event_capacity(&self,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        let event_id = self.event_id(&eventname)?;
+        self.authorize(person_id, Permission::ManageEvents, Some(event_id))?;
+
+        let query = r#"
+            UPDATE events
+            SET capacity = ?1
+            WHERE id = ?2"#;
+        self.conn
+            .execute(query, (capacity, event_id))
+            .chain_err(|| "Unable to change the capacity")?;
+
+        self.promote_waitlisted(event_id)
+    }
+
+    fn create_room(&self,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ManageEvents, None)?;
+        if let Some(existing) = self.room_named(&room)? {
+            bail!(ErrorKind::Conflict(format!("room `{}` already exists", existing)))
+        }
+
+        let query = r#"
+            INSERT INTO venues (venue)
+            VALUES (?1)
+            ON CONFLICT DO NOTHING"#;
+        self.conn
+            .execute(query, [&venue])
+            .chain_err(|| "Unable to insert venue")?;
+
+        let query = r#"
+            INSERT INTO rooms (room, venue_id, capacity, floor)
+            SELECT ?1, id, ?3, ?4
+            FROM venues
+            WHERE venue = ?2"#;
+        self.conn
+            .execute(query, (&room, &venue, capacity, floor))
+            .map(|_| ())
+            .chain_err(|| "Unable to insert room")
+    }
+
+    fn list_rooms(&self, credentials: Credentials, venue: Option<String>) -> Result<Vec<Room>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ScheduleTalks, None)?;
+        if let Some(ref venue) = venue {
+            let query = r#"
+                SELECT 1 FROM venues
+                WHERE venue = ?1"#;
+            if self.query(query, [venue], |_| Ok(()))
+                   .chain_err(|| "Unable to load venue")?
+                   .is_empty() {
+                bail!(ErrorKind::NotFound(format!("venue with venue=`{}` not found", venue)))
+            }
+        }
+
+        let query = r#"
+            SELECT room, venue, capacity, floor
+            FROM rooms
+            LEFT JOIN venues ON venues.id = rooms.venue_id
+            WHERE ?1 IS NULL
+               OR venue = ?1
+            ORDER BY room"#;
+        self.query(query, [&venue], |row| {
+                Ok(Room {
+                       room: row.get("room")?,
+                       venue: row.get("venue")?,
+                       capacity: row.get("capacity")?,
+                       floor: row.get("floor")?,
+                   })
+            })
+            .chain_err(|| "Unable to load rooms")
+    }
+
+    fn create_user(&self,
+                   credentials: Credentials,
+                   newlogin: String,
+                   newpassword: String)
+                   -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ManageAccounts, None)?;
+        self.insert_person(&newlogin, &newpassword, Role::Attendee)
+    }
+
+    fn register_or_accept_talk(&self,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
+                               talk: String,
+                               title: String,
+                               start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
+                               room: String,
+                               initial_evaluation: i16,
+                               eventname: String)
+                               -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.schedule_talk(person_id,
+                           speakers,
+                           talk,
+                           title,
+                           start_timestamp,
+                           end_timestamp,
+                           room,
+                           Some(initial_evaluation),
+                           eventname)
+    }
+
+    fn register_user_for_event(&self, credentials: Credentials, eventname: String) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.event_named(&eventname)?)?;
+        let event_id = self.event_id(&eventname)?;
+
+        let full = self.free_seats(event_id)?.is_some_and(|free| free <= 0);
+        if !full {
+            let query = r#"
+                INSERT INTO person_registered_for_event (person_id, event_id)
+                VALUES (?1, ?2)"#;
+            return self.conn
+                       .execute(query, (person_id, event_id))
+                       .map(|_| ())
+                       .chain_err(|| "Person can't be registered for event");
+        }
+
+        let query = r#"
+            SELECT 1 FROM person_registered_for_event
+            WHERE person_id = ?1
+              AND event_id = ?2"#;
+        if !self.query(query, (person_id, event_id), |_| Ok(()))
+                .chain_err(|| "Unable to load registrations")?
+                .is_empty() {
+            bail!(ErrorKind::Conflict(format!("already registered for event `{}`", eventname)))
+        }
+
+        let query = r#"
+            INSERT INTO event_waitlist (person_id, event_id)
+            VALUES (?1, ?2)"#;
+        self.conn
+            .execute(query, (person_id, event_id))
+            .map(|_| ())
+            .chain_err(|| "Person can't be put on the waitlist")
+    }
+
+    fn unregister_user_from_event(&self,
+                                  credentials: Credentials,
+                                  eventname: String)
+                                  -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.event_named(&eventname)?)?;
+        let event_id = self.event_id(&eventname)?;
+
+        let query = r#"
+            DELETE FROM person_registered_for_event
+            WHERE person_id = ?1
+              AND event_id = ?2"#;
+        let deletes = self.conn
+            .execute(query, (person_id, event_id))
+            .chain_err(|| "Unable to unregister person from event")?;
+        if deletes == 1 {
+            return self.promote_waitlisted(event_id);
+        }
+
+        let query = r#"
+            DELETE FROM event_waitlist
+            WHERE person_id = ?1
+              AND event_id = ?2"#;
+        let deletes = self.conn
+            .execute(query, (person_id, event_id))
+            .chain_err(|| "Unable to remove person from the waitlist")?;
+        if deletes != 1 {
+            bail!(ErrorKind::NotFound("There was no registration to cancel".into()))
+        }
+
+        Ok(())
+    }
+
+    fn attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.talk_event(&talk)?)?;
+        let talk_id = self.accepted_talk_id(&talk)?
+            .ok_or_else(|| ErrorKind::NotFound(format!("talk with talk=`{}` not found", talk)))?;
+
+        let query = r#"
+            INSERT INTO person_attended_for_talk (person_id, talk_id)
+            VALUES (?1, ?2)"#;
+        self.conn
+            .execute(query, (person_id, talk_id))
+            .map(|_| ())
+            .chain_err(|| "Unable to mark attendance of User for the talk")
+    }
+
+    fn cancel_attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.talk_event(&talk)?)?;
+
+        let query = r#"
+            DELETE FROM person_attended_for_talk
+            WHERE person_id = ?1
+              AND talk_id = (SELECT id FROM talks
+                             WHERE talk = ?2
+                               AND status = ?3)"#;
+        let deletes = self.conn
+            .execute(query, (person_id, &talk, TalkStatus::Accepted))
+            .chain_err(|| "Unable to cancel attendance")?;
+        if deletes != 1 {
+            bail!(ErrorKind::NotFound("There was no attendance to cancel".into()))
+        }
+
+        Ok(())
+    }
+
+    fn evaluation(&self,
+                  credentials: Credentials,
+                  talk: String,
+                  rating: i16,
+                  comment: Option<String>)
+                  -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.talk_event(&talk)?)?;
+        let talk_id = self.accepted_talk_id(&talk)?
+            .ok_or_else(|| ErrorKind::NotFound("talk doesn't exist or is not accepted".into()))?;
+
+        // historia odnotowuje jedynie zmiany oceny, nie samej recenzji
+        let query = r#"
+            SELECT rating FROM person_rated_talk
+            WHERE person_id = ?1
+              AND talk_id = ?2"#;
+        let previous: Option<i16> = self.query(query, (person_id, talk_id), |row| row.get("rating"))
+            .chain_err(|| "Unable to load the evaluation")?
+            .into_iter()
+            .next();
+
+        // ponowna ocena zastępuje poprzednią wraz z recenzją
+        let query = r#"
+            INSERT INTO person_rated_talk (person_id, talk_id, rating, comment)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (person_id, talk_id) DO UPDATE
+            SET rating = excluded.rating,
+                comment = excluded.comment"#;
+        self.conn
+            .execute(query, (person_id, talk_id, rating, &comment))
+            .chain_err(|| "Unable to evaluate the talk")?;
+        if previous == Some(rating) {
+            return Ok(());
+        }
+
+        self.record_rating(person_id, talk_id, Some(rating))
+    }
+
+    fn retract_evaluation(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.talk_event(&talk)?)?;
+
+        let query = r#"
+            DELETE FROM person_rated_talk
+            WHERE person_id = ?1
+              AND talk_id = (SELECT id FROM talks
+                             WHERE talk = ?2)
+            RETURNING talk_id"#;
+        let talk_id: i64 = self.query(query, (person_id, &talk), |row| row.get("talk_id"))
+            .chain_err(|| "Unable to retract the evaluation")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorKind::NotFound("There was no evaluation to retract".into()))?;
+
+        self.record_rating(person_id, talk_id, None)
+    }
+
+    fn reject_spontaneous_talk(&self, credentials: Credentials, talk: String) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize_organizer(person_id,
+                                 Permission::ReviewProposals,
+                                 self.talk_event(&talk)?)?;
+
+        let query = r#"
+            UPDATE talks
+            SET status = ?1
+            WHERE talk = ?2
+              AND status = ?3"#;
+        let updates = self.conn
+            .execute(query, (TalkStatus::Rejected, &talk, TalkStatus::Proposed))
+            .chain_err(|| "Unable to reject a proposal")?;
+        if updates != 1 {
+            bail!(ErrorKind::NotFound("There was no proposal to reject".into()))
+        }
+
+        Ok(())
+    }
+
+    fn accept_talk(&self,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+
+        let query = r#"
+            SELECT title, talks.start_timestamp, talks.end_timestamp, room,
+                   coalesce(eventname, '') AS eventname, speakers
+            FROM talks
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+              LEFT JOIN events ON events.id = talks.event_id
+            WHERE talk = ?1
+              AND status = ?2"#;
+        let (title, start_timestamp, end_timestamp, proposed_room, eventname, speakers) =
+            self.query(query, (&talk, TalkStatus::Proposed), |row| {
+                    Ok((row.get::<_, String>("title")?,
+                        timestamp(row, "start_timestamp")?,
+                        timestamp(row, "end_timestamp")?,
+                        row.get::<_, Option<String>>("room")?,
+                        row.get::<_, String>("eventname")?,
+                        speakers(row)?))
+                })
+                .chain_err(|| "Unable to load the proposal")?
+                .into_iter()
+                .next()
+                .ok_or_else(|| ErrorKind::NotFound("There was no proposal to accept".into()))?;
+        let room = room.or(proposed_room)
+            .ok_or_else(|| ErrorKind::Validation("the proposal has no room, give one".into()))?;
+
+        self.schedule_talk(person_id,
+                           speakers,
+                           talk,
+                           title,
+                           start_timestamp,
+                           Some(end_timestamp),
+                           room,
+                           initial_evaluation,
+                           eventname)
+    }
+
+    fn propose_spontaneous_talk(&self,
+                                credentials: Credentials,
+                                talk: String,
+                                title: String,
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
+                                -> Result<()> {
+        let speaker_id = self.authenticate(&credentials)?;
+        let event_id = match eventname {
+            Some(eventname) => Some(self.event_at(&eventname, start_timestamp)?),
+            None => None,
+        };
+        self.authorize(speaker_id, Permission::Participate, event_id)?;
+        let room = match room {
+            Some(room) => {
+                Some(self.room_named(&room)?.ok_or_else(|| {
+                    ErrorKind::NotFound(format!("room with room=`{}` not found", room))
+                })?)
+            }
+            None => None,
+        };
+        let end_timestamp = end_timestamp.unwrap_or_else(|| {
+            start_timestamp + Duration::minutes(i64::from(DEFAULT_TALK_MINUTES))
+        });
+
+        let query = r#"
+            INSERT INTO talks (speaker_id, talk, status, title, start_timestamp, end_timestamp,
+                               room, event_id)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)"#;
+        self.conn
+            .execute(query,
+                     (speaker_id,
+                      &talk,
+                      TalkStatus::Proposed,
+                      &title,
+                      Timestamp(start_timestamp),
+                      Timestamp(end_timestamp),
+                      room,
+                      event_id))
+            .chain_err(|| "Unable to insert a proposal")?;
+
+        self.set_speakers(self.conn.last_insert_rowid(), speaker_id, &speakers)
+    }
+
+    fn make_friends(&self, credentials: Credentials, login2: String) -> Result<()> {
+        let person1_id = self.authenticate(&credentials)?;
+        self.authorize(person1_id, Permission::Participate, None)?;
+        let person2_id = self.authorize_person(&login2, None)?;
+
+        let query = r#"
+            INSERT INTO person_knows_person (person1_id, person2_id)
+            VALUES (?1, ?2)"#;
+        self.conn
+            .execute(query, (person1_id, person2_id))
+            .map(|_| ())
+            .chain_err(|| "These users are already friends?")
+    }
+
+    fn unfriend(&self, credentials: Credentials, login2: String) -> Result<()> {
+        let person1_id = self.authenticate(&credentials)?;
+        self.authorize(person1_id, Permission::Participate, None)?;
+        let person2_id = self.authorize_person(&login2, None)?;
+
+        let query = r#"
+            DELETE FROM person_knows_person
+            WHERE (person1_id, person2_id) IN (VALUES (?1, ?2), (?2, ?1))"#;
+        let deletes = self.conn
+            .execute(query, (person1_id, person2_id))
+            .chain_err(|| "Unable to unfriend")?;
+        if deletes == 0 {
+            bail!(ErrorKind::NotFound("These users are not friends".into()))
+        }
+
+        Ok(())
+    }
+
+    fn user_plan(&self, login: String, page: Page) -> Result<Paged<UserPlan>> {
+        let query = r#"
+            WITH cte(talk_id, person_id, speakerlogin, talk, start_timestamp, end_timestamp,
+                     title, room) AS (
+              SELECT talks.id, person_id, login, talk, start_timestamp, end_timestamp, title,
+                     room
+              FROM person_registered_for_event prfe
+                JOIN talks USING(event_id)
+                JOIN persons primaries ON speaker_id = primaries.id
+              WHERE status = ?1
+                AND start_timestamp >= ?3
+            )
+            SELECT talk_id, speakerlogin, talk, start_timestamp, end_timestamp, title, room,
+                   speakers
+            FROM persons
+              JOIN cte ON cte.person_id = persons.id
+              JOIN talk_speaker_lists USING (talk_id)
+            WHERE persons.login = ?2"#;
+        self.query_page(&USER_PLAN,
+                        query,
+                        &[&TalkStatus::Accepted, &login, &now()],
+                        &page,
+                        |row| {
+                Ok(UserPlan {
+                       login: row.get("speakerlogin")?,
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load person's plan")
+    }
+
+    fn day_plan(&self, date: Date) -> Result<Vec<DayPlan>> {
+        let query = r#"
+            SELECT talk, start_timestamp, end_timestamp, title, talks.room, venue, capacity,
+                   floor, speakers
+            FROM talks
+            JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            LEFT JOIN rooms ON rooms.room = talks.room
+            LEFT JOIN venues ON venues.id = rooms.venue_id
+            WHERE status = ?1
+              AND date(start_timestamp) = ?2
+            ORDER BY talks.room, start_timestamp, talks.id"#;
+        let date = date.format(DATE_FORMAT).to_string();
+        self.query(query, (TalkStatus::Accepted, date), |row| {
+                Ok(DayPlan {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       venue: row.get("venue")?,
+                       capacity: row.get("capacity")?,
+                       floor: row.get("floor")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load day plan")
+    }
+
+    fn event_plan(&self, eventname: String) -> Result<Vec<DayPlan>> {
+        let event_id = self.event_id(&eventname)?;
+
+        let query = r#"
+            SELECT talk, start_timestamp, end_timestamp, title, talks.room, venue, capacity,
+                   floor, speakers
+            FROM talks
+            JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            LEFT JOIN rooms ON rooms.room = talks.room
+            LEFT JOIN venues ON venues.id = rooms.venue_id
+            WHERE status = ?1
+              AND event_id = ?2
+            ORDER BY start_timestamp, talks.id"#;
+        self.query(query, (TalkStatus::Accepted, event_id), |row| {
+                Ok(DayPlan {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       venue: row.get("venue")?,
+                       capacity: row.get("capacity")?,
+                       floor: row.get("floor")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load event plan")
+    }
+
+    fn best_talks(&self,
+                  start_timestamp: DateTime,
+                  end_timestamp: DateTime,
+                  page: Page,
+                  all: bool)
+                  -> Result<Paged<BestTalk>> {
+        // oceny organizatorów i osób planujących referaty wydarzenia liczą się zawsze
+        let all = if all {
+            String::new()
+        } else {
+            format!(r#"WHERE person_id IN (SELECT roles.person_id FROM person_roles roles
+                                           WHERE roles.role IN ({})
+                                             AND (roles.event_id IS NULL
+                                                  OR roles.event_id = talks.event_id))
+                         OR person_id IN (SELECT organizers.person_id
+                                          FROM event_organizers organizers
+                                          WHERE organizers.event_id = talks.event_id)
+                         OR (person_id, talk_id) IN (SELECT * FROM person_attended_for_talk)"#,
+                    Permission::ScheduleTalks.sql_roles())
+        };
+
+        let query = format!(r#"
+            WITH cte(talk_id, average_rate) AS (
+              SELECT talk_id, avg(rating)
+              FROM person_rated_talk
+                JOIN talks ON talks.id = talk_id
+                JOIN events ON events.id = event_id
+              {}
+              GROUP BY talk_id
+            )
+            SELECT talk_id, average_rate, talk, start_timestamp, end_timestamp, title, room,
+                   speakers
+            FROM talks
+              JOIN cte ON cte.talk_id = talks.id
+              JOIN talk_speaker_lists USING (talk_id)
+            WHERE status = ?1
+              AND start_timestamp >= ?2
+              AND start_timestamp <= ?3"#,
+                            all);
+        self.query_page(&BEST_TALKS,
+                        &query,
+                        &[&TalkStatus::Accepted,
+                          &Timestamp(start_timestamp),
+                          &Timestamp(end_timestamp)],
+                        &page,
+                        |row| {
+                Ok(BestTalk {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load best talks")
+    }
+
+    fn most_popular_talks(&self,
+                          start_timestamp: DateTime,
+                          end_timestamp: DateTime,
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>> {
+        let query = r#"
+            WITH cte(talk_id, arrivals) AS (
+              SELECT talk_id, COUNT(person_id)
+              FROM person_attended_for_talk
+              GROUP BY talk_id
+            )
+            SELECT talk_id, arrivals, talk, start_timestamp, end_timestamp, title, room,
+                   speakers
+            FROM talks
+              JOIN cte ON cte.talk_id = talks.id
+              JOIN talk_speaker_lists USING (talk_id)
+            WHERE status = ?1
+              AND start_timestamp >= ?2
+              AND start_timestamp <= ?3"#;
+        self.query_page(&MOST_POPULAR_TALKS,
+                        query,
+                        &[&TalkStatus::Accepted,
+                          &Timestamp(start_timestamp),
+                          &Timestamp(end_timestamp)],
+                        &page,
+                        |row| {
+                Ok(MostPopularTalk {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load most popular talks")
+    }
+
+    fn attended_talks(&self, credentials: Credentials) -> Result<Vec<AttendedTalk>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, None)?;
+
+        let query = r#"
+            SELECT talk, start_timestamp, end_timestamp, title, room, speakers
+            FROM person_attended_for_talk paft
+              JOIN talks ON paft.talk_id = talks.id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE paft.person_id = ?1"#;
+        self.query(query, [person_id], |row| {
+                Ok(AttendedTalk {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load person's talks")
+    }
+
+    fn abandoned_talks(&self,
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>> {
+        let person_id = self.authenticate(&credentials)?;
+
+        let query = format!(r#"
+            WITH person_registered_for_talk(person_id, talk_id) AS (
+              SELECT person_id, talks.id
+              FROM person_registered_for_event
+                JOIN talks USING(event_id)
+              WHERE status = ?2
+            ),
+            cte(talk_id, absent) AS (
+              SELECT talk_id, count(person_id)
+              FROM person_registered_for_talk
+              WHERE (person_id, talk_id) NOT IN (SELECT * FROM person_attended_for_talk)
+              GROUP BY talk_id
+            )
+            SELECT talk_id, talk, start_timestamp, end_timestamp, title, room, absent, speakers
+            FROM talks
+              JOIN cte ON cte.talk_id = talks.id
+              JOIN talk_speaker_lists USING (talk_id)
+            WHERE talks.event_id IN ({})"#,
+                            organized_events(Permission::ViewFeedback));
+        self.query_page(&ABANDONED_TALKS,
+                        &query,
+                        &[&person_id, &TalkStatus::Accepted],
+                        &page,
+                        |row| {
+                Ok(AbandonedTalk {
+                       talk: row.get("talk")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       number: row.get("absent")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load abandoned talks")
+    }
+
+    fn recently_added_talks(&self, page: Page) -> Result<Paged<RecentlyAddedTalk>> {
+        let query = r#"
+            SELECT talks.id AS talk_id, modified_at, talk, login AS speakerlogin,
+                   start_timestamp, end_timestamp, title, room, speakers
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE status = ?1"#;
+        self.query_page(&RECENTLY_ADDED_TALKS, query, &[&TalkStatus::Accepted], &page, |row| {
+                Ok(RecentlyAddedTalk {
+                       talk: row.get("talk")?,
+                       speakerlogin: row.get("speakerlogin")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load recently added talks")
+    }
+
+    /// SQLite bez rozszerzenia FTS5 nie ma wyszukiwania pełnotekstowego, więc trafność
+    /// i dopasowanie zapytania w składni `websearch_to_tsquery` liczy `search_rank`.
+    fn search_talks(&self,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>> {
+        if status != TalkStatusName::Accepted {
+            match credentials {
+                Some(credentials) => {
+                    let person_id = self.authenticate(&credentials)?;
+                    self.authorize(person_id, Permission::ReviewProposals, None)?;
+                }
+                None => {
+                    bail!(ErrorKind::Unauthorized("only organizers can search talks that are \
+                                                   not accepted"
+                                                          .into()))
+                }
+            }
+        }
+
+        let sql = r#"
+            SELECT talks.id AS talk_id, talk, login AS speakerlogin, talks.start_timestamp,
+                   talks.end_timestamp, title, room, eventname, speakers, logins
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+              LEFT JOIN events ON events.id = talks.event_id
+            WHERE status = ?1
+              AND (?2 IS NULL OR talks.start_timestamp >= ?2)
+              AND (?3 IS NULL OR talks.start_timestamp <= ?3)
+            ORDER BY talks.start_timestamp, talks.id"#;
+        let candidates = self.query(sql,
+                                    (TalkStatus::from(status),
+                                     start_timestamp.map(Timestamp),
+                                     end_timestamp.map(Timestamp)),
+                                    |row| {
+                let eventname: Option<String> = row.get("eventname")?;
+                let logins: String = row.get("logins")?;
+                Ok((row.get::<_, i64>("talk_id")?,
+                    FoundTalk {
+                        talk: row.get("talk")?,
+                        speakerlogin: row.get("speakerlogin")?,
+                        start_timestamp: timestamp(row, "start_timestamp")?,
+                        end_timestamp: timestamp(row, "end_timestamp")?,
+                        title: row.get("title")?,
+                        room: row.get("room")?,
+                        score: 0.0,
+                        speakers: speakers(row)?,
+                    },
+                    eventname.unwrap_or_default(),
+                    logins))
+            })
+            .chain_err(|| "Unable to search talks")?;
+
+        let mut talks: Vec<_> = candidates.into_iter()
+            .filter_map(|(talk_id, talk, eventname, logins)| {
+                let fields = [&talk.title[..],
+                              &logins[..],
+                              &eventname[..],
+                              talk.room.as_ref().map_or("", |room| &room[..])];
+                search_rank(&query, fields).map(|score| {
+                    let keys = vec![Key::Float(score),
+                                    Key::Timestamp(talk.start_timestamp),
+                                    Key::Int(talk_id)];
+                    (FoundTalk { score, ..talk }, keys)
+                })
+            })
+            .collect();
+        // a stable sort keeps the chronological order of equally relevant talks
+        talks.sort_by(|a, b| b.0.score.partial_cmp(&a.0.score).unwrap());
+        SEARCH_TALKS.page(talks, &page)
+    }
+
+    fn rejected_talks(&self, credentials: Credentials) -> Result<Vec<RejectedTalk>> {
+        let person_id = self.authenticate(&credentials)?;
+        let reviewer = self.has_permission(person_id, Permission::ReviewProposals, None)?;
+
+        let rejected_talk = |row: &Row| {
+            Ok(RejectedTalk {
+                   talk: row.get("talk")?,
+                   speakerlogin: row.get("speakerlogin")?,
+                   start_timestamp: timestamp(row, "start_timestamp")?,
+                   end_timestamp: timestamp(row, "end_timestamp")?,
+                   title: row.get("title")?,
+                   speakers: speakers(row)?,
+               })
+        };
+        let talks = if reviewer {
+            let query = r#"
+                SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title,
+                       speakers
+                FROM talks
+                  JOIN persons ON persons.id = talks.speaker_id
+                  JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+                WHERE status = ?1"#;
+            self.query(query, [TalkStatus::Rejected], rejected_talk)
+        } else {
+            let query = r#"
+                SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title,
+                       speakers
+                FROM talks
+                  JOIN persons ON persons.id = talks.speaker_id
+                  JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+                WHERE status = ?1
+                  AND talks.id IN (SELECT talk_id FROM talk_speakers WHERE person_id = ?2)"#;
+            self.query(query, (TalkStatus::Rejected, person_id), rejected_talk)
+        };
+        talks.chain_err(|| "Unable to load rejected talks")
+    }
+
+    fn proposals(&self, credentials: Credentials) -> Result<Vec<Proposal>> {
+        let person_id = self.authenticate(&credentials)?;
+        let reviewer = self.has_permission(person_id, Permission::ReviewProposals, None)?;
+
+        let query = format!(r#"
+            SELECT talk, login AS speakerlogin, start_timestamp, end_timestamp, title, speakers
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE status = ?2
+              AND (talks.event_id IN ({}) OR talks.event_id IS NULL AND ?3)"#,
+                            organized_events(Permission::ReviewProposals));
+        self.query(&query, (person_id, TalkStatus::Proposed, reviewer), |row| {
+                Ok(Proposal {
+                       talk: row.get("talk")?,
+                       speakerlogin: row.get("speakerlogin")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load proposals")
+    }
+
+    fn friends_talks(&self,
+                     credentials: Credentials,
+                     start_timestamp: DateTime,
+                     end_timestamp: DateTime,
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, None)?;
+
+        let query = r#"
+            WITH friends(id) AS (
+                SELECT person2_id
+                FROM person_knows_person
+                JOIN (
+                    SELECT person2_id AS person1_id, person1_id AS person2_id
+                    FROM person_knows_person
+                ) AS reversed USING(person1_id, person2_id)
+                WHERE person1_id = ?1
+            )
+            SELECT talks.id AS talk_id, talk, login AS speakerlogin, start_timestamp,
+                   end_timestamp, title, room, speakers
+            FROM talks
+              JOIN persons ON persons.id = talks.speaker_id
+              JOIN talk_speaker_lists ON talk_speaker_lists.talk_id = talks.id
+            WHERE talks.id IN (
+                    SELECT talk_id
+                    FROM talk_speakers
+                      JOIN friends ON friends.id = person_id)
+              AND status = ?2
+              AND start_timestamp >= ?3
+              AND start_timestamp <= ?4"#;
+        self.query_page(&FRIENDS_TALKS,
+                        query,
+                        &[&person_id,
+                          &TalkStatus::Accepted,
+                          &Timestamp(start_timestamp),
+                          &Timestamp(end_timestamp)],
+                        &page,
+                        |row| {
+                Ok(FriendsTalk {
+                       talk: row.get("talk")?,
+                       speakerlogin: row.get("speakerlogin")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load friends talks")
+    }
+
+    fn friends_events(&self,
+                      credentials: Credentials,
+                      eventname: String)
+                      -> Result<Vec<FriendsEvent>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, self.event_named(&eventname)?)?;
+        let login = self.person_login(person_id)?;
+
+        let query = r#"
+            WITH friends(id) AS (
+                SELECT person2_id
+                FROM person_knows_person
+                JOIN (
+                    SELECT person2_id AS person1_id, person1_id AS person2_id
+                    FROM person_knows_person
+                ) AS reversed USING(person1_id, person2_id)
+                WHERE person1_id = ?1
+            )
+            SELECT login AS friendlogin
+            FROM person_registered_for_event
+              JOIN persons ON persons.id = person_id
+              JOIN friends ON friends.id = person_id
+              JOIN events ON events.id = event_id"#;
+        self.query(query, [person_id], |row| {
+                Ok(FriendsEvent {
+                       login: login.clone(),
+                       eventname: eventname.clone(),
+                       friendlogin: row.get("friendlogin")?,
+                   })
+            })
+            .chain_err(|| "Unable to load friends events")
+    }
+
+    fn recommended_talks(&self,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, None)?;
+
+        let query = r#"
+            WITH friends(id) AS (
+                SELECT person2_id
+                FROM person_knows_person
+                JOIN (
+                    SELECT person2_id AS person1_id, person1_id AS person2_id
+                    FROM person_knows_person
+                ) AS reversed USING(person1_id, person2_id)
+                WHERE person1_id = ?1
+            ),
+            attended(talk_id, speaker_id, event_id) AS (
+              SELECT talks.id, speaker_id, event_id
+              FROM person_attended_for_talk paft
+                JOIN talks ON paft.talk_id = talks.id
+              WHERE paft.person_id = ?1
+            ),
+            cte(talk_id, score) AS (
+              SELECT talks.id, CAST(round(
+                  10 * coalesce((
+                    SELECT avg(rating)
+                    FROM person_rated_talk
+                    WHERE talk_id = talks.id), 0)
+                + 5 * coalesce((
+                    SELECT avg(rating)
+                    FROM person_rated_talk
+                      JOIN talks past ON past.id = talk_id
+                    WHERE past.speaker_id = talks.speaker_id
+                      AND past.start_timestamp < talks.start_timestamp), 0)
+                + 5 * (
+                    SELECT count(*)
+                    FROM friends
+                    WHERE friends.id IN (
+                        SELECT person_id
+                        FROM person_attended_for_talk
+                        WHERE talk_id = talks.id)
+                      OR friends.id IN (
+                        SELECT person_id
+                        FROM person_registered_for_event
+                        WHERE event_id = talks.event_id))
+                + 5 * (
+                    SELECT count(*)
+                    FROM attended
+                    WHERE attended.speaker_id = talks.speaker_id)
+                + 2 * (
+                    SELECT count(*)
+                    FROM attended
+                    WHERE attended.event_id = talks.event_id)
+              ) AS integer)
+              FROM talks
+              WHERE status = ?2
+                AND start_timestamp >= ?3
+                AND start_timestamp <= ?4
+                AND talks.id NOT IN (SELECT talk_id FROM talk_speakers WHERE person_id = ?1)
+                AND talks.id NOT IN (SELECT talk_id FROM attended)
+            )
+            SELECT talk_id, talk, login AS speakerlogin, start_timestamp, end_timestamp, title,
+                   room, score, speakers
+            FROM talks
+              JOIN cte ON cte.talk_id = talks.id
+              JOIN talk_speaker_lists USING (talk_id)
+              JOIN persons ON persons.id = talks.speaker_id"#;
+        self.query_page(&RECOMMENDED_TALKS,
+                        query,
+                        &[&person_id,
+                          &TalkStatus::Accepted,
+                          &Timestamp(start_timestamp),
+                          &Timestamp(end_timestamp)],
+                        &page,
+                        |row| {
+                Ok(RecommendedTalk {
+                       talk: row.get("talk")?,
+                       speakerlogin: row.get("speakerlogin")?,
+                       start_timestamp: timestamp(row, "start_timestamp")?,
+                       end_timestamp: timestamp(row, "end_timestamp")?,
+                       title: row.get("title")?,
+                       room: row.get("room")?,
+                       score: row.get("score")?,
+                       speakers: speakers(row)?,
+                   })
+            })
+            .chain_err(|| "Unable to load recommended talks")
+    }
+
+    fn waitlist(&self, credentials: Credentials, eventname: String) -> Result<Vec<WaitlistEntry>> {
+        let person_id = self.authenticate(&credentials)?;
+        let event_id = self.event_id(&eventname)?;
+
+        // widzący całą listę widzą wszystkich
+        let everyone = self.has_permission(person_id, Permission::ViewWaitlist, Some(event_id))?;
+        let person_id = if everyone { None } else { Some(person_id) };
+        let query = r#"
+            WITH waitlist(person_id, position) AS (
+              SELECT person_id, row_number() OVER (ORDER BY id)
+              FROM event_waitlist
+              WHERE event_id = ?1
+            )
+            SELECT login, position
+            FROM waitlist
+              JOIN persons ON persons.id = person_id
+            WHERE ?2 IS NULL
+               OR person_id = ?2
+            ORDER BY position"#;
+        self.query(query, (event_id, person_id), |row| {
+                Ok(WaitlistEntry {
+                       login: row.get("login")?,
+                       eventname: eventname.clone(),
+                       position: row.get("position")?,
+                   })
+            })
+            .chain_err(|| "Unable to load the waitlist")
+    }
+
+    fn rating_history(&self, credentials: Credentials, talk: String) -> Result<Vec<RatingChange>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ViewFeedback, self.talk_event(&talk)?)?;
+
+        let talk_id = self.talk_id(&talk)?;
+
+        // średnia z ostatnich ocen każdego uczestnika do danej zmiany włącznie
+        let query = r#"
+            SELECT login, rating, changed_at,
+                   (SELECT avg(latest.rating)
+                    FROM rating_history latest
+                    WHERE latest.talk_id = change.talk_id
+                      AND latest.id = (SELECT max(h.id)
+                                       FROM rating_history h
+                                       WHERE h.person_id = latest.person_id
+                                         AND h.talk_id = latest.talk_id
+                                         AND h.id <= change.id)) AS average
+            FROM rating_history change
+              JOIN persons ON persons.id = change.person_id
+            WHERE change.talk_id = ?1
+            ORDER BY change.id"#;
+        self.query(query, [talk_id], |row| {
+                Ok(RatingChange {
+                       login: row.get("login")?,
+                       rating: row.get("rating")?,
+                       changed_at: timestamp(row, "changed_at")?,
+                       average: row.get("average")?,
+                   })
+            })
+            .chain_err(|| "Unable to load the rating history")
+    }
+
+    fn talk_reviews(&self, credentials: Credentials, talk: String) -> Result<Vec<TalkReview>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::ViewFeedback, self.talk_event(&talk)?)?;
+        let talk_id = self.talk_id(&talk)?;
+
+        let query = r#"
+            SELECT login, rating, comment,
+                   EXISTS (SELECT 1
+                           FROM person_attended_for_talk attended
+                           WHERE attended.person_id = rated.person_id
+                             AND attended.talk_id = rated.talk_id) AS attended
+            FROM person_rated_talk rated
+              JOIN persons ON persons.id = rated.person_id
+            WHERE rated.talk_id = ?1
+            ORDER BY login"#;
+        self.query(query, [talk_id], |row| {
+                Ok(TalkReview {
+                       login: row.get("login")?,
+                       rating: row.get("rating")?,
+                       comment: row.get("comment")?,
+                       attended: row.get("attended")?,
+                   })
+            })
+            .chain_err(|| "Unable to load the reviews")
+    }
+
+    fn my_feedback(&self, credentials: Credentials) -> Result<Vec<Feedback>> {
+        let person_id = self.authenticate(&credentials)?;
+        self.authorize(person_id, Permission::Participate, None)?;
+
+        // bez loginów, kolejność nie zdradza autorów
+        let query = r#"
+            SELECT talk, rating, comment
+            FROM person_rated_talk
+              JOIN talks ON talks.id = person_rated_talk.talk_id
+            WHERE talks.id IN (SELECT talk_id FROM talk_speakers WHERE person_id = ?1)
+              AND comment IS NOT NULL
+            ORDER BY talk, rating DESC, comment"#;
+        self.query(query, [person_id], |row| {
+                Ok(Feedback {
+                       talk: row.get("talk")?,
+                       rating: row.get("rating")?,
+                       comment: row.get("comment")?,
+                   })
+            })
+            .chain_err(|| "Unable to load the feedback")
+    }
+
+    fn open_session(&self, login: String, password: String) -> Result<Vec<Session>> {
+        let person_id = self.authorize_person(&login, Some(&password))?;
+
+        // forget expired sessions
+        let query = r#"
+            DELETE FROM sessions
+            WHERE expires_at <= ?1"#;
+        self.conn
+            .execute(query, [now()])
+            .chain_err(|| "Unable to remove expired sessions")?;
+
+        let expires_at = Timestamp(now().0 + Duration::hours(i64::from(SESSION_TTL_HOURS)));
+        let query = r#"
+            INSERT INTO sessions (token, person_id, expires_at)
+            VALUES (?1, ?2, ?3)
+            RETURNING token, expires_at"#;
+        self.query(query, (new_session_token(), person_id, expires_at), |row| {
+                Ok(Session {
+                       token: row.get("token")?,
+                       expires_at: timestamp(row, "expires_at")?,
+                   })
+            })
+            .chain_err(|| "Unable to open a session")
+    }
+
+    fn close_session(&self, token: String) -> Result<()> {
+        let query = r#"
+            DELETE FROM sessions
+            WHERE token = ?1"#;
+        let deletes = self.conn
+            .execute(query, [&token])
+            .chain_err(|| "Unable to close a session")?;
+        if deletes != 1 {
+            bail!(ErrorKind::NotFound("There was no session to close".into()))
+        }
+
+        Ok(())
+    }
+
+    fn revoke_sessions(&self, credentials: Credentials) -> Result<()> {
+        let person_id = self.authenticate(&credentials)?;
+
+        let query = r#"
+            DELETE FROM sessions
+            WHERE person_id = ?1"#;
+        self.conn
+            .execute(query, [person_id])
+            .map(|_| ())
+            .chain_err(|| "Unable to revoke sessions")
+    }
+
+    fn grant_role(&self,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()> {
+        let granter_id = self.authenticate(&credentials)?;
+        let event_id = self.role_event(eventname)?;
+        self.authorize(granter_id, Permission::ManageRoles, event_id)?;
+        let person_id = self.authorize_person(&personlogin, None)?;
+
+        self.insert_role(person_id, role, event_id)
+    }
+
+    fn revoke_role(&self,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()> {
+        let granter_id = self.authenticate(&credentials)?;
+        let event_id = self.role_event(eventname)?;
+        self.authorize(granter_id, Permission::ManageRoles, event_id)?;
+        let person_id = self.authorize_person(&personlogin, None)?;
+
+        let query = r#"
+            DELETE FROM person_roles
+            WHERE person_id = ?1
+              AND role = ?2
+              AND event_id IS ?3"#;
+        let deletes = self.conn
+            .execute(query, (person_id, role.as_str(), event_id))
+            .chain_err(|| "Unable to revoke the role")?;
+        if deletes == 0 {
+            bail!(ErrorKind::NotFound("The person has no such role".into()))
+        }
+
+        Ok(())
+    }
+
+    fn grant_event_organizer(&self,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()> {
+        let granter_id = self.authenticate(&credentials)?;
+        let event_id = self.authorize_owner(granter_id, &eventname)?;
+        let person_id = self.authorize_person(&personlogin, None)?;
+
+        self.insert_organizer(event_id, person_id, owner)
+    }
+
+    fn revoke_event_organizer(&self,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()> {
+        let granter_id = self.authenticate(&credentials)?;
+        let event_id = self.authorize_owner(granter_id, &eventname)?;
+        let person_id = self.authorize_person(&personlogin, None)?;
+
+        let query = r#"
+            SELECT is_owner FROM event_organizers
+            WHERE event_id = ?1
+              AND person_id = ?2"#;
+        let was_owner: bool = self.query(query, (event_id, person_id), |row| row.get("is_owner"))
+            .chain_err(|| "Unable to load the organizer")?
+            .into_iter()
+            .next()
+            .ok_or_else(|| ErrorKind::NotFound("The person does not organize the event".into()))?;
+
+        let query = r#"
+            DELETE FROM event_organizers
+            WHERE event_id = ?1
+              AND person_id = ?2"#;
+        self.conn
+            .execute(query, (event_id, person_id))
+            .chain_err(|| "Unable to revoke the organizer")?;
+
+        let query = r#"
+            SELECT count(*) FROM event_organizers
+            WHERE event_id = ?1
+              AND is_owner"#;
+        let owners: i64 = self.conn
+            .query_row(query, [event_id], |row| row.get(0))
+            .chain_err(|| "Unable to count the owners")?;
+        if was_owner && owners == 0 {
+            bail!(ErrorKind::Conflict("The event would have no owner".into()))
+        }
+
+        Ok(())
+    }
+}
+
+impl MigrationTarget for SqliteStorage {
+    fn migrations(&self) -> &'static [Migration] {
+        SQLITE_MIGRATIONS
+    }
+
+    fn tracked(&self) -> Result<bool> {
+        let query = r#"
+            SELECT count(*) AS present
+            FROM sqlite_master
+            WHERE type = 'table'
+              AND name = 'schema_migrations'"#;
+        let present = self.query(query, [], |row| row.get::<_, i64>("present"))
+            .chain_err(|| "Unable to look for schema_migrations")?;
+        Ok(present.first().is_some_and(|&count| count > 0))
+    }
+
+    fn applied(&self) -> Result<Vec<(String, DateTime)>> {
+        self.conn
+            .execute_batch(r#"
+                CREATE TABLE IF NOT EXISTS schema_migrations (
+                    version text PRIMARY KEY,
+                    applied_at text NOT NULL
+                        DEFAULT (strftime('%Y-%m-%d %H:%M:%S', 'now', 'localtime'))
+                )"#)
+            .chain_err(|| "Unable to create schema_migrations")?;
+
+        let query = r#"
+            SELECT version, applied_at
+            FROM schema_migrations"#;
+        self.query(query, [], |row| Ok((row.get("version")?, timestamp(row, "applied_at")?)))
+            .chain_err(|| "Unable to load applied migrations")
+    }
+
+    fn run(&self, migration: &Migration, sql: &str, revert: bool) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute_batch(sql)?;
+        let query = if revert {
+            r#"
+            DELETE FROM schema_migrations
+            WHERE version = ?1"#
+        } else {
+            r#"
+            INSERT INTO schema_migrations (version)
+            VALUES (?1)"#
+        };
+        tx.execute(query, [migration.version])
+            .chain_err(|| "Unable to record the migration")?;
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,830 @@
+use errors::*;
+
+use api::{Date, DateTime, Credentials, Session, WaitlistEntry, RatingChange, TalkReview,
+          Feedback, TalkStatusName, Room, Speaker, OrganizerOutcome, OrganizerAttempt, Role};
+use api::{AttendedTalk, UserPlan, DayPlan, BestTalk, MostPopularTalk, AbandonedTalk,
+          RecentlyAddedTalk, RejectedTalk, Proposal, FriendsTalk, FriendsEvent, RecommendedTalk,
+          FoundTalk};
+use connection::ConnectionConfig;
+use database::Connection;
+use migrate::{Migration, MigrationTarget};
+use paging::{Page, Paged};
+use sqlite::SqliteStorage;
+
+use bcrypt;
+use rand::{self, Rng};
+use rand::distributions::Alphanumeric;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// Koszt funkcji bcrypt użytej do haszowania haseł, w testach najniższy możliwy.
+#[cfg(not(test))]
+const PASSWORD_HASH_COST: u32 = bcrypt::DEFAULT_COST;
+#[cfg(test)]
+const PASSWORD_HASH_COST: u32 = 4;
+
+/// Czas ważności tokenu sesji w godzinach.
+pub const SESSION_TTL_HOURS: i32 = 24;
+
+/// Długość losowego tokenu sesji.
+pub const SESSION_TOKEN_LEN: usize = 32;
+
+/// Czas trwania referatu w minutach, jeśli nie podano końca ani długości.
+pub const DEFAULT_TALK_MINUTES: i32 = 30;
+
+/// Najdłuższy czas trwania referatu w minutach podawany jako <duration> (trzy dni).
+pub const MAX_TALK_MINUTES: i64 = 3 * 24 * 60;
+
+/// Wagi tytułu, loginu prelegenta, nazwy wydarzenia i sali w wyszukiwaniu referatów,
+/// takie jak domyślne wagi etykiet A-D w `ts_rank`.
+pub const SEARCH_WEIGHTS: [f64; 4] = [1.0, 0.4, 0.2, 0.1];
+
+/// Uprawnienie wymagane przez zapytanie. Daje je każda z ról `roles` nadana osobie
+/// dla wszystkich wydarzeń albo dla wydarzenia, którego dotyczy zapytanie.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Permission {
+    /// Rejestracja na wydarzenia, obecność, oceny, propozycje referatów i znajomi.
+    Participate,
+    /// Tworzenie wydarzeń i sal, zmiana limitu miejsc.
+    ManageEvents,
+    /// Tworzenie kont uczestników.
+    ManageAccounts,
+    /// Nadawanie i odbieranie ról.
+    ManageRoles,
+    /// Rejestracja i akceptacja referatów, lista sal.
+    ScheduleTalks,
+    /// Przeglądanie i odrzucanie propozycji referatów.
+    ReviewProposals,
+    /// Oceny i recenzje referatów, referaty porzucane przez uczestników.
+    ViewFeedback,
+    /// Cała lista oczekujących wydarzenia.
+    ViewWaitlist,
+}
+
+impl Permission {
+    /// Role dające to uprawnienie.
+    pub fn roles(&self) -> &'static [Role] {
+        use self::Permission::*;
+        match *self {
+            Participate => {
+                &[Role::Admin,
+                  Role::ProgramCommittee,
+                  Role::TrackChair,
+                  Role::Volunteer,
+                  Role::Speaker,
+                  Role::Attendee]
+            }
+            ManageEvents | ManageRoles => &[Role::Admin],
+            ManageAccounts | ViewWaitlist => &[Role::Admin, Role::Volunteer],
+            ReviewProposals => &[Role::Admin, Role::ProgramCommittee],
+            ScheduleTalks | ViewFeedback => {
+                &[Role::Admin, Role::ProgramCommittee, Role::TrackChair]
+            }
+        }
+    }
+
+    /// Czy rola o nazwie `role` daje to uprawnienie.
+    pub fn is_granted_by(&self, role: &str) -> bool {
+        self.roles().iter().any(|r| r.as_str() == role)
+    }
+
+    /// Role dające to uprawnienie jako lista napisów SQL do warunku `role IN (...)`.
+    pub fn sql_roles(&self) -> String {
+        self.roles()
+            .iter()
+            .map(|role| format!("'{}'", role.as_str()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Błąd dla osoby bez tego uprawnienia.
+    pub fn denied(&self) -> Error {
+        ErrorKind::Unauthorized(format!("requires one of the roles {}", self.role_list())).into()
+    }
+
+    /// Błąd dla osoby, która nie organizuje wydarzenia i nie ma w nim tego uprawnienia.
+    pub fn denied_in_event(&self) -> Error {
+        let message = format!("requires organizing the event or one of the roles {} in it",
+                              self.role_list());
+        ErrorKind::Unauthorized(message).into()
+    }
+
+    fn role_list(&self) -> String {
+        let roles: Vec<_> = self.roles().iter().map(Role::as_str).collect();
+        roles.join(", ")
+    }
+}
+
+/// Everything the requests need from a database. `database` implements it for a Postgres
+/// `Connection`, `sqlite` for a SQLite file, and `memory` keeps the data in memory so routes
+/// can be tested without a server.
+///
+/// Every operation behaves as described in `database`, including the kind of error it reports.
+pub trait Storage
+    where Self: Sized
+{
+    /// Connects to the storage described by `config`.
+    fn open(config: &ConnectionConfig) -> Result<Self>;
+
+    /// Brings the schema up to date.
+    fn setup(&self) -> Result<()>;
+
+    /// Fails with `validation` unless the schema is up to date, without changing anything.
+    fn check_schema(&self) -> Result<()>;
+
+    /// Opens the storage and migrates its schema, or only checks the schema
+    /// if `auto_migrate` is turned off in `config`.
+    fn connect(config: &ConnectionConfig) -> Result<Self> {
+        let storage = Self::open(config)?;
+        if config.auto_migrate.unwrap_or(true) {
+            storage.setup()?;
+        } else {
+            storage.check_schema()?;
+        }
+        Ok(storage)
+    }
+
+    /// Runs `f` so that either all of its changes are kept, or none of them if it fails.
+    fn atomically<T, F>(&self, f: F) -> Result<T> where F: FnOnce() -> Result<T>;
+
+    fn create_organizer_account(&self, newlogin: String, newpassword: String) -> Result<()>;
+
+    /// Marks the organizer secret as used, `false` if it had been used before.
+    fn use_organizer_secret(&self, secret: &str) -> Result<bool>;
+
+    fn record_organizer_attempt(&self, newlogin: &str, outcome: OrganizerOutcome) -> Result<()>;
+
+    fn organizer_attempts(&self) -> Result<Vec<OrganizerAttempt>>;
+
+    fn create_event(&self,
+                    credentials: Credentials,
+                    eventname: String,
+                    start_timestamp: DateTime,
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
+                    -> Result<()>;
+
+    fn set_event_capacity(&self,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()>;
+
+    fn create_room(&self,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()>;
+
+    fn list_rooms(&self, credentials: Credentials, venue: Option<String>) -> Result<Vec<Room>>;
+
+    fn create_user(&self,
+                   credentials: Credentials,
+                   newlogin: String,
+                   newpassword: String)
+                   -> Result<()>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn register_or_accept_talk(&self,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
+                               talk: String,
+                               title: String,
+                               start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
+                               room: String,
+                               initial_evaluation: i16,
+                               eventname: String)
+                               -> Result<()>;
+
+    fn register_user_for_event(&self, credentials: Credentials, eventname: String) -> Result<()>;
+
+    fn unregister_user_from_event(&self, credentials: Credentials, eventname: String) -> Result<()>;
+
+    fn attendance(&self, credentials: Credentials, talk: String) -> Result<()>;
+
+    fn cancel_attendance(&self, credentials: Credentials, talk: String) -> Result<()>;
+
+    fn evaluation(&self,
+                  credentials: Credentials,
+                  talk: String,
+                  rating: i16,
+                  comment: Option<String>)
+                  -> Result<()>;
+
+    fn retract_evaluation(&self, credentials: Credentials, talk: String) -> Result<()>;
+
+    fn reject_spontaneous_talk(&self, credentials: Credentials, talk: String) -> Result<()>;
+
+    fn accept_talk(&self,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn propose_spontaneous_talk(&self,
+                                credentials: Credentials,
+                                talk: String,
+                                title: String,
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
+                                -> Result<()>;
+
+    fn make_friends(&self, credentials: Credentials, login2: String) -> Result<()>;
+
+    fn unfriend(&self, credentials: Credentials, login2: String) -> Result<()>;
+
+    fn user_plan(&self, login: String, page: Page) -> Result<Paged<UserPlan>>;
+
+    fn day_plan(&self, date: Date) -> Result<Vec<DayPlan>>;
+
+    fn event_plan(&self, eventname: String) -> Result<Vec<DayPlan>>;
+
+    fn best_talks(&self,
+                  start_timestamp: DateTime,
+                  end_timestamp: DateTime,
+                  page: Page,
+                  all: bool)
+                  -> Result<Paged<BestTalk>>;
+
+    fn most_popular_talks(&self,
+                          start_timestamp: DateTime,
+                          end_timestamp: DateTime,
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>>;
+
+    fn attended_talks(&self, credentials: Credentials) -> Result<Vec<AttendedTalk>>;
+
+    fn abandoned_talks(&self,
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>>;
+
+    fn recently_added_talks(&self, page: Page) -> Result<Paged<RecentlyAddedTalk>>;
+
+    /// `query` has the `websearch_to_tsquery` syntax (`"exact phrase"`, `or`, `-excluded`)
+    /// in every database, `score` is comparable only within a single search.
+    fn search_talks(&self,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>>;
+
+    fn rejected_talks(&self, credentials: Credentials) -> Result<Vec<RejectedTalk>>;
+
+    fn proposals(&self, credentials: Credentials) -> Result<Vec<Proposal>>;
+
+    fn friends_talks(&self,
+                     credentials: Credentials,
+                     start_timestamp: DateTime,
+                     end_timestamp: DateTime,
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>>;
+
+    fn friends_events(&self,
+                      credentials: Credentials,
+                      eventname: String)
+                      -> Result<Vec<FriendsEvent>>;
+
+    fn recommended_talks(&self,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>>;
+
+    fn waitlist(&self, credentials: Credentials, eventname: String) -> Result<Vec<WaitlistEntry>>;
+
+    fn rating_history(&self, credentials: Credentials, talk: String) -> Result<Vec<RatingChange>>;
+
+    fn talk_reviews(&self, credentials: Credentials, talk: String) -> Result<Vec<TalkReview>>;
+
+    fn my_feedback(&self, credentials: Credentials) -> Result<Vec<Feedback>>;
+
+    fn open_session(&self, login: String, password: String) -> Result<Vec<Session>>;
+
+    fn close_session(&self, token: String) -> Result<()>;
+
+    fn revoke_sessions(&self, credentials: Credentials) -> Result<()>;
+
+    fn grant_role(&self,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()>;
+
+    fn revoke_role(&self,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()>;
+
+    fn grant_event_organizer(&self,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()>;
+
+    fn revoke_event_organizer(&self,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()>;
+}
+
+/// Losowy token nowej sesji.
+pub fn new_session_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SESSION_TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    bcrypt::hash(password, PASSWORD_HASH_COST).chain_err(|| "Unable to hash password")
+}
+
+/// Sprawdza <password> ze skrótem bcrypt, którego żadne konto nie ma - wywoływana dla
+/// nieistniejącego loginu, żeby odpowiedź trwała tyle samo, co przy błędnym haśle
+/// i nie zdradzała, czy login istnieje.
+pub fn verify_unknown_login(password: &str) {
+    static UNKNOWN_LOGIN_HASH: OnceLock<String> = OnceLock::new();
+    let hash = UNKNOWN_LOGIN_HASH.get_or_init(|| hash_password("").unwrap_or_default());
+    let _ = bcrypt::verify(password, hash);
+}
+
+/// Porównuje dwa ciągi bajtów w czasie zależnym tylko od ich długości, więc czas
+/// nieudanego porównania nie zdradza długości zgodnego przedrostka.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Skrót SHA-256 sekretu organizatora, szesnastkowo. Sekret pochodzi z konfiguracji,
+/// więc wystarcza skrót bez soli - zużyty sekret znajduje się jednym zapytaniem
+/// po unikalnym indeksie.
+pub fn secret_digest(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Słowa tekstu małymi literami, podzielone podobnie jak w konfiguracji `simple`
+/// wyszukiwania pełnotekstowego PostgreSQL.
+pub fn search_words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Wyraz zapytania wyszukiwania: słowo albo fraza w cudzysłowie (słowa występujące
+/// kolejno), poprzedzona `-`, jeśli nie może wystąpić.
+struct SearchTerm {
+    words: Vec<String>,
+    excluded: bool,
+}
+
+/// Zapytanie w składni `websearch_to_tsquery`: alternatywy (oddzielone słowem `or`)
+/// koniunkcji wyrazów. Nawiasy i pozostałe znaki przestankowe są pomijane.
+fn search_alternatives(query: &str) -> Vec<Vec<SearchTerm>> {
+    let mut alternatives = vec![Vec::new()];
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let excluded = c == '-';
+        let quoted = if excluded { chars.peek() == Some(&'"') } else { c == '"' };
+        if excluded && quoted {
+            chars.next();
+        }
+        let mut text = String::new();
+        if !excluded && !quoted {
+            text.push(c);
+        }
+        let ends = |c: char| c == '"' || (!quoted && c.is_whitespace());
+        while let Some(c) = chars.next_if(|&c| !ends(c)) {
+            text.push(c);
+        }
+        if quoted {
+            chars.next();
+        } else if !excluded && text.to_lowercase() == "or" {
+            alternatives.push(Vec::new());
+            continue;
+        }
+        let words = search_words(&text);
+        if !words.is_empty() {
+            alternatives.last_mut().unwrap().push(SearchTerm { words, excluded });
+        }
+    }
+    alternatives.retain(|terms| !terms.is_empty());
+    alternatives
+}
+
+/// Trafność referatu dla zapytania tam, gdzie nie ma wyszukiwania pełnotekstowego.
+/// Zapytanie ma składnię `websearch_to_tsquery` (`"dokładna fraza"`, `or`, `-wykluczone`):
+/// każdy wyraz alternatywy liczy się z wagą najważniejszego z pól `fields` (tytuł, login
+/// prelegenta, nazwa wydarzenia, sala), w którym występuje, a trafność referatu to
+/// najwyższa średnia z pasujących alternatyw. `None`, jeśli żadna alternatywa nie pasuje.
+pub fn search_rank(query: &str, fields: [&str; 4]) -> Option<f64> {
+    let fields: Vec<Vec<String>> = fields.iter().map(|field| search_words(field)).collect();
+    let weight = |term: &SearchTerm| {
+        fields.iter()
+            .zip(&SEARCH_WEIGHTS)
+            .find(|&(field, _)| field.windows(term.words.len()).any(|words| words == term.words))
+            .map(|(_, &weight)| weight)
+    };
+    search_alternatives(query)
+        .iter()
+        .filter_map(|terms| {
+            if terms.iter().any(|term| term.excluded && weight(term).is_some()) {
+                return None;
+            }
+            let included: Vec<&SearchTerm> =
+                terms.iter().filter(|term| !term.excluded).collect();
+            let total: Option<f64> = included.iter().map(|term| weight(term)).sum();
+            total.map(|total| total / included.len().max(1) as f64)
+        })
+        .fold(None, |best: Option<f64>, score| Some(best.map_or(score, |best| best.max(score))))
+}
+
+/// The database picked by the connection settings: SQLite when they name a SQLite file,
+/// see `ConnectionConfig::sqlite_path`, Postgres otherwise.
+pub enum Backend {
+    Postgres(Box<Connection>),
+    Sqlite(SqliteStorage),
+}
+
+/// Forwards a call to whichever database is in use.
+macro_rules! delegate {
+    ($self:ident . $method:ident ( $($arg:expr),* )) => {
+        match *$self {
+            Backend::Postgres(ref conn) => conn.$method($($arg),*),
+            Backend::Sqlite(ref conn) => conn.$method($($arg),*),
+        }
+    };
+}
+
+impl Storage for Backend {
+    fn open(config: &ConnectionConfig) -> Result<Backend> {
+        match config.sqlite_path()? {
+            Some(path) => SqliteStorage::open_path(&path).map(Backend::Sqlite),
+            None => Connection::open(config).map(|conn| Backend::Postgres(Box::new(conn))),
+        }
+    }
+
+    fn setup(&self) -> Result<()> {
+        delegate!(self.setup())
+    }
+
+    fn check_schema(&self) -> Result<()> {
+        delegate!(self.check_schema())
+    }
+
+    fn atomically<T, F>(&self, f: F) -> Result<T>
+        where F: FnOnce() -> Result<T>
+    {
+        delegate!(self.atomically(f))
+    }
+
+    fn create_organizer_account(&self, newlogin: String, newpassword: String) -> Result<()> {
+        delegate!(self.create_organizer_account(newlogin, newpassword))
+    }
+
+    fn use_organizer_secret(&self, secret: &str) -> Result<bool> {
+        delegate!(self.use_organizer_secret(secret))
+    }
+
+    fn record_organizer_attempt(&self, newlogin: &str, outcome: OrganizerOutcome) -> Result<()> {
+        delegate!(self.record_organizer_attempt(newlogin, outcome))
+    }
+
+    fn organizer_attempts(&self) -> Result<Vec<OrganizerAttempt>> {
+        delegate!(self.organizer_attempts())
+    }
+
+    fn create_event(&self,
+                    credentials: Credentials,
+                    eventname: String,
+                    start_timestamp: DateTime,
+                    end_timestamp: DateTime,
+                    capacity: Option<i32>)
+                    -> Result<()> {
+        delegate!(self.create_event(credentials,
+                                    eventname,
+                                    start_timestamp,
+                                    end_timestamp,
+                                    capacity))
+    }
+
+    fn set_event_capacity(&self,
+                          credentials: Credentials,
+                          eventname: String,
+                          capacity: Option<i32>)
+                          -> Result<()> {
+        delegate!(self.set_event_capacity(credentials, eventname, capacity))
+    }
+
+    fn create_room(&self,
+                   credentials: Credentials,
+                   room: String,
+                   venue: String,
+                   capacity: Option<i32>,
+                   floor: Option<i32>)
+                   -> Result<()> {
+        delegate!(self.create_room(credentials, room, venue, capacity, floor))
+    }
+
+    fn list_rooms(&self, credentials: Credentials, venue: Option<String>) -> Result<Vec<Room>> {
+        delegate!(self.list_rooms(credentials, venue))
+    }
+
+    fn create_user(&self,
+                   credentials: Credentials,
+                   newlogin: String,
+                   newpassword: String)
+                   -> Result<()> {
+        delegate!(self.create_user(credentials, newlogin, newpassword))
+    }
+
+    fn register_or_accept_talk(&self,
+                               credentials: Credentials,
+                               speakers: Vec<Speaker>,
+                               talk: String,
+                               title: String,
+                               start_timestamp: DateTime,
+                               end_timestamp: Option<DateTime>,
+                               room: String,
+                               initial_evaluation: i16,
+                               eventname: String)
+                               -> Result<()> {
+        delegate!(self.register_or_accept_talk(credentials,
+                                               speakers,
+                                               talk,
+                                               title,
+                                               start_timestamp,
+                                               end_timestamp,
+                                               room,
+                                               initial_evaluation,
+                                               eventname))
+    }
+
+    fn register_user_for_event(&self, credentials: Credentials, eventname: String) -> Result<()> {
+        delegate!(self.register_user_for_event(credentials, eventname))
+    }
+
+    fn unregister_user_from_event(&self,
+                                  credentials: Credentials,
+                                  eventname: String)
+                                  -> Result<()> {
+        delegate!(self.unregister_user_from_event(credentials, eventname))
+    }
+
+    fn attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        delegate!(self.attendance(credentials, talk))
+    }
+
+    fn cancel_attendance(&self, credentials: Credentials, talk: String) -> Result<()> {
+        delegate!(self.cancel_attendance(credentials, talk))
+    }
+
+    fn evaluation(&self,
+                  credentials: Credentials,
+                  talk: String,
+                  rating: i16,
+                  comment: Option<String>)
+                  -> Result<()> {
+        delegate!(self.evaluation(credentials, talk, rating, comment))
+    }
+
+    fn retract_evaluation(&self, credentials: Credentials, talk: String) -> Result<()> {
+        delegate!(self.retract_evaluation(credentials, talk))
+    }
+
+    fn reject_spontaneous_talk(&self, credentials: Credentials, talk: String) -> Result<()> {
+        delegate!(self.reject_spontaneous_talk(credentials, talk))
+    }
+
+    fn accept_talk(&self,
+                   credentials: Credentials,
+                   talk: String,
+                   room: Option<String>,
+                   initial_evaluation: Option<i16>)
+                   -> Result<()> {
+        delegate!(self.accept_talk(credentials, talk, room, initial_evaluation))
+    }
+
+    fn propose_spontaneous_talk(&self,
+                                credentials: Credentials,
+                                talk: String,
+                                title: String,
+                                start_timestamp: DateTime,
+                                end_timestamp: Option<DateTime>,
+                                speakers: Vec<Speaker>,
+                                eventname: Option<String>,
+                                room: Option<String>)
+                                -> Result<()> {
+        delegate!(self.propose_spontaneous_talk(credentials,
+                                                talk,
+                                                title,
+                                                start_timestamp,
+                                                end_timestamp,
+                                                speakers,
+                                                eventname,
+                                                room))
+    }
+
+    fn make_friends(&self, credentials: Credentials, login2: String) -> Result<()> {
+        delegate!(self.make_friends(credentials, login2))
+    }
+
+    fn unfriend(&self, credentials: Credentials, login2: String) -> Result<()> {
+        delegate!(self.unfriend(credentials, login2))
+    }
+
+    fn user_plan(&self, login: String, page: Page) -> Result<Paged<UserPlan>> {
+        delegate!(self.user_plan(login, page))
+    }
+
+    fn day_plan(&self, date: Date) -> Result<Vec<DayPlan>> {
+        delegate!(self.day_plan(date))
+    }
+
+    fn event_plan(&self, eventname: String) -> Result<Vec<DayPlan>> {
+        delegate!(self.event_plan(eventname))
+    }
+
+    fn best_talks(&self,
+                  start_timestamp: DateTime,
+                  end_timestamp: DateTime,
+                  page: Page,
+                  all: bool)
+                  -> Result<Paged<BestTalk>> {
+        delegate!(self.best_talks(start_timestamp, end_timestamp, page, all))
+    }
+
+    fn most_popular_talks(&self,
+                          start_timestamp: DateTime,
+                          end_timestamp: DateTime,
+                          page: Page)
+                          -> Result<Paged<MostPopularTalk>> {
+        delegate!(self.most_popular_talks(start_timestamp, end_timestamp, page))
+    }
+
+    fn attended_talks(&self, credentials: Credentials) -> Result<Vec<AttendedTalk>> {
+        delegate!(self.attended_talks(credentials))
+    }
+
+    fn abandoned_talks(&self,
+                       credentials: Credentials,
+                       page: Page)
+                       -> Result<Paged<AbandonedTalk>> {
+        delegate!(self.abandoned_talks(credentials, page))
+    }
+
+    fn recently_added_talks(&self, page: Page) -> Result<Paged<RecentlyAddedTalk>> {
+        delegate!(self.recently_added_talks(page))
+    }
+
+    fn search_talks(&self,
+                    credentials: Option<Credentials>,
+                    query: String,
+                    start_timestamp: Option<DateTime>,
+                    end_timestamp: Option<DateTime>,
+                    status: TalkStatusName,
+                    page: Page)
+                    -> Result<Paged<FoundTalk>> {
+        delegate!(self.search_talks(credentials,
+                                    query,
+                                    start_timestamp,
+                                    end_timestamp,
+                                    status,
+                                    page))
+    }
+
+    fn rejected_talks(&self, credentials: Credentials) -> Result<Vec<RejectedTalk>> {
+        delegate!(self.rejected_talks(credentials))
+    }
+
+    fn proposals(&self, credentials: Credentials) -> Result<Vec<Proposal>> {
+        delegate!(self.proposals(credentials))
+    }
+
+    fn friends_talks(&self,
+                     credentials: Credentials,
+                     start_timestamp: DateTime,
+                     end_timestamp: DateTime,
+                     page: Page)
+                     -> Result<Paged<FriendsTalk>> {
+        delegate!(self.friends_talks(credentials, start_timestamp, end_timestamp, page))
+    }
+
+    fn friends_events(&self,
+                      credentials: Credentials,
+                      eventname: String)
+                      -> Result<Vec<FriendsEvent>> {
+        delegate!(self.friends_events(credentials, eventname))
+    }
+
+    fn recommended_talks(&self,
+                         credentials: Credentials,
+                         start_timestamp: DateTime,
+                         end_timestamp: DateTime,
+                         page: Page)
+                         -> Result<Paged<RecommendedTalk>> {
+        delegate!(self.recommended_talks(credentials, start_timestamp, end_timestamp, page))
+    }
+
+    fn waitlist(&self, credentials: Credentials, eventname: String) -> Result<Vec<WaitlistEntry>> {
+        delegate!(self.waitlist(credentials, eventname))
+    }
+
+    fn rating_history(&self, credentials: Credentials, talk: String) -> Result<Vec<RatingChange>> {
+        delegate!(self.rating_history(credentials, talk))
+    }
+
+    fn talk_reviews(&self, credentials: Credentials, talk: String) -> Result<Vec<TalkReview>> {
+        delegate!(self.talk_reviews(credentials, talk))
+    }
+
+    fn my_feedback(&self, credentials: Credentials) -> Result<Vec<Feedback>> {
+        delegate!(self.my_feedback(credentials))
+    }
+
+    fn open_session(&self, login: String, password: String) -> Result<Vec<Session>> {
+        delegate!(self.open_session(login, password))
+    }
+
+    fn close_session(&self, token: String) -> Result<()> {
+        delegate!(self.close_session(token))
+    }
+
+    fn revoke_sessions(&self, credentials: Credentials) -> Result<()> {
+        delegate!(self.revoke_sessions(credentials))
+    }
+
+    fn grant_role(&self,
+                  credentials: Credentials,
+                  personlogin: String,
+                  role: Role,
+                  eventname: Option<String>)
+                  -> Result<()> {
+        delegate!(self.grant_role(credentials, personlogin, role, eventname))
+    }
+
+    fn revoke_role(&self,
+                   credentials: Credentials,
+                   personlogin: String,
+                   role: Role,
+                   eventname: Option<String>)
+                   -> Result<()> {
+        delegate!(self.revoke_role(credentials, personlogin, role, eventname))
+    }
+
+    fn grant_event_organizer(&self,
+                             credentials: Credentials,
+                             personlogin: String,
+                             eventname: String,
+                             owner: bool)
+                             -> Result<()> {
+        delegate!(self.grant_event_organizer(credentials, personlogin, eventname, owner))
+    }
+
+    fn revoke_event_organizer(&self,
+                              credentials: Credentials,
+                              personlogin: String,
+                              eventname: String)
+                              -> Result<()> {
+        delegate!(self.revoke_event_organizer(credentials, personlogin, eventname))
+    }
+}
+
+impl MigrationTarget for Backend {
+    fn migrations(&self) -> &'static [Migration] {
+        delegate!(self.migrations())
+    }
+
+    fn tracked(&self) -> Result<bool> {
+        delegate!(self.tracked())
+    }
+
+    fn applied(&self) -> Result<Vec<(String, DateTime)>> {
+        delegate!(self.applied())
+    }
+
+    fn run(&self, migration: &Migration, sql: &str, revert: bool) -> Result<()> {
+        delegate!(self.run(migration, sql, revert))
+    }
+}
@@ -0,0 +1,424 @@
+use errors::*;
+use api::{Response, ResponseInfo};
+
+use serde::ser::{self, Impossible, Serialize, SerializeSeq, SerializeStruct, Serializer};
+use serde_json::{self, Value};
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// How the data of a response is written out.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    Json,
+    /// RFC 4180, with a header row.
+    Csv,
+    /// Tab-separated, with a header row; tabs, newlines and backslashes are escaped as `\t` etc.
+    Tsv,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s {
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => bail!(ErrorKind::Validation(format!("unknown output format `{}`", s))),
+        }
+    }
+}
+
+/// Renders a list in the response as a table in `format`, a row per element with columns
+/// in the order of the fields. Other responses, e.g. the calendar of `export_ical`,
+/// are left as they are. A page keeps its `next_cursor`.
+pub fn tabulate(response: Response, format: OutputFormat) -> Result<Response> {
+    Ok(match response {
+           Response::Ok(info) => Response::Ok(tabulate_info(info, format)?),
+           Response::Page(info, next_cursor) => {
+               Response::Page(tabulate_info(info, format)?, next_cursor)
+           }
+           response => response,
+       })
+}
+
+fn tabulate_info(info: ResponseInfo, format: OutputFormat) -> Result<ResponseInfo> {
+    let (columns, rows) = match (format, info.columns(), info.serialize(Table)) {
+        (OutputFormat::Json, _, _) | (_, None, _) | (_, _, Err(NotATable)) => return Ok(info),
+        (_, Some(columns), Ok(rows)) => (columns, rows),
+    };
+    check_columns(columns, &rows)?;
+    Ok(match format {
+           OutputFormat::Csv => ResponseInfo::Csv(render(columns, &rows, ",", "\r\n", csv_field)),
+           OutputFormat::Tsv => ResponseInfo::Tsv(render(columns, &rows, "\t", "\n", tsv_field)),
+           OutputFormat::Json => unreachable!(),
+       })
+}
+
+type Row = Vec<(&'static str, String)>;
+
+/// `ResponseInfo::columns` is kept by hand, so a list whose fields differ from it
+/// fails instead of getting a wrong header.
+fn check_columns(columns: &[&str], rows: &[Row]) -> Result<()> {
+    for row in rows {
+        if !row.iter().map(|&(name, _)| name).eq(columns.iter().cloned()) {
+            bail!("columns {:?} don't match the fields of {:?}", columns, row)
+        }
+    }
+    Ok(())
+}
+
+/// The header comes from the columns of the response, so an empty list is a lone header.
+fn render(columns: &[&str],
+          rows: &[Row],
+          separator: &str,
+          newline: &str,
+          field: fn(&str) -> String)
+          -> String {
+    let line = |values: Vec<&str>| {
+        let fields: Vec<String> = values.into_iter().map(field).collect();
+        fields.join(separator) + newline
+    };
+
+    let mut table = line(columns.to_vec());
+    for row in rows {
+        table += &line(row.iter().map(|(_, value)| &value[..]).collect());
+    }
+    table
+}
+
+/// Spreadsheets run a cell starting with one of these as a formula, so such cells get
+/// a leading `'`, unless they are plain numbers like `-1`.
+fn neutralize(value: &str) -> Cow<'_, str> {
+    let formula = value.starts_with(['=', '+', '-', '@', '\t', '\r']) &&
+                  value.parse::<f64>().is_err();
+    if formula {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    let value = neutralize(value);
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.into_owned()
+    }
+}
+
+fn tsv_field(value: &str) -> String {
+    neutralize(value)
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Cells are the JSON values of the fields, without quotes around strings;
+/// a missing value is an empty cell and a list, e.g. the speakers of a talk, stays JSON.
+fn cell<T: ?Sized + Serialize>(value: &T) -> Fallible<String> {
+    match serde_json::to_value(value).map_err(|_| NotATable)? {
+        Value::Null => Ok(String::new()),
+        Value::String(s) => Ok(s),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Number(n) => Ok(n.to_string()),
+        value @ Value::Array(_) | value @ Value::Object(_) => Ok(value.to_string()),
+    }
+}
+
+type Fallible<T> = ::std::result::Result<T, NotATable>;
+
+/// The response holds something else than a list of flat structs.
+#[derive(Debug)]
+struct NotATable;
+
+impl fmt::Display for NotATable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "not a list of rows")
+    }
+}
+
+impl ::std::error::Error for NotATable {}
+
+impl ser::Error for NotATable {
+    fn custom<T: fmt::Display>(_: T) -> NotATable {
+        NotATable
+    }
+}
+
+/// Every method of `Serializer` but the ones given, failing with `NotATable`.
+macro_rules! not_a_table {
+    ($($method:ident($($typ:ty),*) -> $ok:ty;)*) => {
+        $(
+            fn $method(self, $(_: $typ),*) -> Fallible<$ok> {
+                Err(NotATable)
+            }
+        )*
+    };
+    (@all) => {
+        not_a_table! {
+            serialize_bool(bool) -> Self::Ok;
+            serialize_i8(i8) -> Self::Ok;
+            serialize_i16(i16) -> Self::Ok;
+            serialize_i32(i32) -> Self::Ok;
+            serialize_i64(i64) -> Self::Ok;
+            serialize_u8(u8) -> Self::Ok;
+            serialize_u16(u16) -> Self::Ok;
+            serialize_u32(u32) -> Self::Ok;
+            serialize_u64(u64) -> Self::Ok;
+            serialize_f32(f32) -> Self::Ok;
+            serialize_f64(f64) -> Self::Ok;
+            serialize_char(char) -> Self::Ok;
+            serialize_str(&str) -> Self::Ok;
+            serialize_bytes(&[u8]) -> Self::Ok;
+            serialize_none() -> Self::Ok;
+            serialize_unit() -> Self::Ok;
+            serialize_unit_struct(&'static str) -> Self::Ok;
+            serialize_unit_variant(&'static str, u32, &'static str) -> Self::Ok;
+            serialize_tuple(usize) -> Self::SerializeTuple;
+            serialize_tuple_struct(&'static str, usize) -> Self::SerializeTupleStruct;
+            serialize_tuple_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeTupleVariant;
+            serialize_map(Option<usize>) -> Self::SerializeMap;
+            serialize_struct_variant(&'static str, u32, &'static str, usize)
+                -> Self::SerializeStructVariant;
+        }
+
+        fn serialize_some<T: ?Sized + Serialize>(self, _: &T) -> Fallible<Self::Ok> {
+            Err(NotATable)
+        }
+
+        fn serialize_newtype_struct<T: ?Sized + Serialize>(self,
+                                                           _: &'static str,
+                                                           _: &T)
+                                                           -> Fallible<Self::Ok> {
+            Err(NotATable)
+        }
+
+        fn serialize_newtype_variant<T: ?Sized + Serialize>(self,
+                                                            _: &'static str,
+                                                            _: u32,
+                                                            _: &'static str,
+                                                            _: &T)
+                                                            -> Fallible<Self::Ok> {
+            Err(NotATable)
+        }
+    };
+}
+
+/// Accepts only a sequence, of rows.
+struct Table;
+
+impl Serializer for Table {
+    type Ok = Vec<Row>;
+    type Error = NotATable;
+    type SerializeSeq = Rows;
+    type SerializeTuple = Impossible<Vec<Row>, NotATable>;
+    type SerializeTupleStruct = Impossible<Vec<Row>, NotATable>;
+    type SerializeTupleVariant = Impossible<Vec<Row>, NotATable>;
+    type SerializeMap = Impossible<Vec<Row>, NotATable>;
+    type SerializeStruct = Impossible<Vec<Row>, NotATable>;
+    type SerializeStructVariant = Impossible<Vec<Row>, NotATable>;
+
+    not_a_table!(@all);
+
+    fn serialize_seq(self, len: Option<usize>) -> Fallible<Rows> {
+        Ok(Rows(Vec::with_capacity(len.unwrap_or(0))))
+    }
+
+    fn serialize_struct(self,
+                        _: &'static str,
+                        _: usize)
+                        -> Fallible<Self::SerializeStruct> {
+        Err(NotATable)
+    }
+}
+
+struct Rows(Vec<Row>);
+
+impl SerializeSeq for Rows {
+    type Ok = Vec<Row>;
+    type Error = NotATable;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self,
+                                                value: &T)
+                                                -> Fallible<()> {
+        self.0.push(value.serialize(RowSerializer)?);
+        Ok(())
+    }
+
+    fn end(self) -> Fallible<Vec<Row>> {
+        Ok(self.0)
+    }
+}
+
+/// Accepts only a struct, whose fields become the cells of a row.
+struct RowSerializer;
+
+impl Serializer for RowSerializer {
+    type Ok = Row;
+    type Error = NotATable;
+    type SerializeSeq = Impossible<Row, NotATable>;
+    type SerializeTuple = Impossible<Row, NotATable>;
+    type SerializeTupleStruct = Impossible<Row, NotATable>;
+    type SerializeTupleVariant = Impossible<Row, NotATable>;
+    type SerializeMap = Impossible<Row, NotATable>;
+    type SerializeStruct = Cells;
+    type SerializeStructVariant = Impossible<Row, NotATable>;
+
+    not_a_table!(@all);
+
+    fn serialize_seq(self, _: Option<usize>) -> Fallible<Self::SerializeSeq> {
+        Err(NotATable)
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Fallible<Cells> {
+        Ok(Cells(Vec::with_capacity(len)))
+    }
+}
+
+struct Cells(Row);
+
+impl SerializeStruct for Cells {
+    type Ok = Row;
+    type Error = NotATable;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self,
+                                              key: &'static str,
+                                              value: &T)
+                                              -> Fallible<()> {
+        self.0.push((key, cell(value)?));
+        Ok(())
+    }
+
+    fn end(self) -> Fallible<Row> {
+        Ok(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use api::{DateTime, Proposal, Speaker, SpeakerRole, TalkReview, WaitlistEntry};
+
+    fn table(response: Result<Response>) -> ResponseInfo {
+        match response.unwrap() {
+            Response::Ok(info) => info,
+            Response::Page(..) | Response::NotImplemented => panic!("unexpected response"),
+        }
+    }
+
+    #[test]
+    fn renders_csv_with_a_header_in_field_order() {
+        let waitlist = vec![WaitlistEntry {
+                                login: "o'neil, jr".to_owned(),
+                                eventname: "E".to_owned(),
+                                position: 1,
+                            }];
+        let response = Response::Ok(ResponseInfo::Waitlist(waitlist));
+        assert_eq!(table(tabulate(response, OutputFormat::Csv)),
+                   ResponseInfo::Csv("login,eventname,position\r\n\"o'neil, jr\",E,1\r\n".into()));
+    }
+
+    #[test]
+    fn renders_tsv_with_empty_cells_for_missing_values() {
+        let reviews = vec![TalkReview {
+                               login: "u2".to_owned(),
+                               rating: 8,
+                               comment: Some("good\tpace\nnice".to_owned()),
+                               attended: true,
+                           },
+                           TalkReview {
+                               login: "u3".to_owned(),
+                               rating: 4,
+                               comment: None,
+                               attended: false,
+                           }];
+        let response = Response::Ok(ResponseInfo::TalkReviews(reviews));
+        assert_eq!(table(tabulate(response, OutputFormat::Tsv)),
+                   ResponseInfo::Tsv("login\trating\tcomment\tattended\n\
+                                      u2\t8\tgood\\tpace\\nnice\ttrue\n\
+                                      u3\t4\t\tfalse\n"
+                                             .into()));
+    }
+
+    #[test]
+    fn rejects_rows_that_differ_from_the_columns() {
+        let rows = vec![vec![("login", "u1".to_owned()), ("eventname", "E".to_owned())]];
+        assert!(check_columns(&["login", "eventname"], &rows).is_ok());
+        assert!(check_columns(&["login", "position"], &rows).is_err());
+        assert!(check_columns(&["login"], &rows).is_err());
+    }
+
+    #[test]
+    fn neutralizes_formulas() {
+        let reviews = vec![TalkReview {
+                               login: "u2".to_owned(),
+                               rating: 8,
+                               comment: Some("=HYPERLINK(\"http://x\", \"a,b\")".to_owned()),
+                               attended: true,
+                           },
+                           TalkReview {
+                               login: "@u3".to_owned(),
+                               rating: 4,
+                               comment: Some("-1".to_owned()),
+                               attended: false,
+                           }];
+        let response = Response::Ok(ResponseInfo::TalkReviews(reviews));
+        assert_eq!(table(tabulate(response, OutputFormat::Csv)),
+                   ResponseInfo::Csv("login,rating,comment,attended\r\n\
+                                      u2,8,\"'=HYPERLINK(\"\"http://x\"\", \"\"a,b\"\")\",true\r\n\
+                                      '@u3,4,-1,false\r\n"
+                                             .into()));
+    }
+
+    #[test]
+    fn renders_nested_lists_as_json_cells() {
+        let at = DateTime::parse_from_str("2030-01-02 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        let proposals = vec![Proposal {
+                                 talk: "t1".to_owned(),
+                                 speakerlogin: "u1".to_owned(),
+                                 start_timestamp: at,
+                                 end_timestamp: at,
+                                 title: "T".to_owned(),
+                                 speakers: vec![Speaker {
+                                                    login: "u1".to_owned(),
+                                                    role: SpeakerRole::Primary,
+                                                }],
+                             }];
+        let response = Response::Ok(ResponseInfo::Proposals(proposals));
+        assert_eq!(table(tabulate(response, OutputFormat::Tsv)),
+                   ResponseInfo::Tsv("talk\tspeakerlogin\tstart_timestamp\tend_timestamp\ttitle\t\
+                                      speakers\n\
+                                      t1\tu1\t2030-01-02 10:00:00\t2030-01-02 10:00:00\tT\t\
+                                      [{\"login\":\"u1\",\"role\":\"primary\"}]\n"
+                                             .into()));
+    }
+
+    #[test]
+    fn leaves_other_responses_alone() {
+        let ics = ResponseInfo::Ical("BEGIN:VCALENDAR".to_owned());
+        assert_eq!(table(tabulate(Response::Ok(ics), OutputFormat::Csv)),
+                   ResponseInfo::Ical("BEGIN:VCALENDAR".to_owned()));
+        assert_eq!(table(tabulate(Response::Ok(ResponseInfo::Empty), OutputFormat::Tsv)),
+                   ResponseInfo::Empty);
+    }
+
+    #[test]
+    fn renders_a_header_for_an_empty_list() {
+        assert_eq!(table(tabulate(Response::Ok(ResponseInfo::Waitlist(vec![])),
+                                  OutputFormat::Csv)),
+                   ResponseInfo::Csv("login,eventname,position\r\n".into()));
+        let page = Response::Page(ResponseInfo::Rooms(vec![]), None);
+        match tabulate(page, OutputFormat::Tsv).unwrap() {
+            Response::Page(info, None) => {
+                assert_eq!(info, ResponseInfo::Tsv("room\tvenue\tcapacity\tfloor\n".into()))
+            }
+            _ => panic!("unexpected response"),
+        }
+    }
+}